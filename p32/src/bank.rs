@@ -1,11 +1,104 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
 use std::fmt;
 
+pub mod access;
+pub mod account;
+pub mod account_id;
+pub mod clearing;
+pub mod concurrent;
+pub mod currency;
+pub mod error;
+pub mod events;
+pub mod general_ledger;
+pub mod import;
+pub mod interest;
+pub mod ledger;
+pub mod loan;
+pub mod merge;
+pub mod money;
+pub mod overdraft;
+pub mod rounding;
+pub mod rules;
+pub mod snapshot;
+pub mod standing_order;
+pub mod statement;
+pub mod storage;
+
+pub use access::{Action, Denial, Principal, Role, Session};
+pub use account::{AccountStatus, KycStatus};
+pub use account_id::AccountId;
+pub use clearing::{
+    ClearingHouse, InterbankPayment, NetPosition, Obligation, PaymentStatus, SettlementReport,
+};
+pub use concurrent::ConcurrentBank;
+pub use currency::{Currency, ExchangeRates};
+pub use error::BankError;
+pub use events::{AccountChange, BankEvent, Delivery};
+pub use general_ledger::{GlAccount, JournalEntry, Posting, Side, TrialBalance, TrialBalanceRow};
+pub use import::{ImportFailure, ImportReport};
+pub use interest::{Compounding, DayCount, InterestConfig, Tier};
+pub use ledger::{EntryKind, Ledger, LedgerEntry};
+pub use loan::{Amortization, Installment, InstallmentOutcome, InstallmentRecord, Loan};
+pub use merge::{
+    ConflictPolicy, CreditLinePolicy, InterestPolicy, MergeAction, MergePlan, MergePolicy,
+    RateChange, UserMerge,
+};
+pub use money::Money;
+pub use overdraft::{OverdraftFee, OverdraftPolicy, SoftOverdraft};
+pub use rounding::Rounding;
+pub use rules::{
+    Blocklist, CustomRule, DailyLimit, MaxTransferAmount, Rejection, RejectionReason,
+    TransferCheck, TransferRule, Velocity,
+};
+pub use snapshot::{DEFAULT_UNDO_LIMIT, Snapshot};
+pub use standing_order::{
+    PaymentOutcome, PaymentRecord, PendingRetry, RetryPolicy, Schedule, StandingOrder,
+};
+pub use statement::{BankReport, CreditUsage, Movement, Statement};
+pub use storage::{StorageError, StorageFormat};
+
+use events::EventBus;
+use snapshot::History;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct User {
+    // Assigned when the account is opened; `Bank::users` is keyed by it.
+    #[serde(default)]
+    pub id: AccountId,
+    // Display name of the account holder. Several accounts, and several
+    // customers, can share a name.
     pub name: String,
-    pub credit_line: u64,
-    pub balance: i64,
+    // Customer number, shared by all of one customer's accounts.
+    #[serde(default)]
+    pub customer: u64,
+    pub credit_line: Money,
+    pub balance: Money,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default)]
+    pub status: AccountStatus,
+    #[serde(default)]
+    pub kyc: KycStatus,
+    #[serde(default)]
+    pub overdraft: OverdraftPolicy,
+}
+
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct StagedTransfer {
+    pub(crate) from_balance: Money,
+    pub(crate) to_balance: Money,
+    pub(crate) credited: Money,
+    // Overdraft fee charged to the sender, already taken out of
+    // `from_balance`.
+    pub(crate) fee: Money,
 }
 
 pub struct Bank {
@@ -13,6 +106,18 @@ pub struct Bank {
     pub name: String,
     pub credit_interest: u64,
     pub debit_interest: u64,
+    pub currency: Currency,
+    pub exchange_rates: ExchangeRates,
+    pub fx_rounding: Rounding,
+    pub interest_config: InterestConfig,
+    interest_carry: HashMap<String, i64>,
+    ledger: Ledger,
+    standing_orders: Vec<StandingOrder>,
+    loans: Vec<Loan>,
+    rules: Vec<Box<dyn TransferRule>>,
+    events: EventBus,
+    history: History,
+    denials: Vec<Denial>,
 }
 
 // For User
@@ -21,8 +126,14 @@ impl fmt::Display for User {
         write!(
             f,
             "User: {}, Credit Line: {}, Balance: {}",
-            self.name, self.credit_line, self.balance
-        )
+            self.name,
+            self.credit_line.format(self.currency),
+            self.balance.format(self.currency)
+        )?;
+        if !self.is_active() {
+            write!(f, " ({})", self.status)?;
+        }
+        Ok(())
     }
 }
 
@@ -37,6 +148,95 @@ impl fmt::Display for Bank {
     }
 }
 
+impl User {
+    pub fn new(name: String, credit_line: Money, balance: Money) -> Self {
+        User {
+            id: AccountId::default(),
+            name,
+            customer: 0,
+            credit_line,
+            balance,
+            currency: Currency::default(),
+            status: AccountStatus::default(),
+            kyc: KycStatus::default(),
+            overdraft: OverdraftPolicy::default(),
+        }
+    }
+
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    // Opens the account for an existing customer instead of a new one.
+    pub fn for_customer(mut self, customer: u64) -> Self {
+        self.customer = customer;
+        self
+    }
+
+    // Balance left after taking `amount` out of `balance`, as long as it stays
+    // within the credit line and overdraft limit.
+    pub(crate) fn checked_debit(&self, balance: Money, amount: Money) -> Result<Money, BankError> {
+        if amount.is_negative() {
+            return Err(BankError::NegativeAmount(amount));
+        }
+
+        let Some(new_balance) = balance.checked_sub(amount) else {
+            return Err(BankError::TransferOverflow(self.name.clone()));
+        };
+
+        if new_balance < self.debit_floor() {
+            return Err(BankError::InsufficientCredit {
+                user: self.name.clone(),
+                amount,
+            });
+        }
+
+        Ok(new_balance)
+    }
+
+    // Signed interest for one accrual period: negative balances are charged
+    // `credit_interest`, positive ones earn `debit_interest` (both in bp).
+    pub(crate) fn accrued_interest(
+        &self,
+        credit_interest: u64,
+        debit_interest: u64,
+    ) -> Result<Money, BankError> {
+        let rate = match self.balance.minor().cmp(&0) {
+            std::cmp::Ordering::Less => credit_interest,
+            std::cmp::Ordering::Greater => debit_interest,
+            std::cmp::Ordering::Equal => return Ok(Money::ZERO),
+        };
+
+        let interest = match self.balance.minor().unsigned_abs().checked_mul(rate) {
+            Some(result) => result / 10000,
+            None => return Err(BankError::InterestOverflow(self.name.clone())),
+        };
+
+        let interest_i64 =
+            i64::try_from(interest).map_err(|_| BankError::InterestOverflow(self.name.clone()))?;
+
+        let interest = if self.balance.is_negative() {
+            Money::from_minor(-interest_i64)
+        } else {
+            Money::from_minor(interest_i64)
+        };
+        interest
+            .checked_add(self.overdraft_penalty()?)
+            .ok_or_else(|| BankError::InterestOverflow(self.name.clone()))
+    }
+}
+
+impl Transfer {
+    pub fn new(from: &str, to: &str, amount: Money) -> Self {
+        Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        }
+    }
+}
+
 impl Bank {
     pub fn new(name: String, credit_interest: u64, debit_interest: u64) -> Self {
         Bank {
@@ -44,39 +244,71 @@ impl Bank {
             users: HashMap::new(),
             credit_interest,
             debit_interest,
+            currency: Currency::default(),
+            exchange_rates: ExchangeRates::new(),
+            fx_rounding: Rounding::default(),
+            interest_config: InterestConfig::default(),
+            interest_carry: HashMap::new(),
+            ledger: Ledger::new(),
+            standing_orders: Vec::new(),
+            loans: Vec::new(),
+            rules: Vec::new(),
+            events: EventBus::default(),
+            history: History::default(),
+            denials: Vec::new(),
         }
     }
 
-    pub fn add_user(&mut self, user: User) -> Result<(), String> {
-        if self.users.contains_key(&user.name) {
-            return Err(format!("User '{}' already exists", user.name));
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    // Opens an account for a new customer whose name isn't taken yet, so
+    // that name-based calls keep finding exactly one account. Use
+    // `open_account` for customers sharing a name or holding several
+    // accounts.
+    pub fn add_user(&mut self, user: User) -> Result<AccountId, BankError> {
+        if self
+            .users
+            .values()
+            .any(|existing| existing.name == user.name)
+        {
+            return Err(BankError::DuplicateUser(user.name));
         }
+        self.open_account(user.for_customer(0))
+    }
 
-        self.users.insert(user.name.clone(), user);
-        Ok(())
+    // Liabilities and assets in the bank's own currency. Fails like
+    // `calc_balance_in` when an account's currency has no rate to it.
+    pub fn calc_balance(&self) -> Result<(Money, Money), BankError> {
+        self.calc_balance_in(self.currency)
     }
 
-    pub fn calc_balance(&self) -> (u64, u64) {
-        let mut liability: u64 = 0;
-        let mut asset: u64 = 0;
+    // Liabilities and assets expressed in `currency`, converting every
+    // account with the bank's exchange rates.
+    pub fn calc_balance_in(&self, currency: Currency) -> Result<(Money, Money), BankError> {
+        let mut liability = Money::ZERO;
+        let mut asset = Money::ZERO;
 
         for user in self.users.values() {
-            match user.balance.cmp(&0) {
-                std::cmp::Ordering::Greater => {
-                    let positive_balance = u64::try_from(user.balance)
-                        .expect("Positive balance conversion to u64 failed");
+            let balance = self.exchange_rates.convert(
+                user.balance,
+                user.currency,
+                currency,
+                self.fx_rounding,
+            )?;
 
+            match balance.cmp(&Money::ZERO) {
+                std::cmp::Ordering::Greater => {
                     liability = liability
-                        .checked_add(positive_balance)
-                        .expect("Liability calculation overflow");
+                        .checked_add(balance)
+                        .ok_or(BankError::BalanceOverflow)?;
                 }
                 std::cmp::Ordering::Less => {
-                    let negative_balance = u64::try_from(user.balance.abs())
-                        .expect("Negative balance conversion to u64 failed");
-
-                    asset = asset
-                        .checked_add(negative_balance)
-                        .expect("Asset calculation overflow");
+                    asset = balance
+                        .checked_neg()
+                        .and_then(|balance| asset.checked_add(balance))
+                        .ok_or(BankError::BalanceOverflow)?;
                 }
                 std::cmp::Ordering::Equal => {
                     // Zero balance, no effect on liability or asset
@@ -84,167 +316,267 @@ impl Bank {
             }
         }
 
-        (liability, asset)
+        Ok((liability, asset))
     }
 
-    pub fn transfer_funds(&mut self, from: &str, to: &str, amount: u64) -> Result<(), String> {
-        if !self.users.contains_key(from) {
-            return Err(format!("User '{}' not found", from));
-        }
-
-        if !self.users.contains_key(to) {
-            return Err(format!("User '{}' not found", to));
+    // `from` and `to` are account ids or unambiguous names.
+    pub fn transfer_funds(&mut self, from: &str, to: &str, amount: Money) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot();
+        match self.try_transfer(from, to, amount) {
+            Ok(event) => {
+                self.checkpoint(snapshot);
+                self.events.publish(event);
+                Ok(())
+            }
+            Err(error) => {
+                self.events
+                    .publish(BankEvent::rejected(from, to, amount, &error));
+                Err(error)
+            }
         }
+    }
 
-        let amount_i64: i64 = amount
-            .try_into()
-            .map_err(|_| "Amount too large to process".to_string())?;
+    fn try_transfer(
+        &mut self,
+        from: &str,
+        to: &str,
+        amount: Money,
+    ) -> Result<BankEvent, BankError> {
+        let from = self.account_id(from)?;
+        let to = self.account_id(to)?;
+        self.check_transfer_rules(from.as_str(), to.as_str(), amount, Utc::now(), &[])?;
 
-        {
-            let from_user = self.users.get(from).unwrap();
+        let mut staged = HashMap::new();
+        let before = (
+            self.users[from.as_str()].balance,
+            self.users[to.as_str()].balance,
+        );
+        let staged_transfer =
+            self.stage_transfer(&mut staged, from.as_str(), to.as_str(), amount)?;
 
-            let Some(new_balance) = from_user.balance.checked_sub(amount_i64) else {
-                return Err("Arithmetic overflow in balance calculation".to_string());
-            };
+        self.commit_staged(staged);
+        let entry_id =
+            self.ledger
+                .record_transfer(from.as_str(), to.as_str(), amount, staged_transfer);
 
-            let credit_line_i64: i64 = from_user
-                .credit_line
-                .try_into()
-                .map_err(|_| "Credit line too large to process".to_string())?;
+        Ok(BankEvent::transfer(
+            entry_id,
+            (&self.users[from.as_str()], &self.users[to.as_str()]),
+            amount,
+            before,
+            &staged_transfer,
+        ))
+    }
 
-            if new_balance < -credit_line_i64 {
-                return Err("Insufficient credit line".to_string());
-            }
-        }
+    pub fn transfer_batch(&mut self, transfers: &[Transfer]) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot();
+        let mut staged = HashMap::new();
+        let mut results = Vec::with_capacity(transfers.len());
+        // What each sender has sent so far in this batch, so the rules count
+        // it alongside the ledger.
+        let mut pending: HashMap<AccountId, Vec<Money>> = HashMap::new();
 
-        if let Some(from_user) = self.users.get_mut(from) {
-            from_user.balance = from_user
-                .balance
-                .checked_sub(amount_i64)
-                .expect("Arithmetic overflow when deducting from sender");
+        let now = Utc::now();
+        for (index, transfer) in transfers.iter().enumerate() {
+            let staged_transfer = self
+                .account_id(&transfer.from)
+                .and_then(|from| Ok((from, self.account_id(&transfer.to)?)))
+                .and_then(|(from, to)| {
+                    self.check_transfer_rules(
+                        from.as_str(),
+                        to.as_str(),
+                        transfer.amount,
+                        now,
+                        pending.get(&from).map_or(&[], Vec::as_slice),
+                    )?;
+                    let balance = |id: &AccountId| {
+                        staged
+                            .get(id.as_str())
+                            .copied()
+                            .or_else(|| self.users.get(id.as_str()).map(|user| user.balance))
+                            .unwrap_or_default()
+                    };
+                    let before = (balance(&from), balance(&to));
+                    let staged_transfer = self.stage_transfer(
+                        &mut staged,
+                        from.as_str(),
+                        to.as_str(),
+                        transfer.amount,
+                    )?;
+                    Ok((from, to, before, staged_transfer))
+                })
+                .map_err(|error| {
+                    self.events.publish(BankEvent::rejected(
+                        &transfer.from,
+                        &transfer.to,
+                        transfer.amount,
+                        &error,
+                    ));
+                    BankError::BatchTransferFailed {
+                        index,
+                        error: Box::new(error),
+                    }
+                })?;
+            pending
+                .entry(staged_transfer.0.clone())
+                .or_default()
+                .push(transfer.amount);
+            results.push(staged_transfer);
         }
 
-        if let Some(to_user) = self.users.get_mut(to) {
-            to_user.balance = match to_user.balance.checked_add(amount_i64) {
-                Some(balance) => balance,
-                None => {
-                    if let Some(from_user) = self.users.get_mut(from) {
-                        from_user.balance += amount_i64;
-                    }
-                    return Err("Arithmetic overflow when adding to receiver".to_string());
-                }
-            };
+        self.commit_staged(staged);
+        for (transfer, (from, to, before, staged_transfer)) in transfers.iter().zip(results) {
+            let entry_id = self.ledger.record_transfer(
+                from.as_str(),
+                to.as_str(),
+                transfer.amount,
+                staged_transfer,
+            );
+            self.events.publish(BankEvent::transfer(
+                entry_id,
+                (&self.users[from.as_str()], &self.users[to.as_str()]),
+                transfer.amount,
+                before,
+                &staged_transfer,
+            ));
         }
 
+        self.checkpoint(snapshot);
         Ok(())
     }
 
-    pub fn accrue_interest(&mut self) -> Result<(), String> {
-        for user in self.users.values_mut() {
-            match user.balance.cmp(&0) {
-                std::cmp::Ordering::Less => {
-                    let abs_balance = u64::try_from(user.balance.abs()).map_err(|_| {
-                        format!(
-                            "Balance too large to calculate interest for user {}",
-                            user.name
-                        )
-                    })?;
-
-                    let interest = match abs_balance.checked_mul(self.credit_interest) {
-                        Some(result) => result / 10000,
-                        None => {
-                            return Err(format!(
-                                "Interest calculation overflow for user {}",
-                                user.name
-                            ));
-                        }
-                    };
+    // Validates a transfer against the staged balances (falling back to the
+    // committed ones) and stages its result without touching `self.users`.
+    // The amount is in the sender's currency and is converted for the
+    // receiver when the two accounts differ.
+    fn stage_transfer(
+        &self,
+        staged: &mut HashMap<String, Money>,
+        from: &str,
+        to: &str,
+        amount: Money,
+    ) -> Result<StagedTransfer, BankError> {
+        let Some(from_user) = self.users.get(from) else {
+            return Err(BankError::UserNotFound(from.to_string()));
+        };
 
-                    let interest_i64 = i64::try_from(interest).map_err(|_| {
-                        format!(
-                            "Interest too large to convert to i64 for user {}",
-                            user.name
-                        )
-                    })?;
-
-                    user.balance = match user.balance.checked_sub(interest_i64) {
-                        Some(result) => result,
-                        None => {
-                            return Err(format!(
-                                "Balance underflow when applying interest for user {}",
-                                user.name
-                            ));
-                        }
-                    };
-                }
-                std::cmp::Ordering::Greater => {
-                    let positive_balance = u64::try_from(user.balance).map_err(|_| {
-                        format!(
-                            "Balance too large to calculate interest for user {}",
-                            user.name
-                        )
-                    })?;
-
-                    let interest = match positive_balance.checked_mul(self.debit_interest) {
-                        Some(result) => result / 10000,
-                        None => {
-                            return Err(format!(
-                                "Interest calculation overflow for user {}",
-                                user.name
-                            ));
-                        }
-                    };
+        let Some(to_user) = self.users.get(to) else {
+            return Err(BankError::UserNotFound(to.to_string()));
+        };
 
-                    let interest_i64 = i64::try_from(interest).map_err(|_| {
-                        format!(
-                            "Interest too large to convert to i64 for user {}",
-                            user.name
-                        )
-                    })?;
-
-                    user.balance = match user.balance.checked_add(interest_i64) {
-                        Some(result) => result,
-                        None => {
-                            return Err(format!(
-                                "Balance overflow when applying interest for user {}",
-                                user.name
-                            ));
-                        }
-                    };
-                }
-                std::cmp::Ordering::Equal => {
-                    // No interest for zero balance
-                }
+        from_user.check_active()?;
+        to_user.check_active()?;
+
+        let from_balance = staged.get(from).copied().unwrap_or(from_user.balance);
+        // Moving money within one account doesn't overdraw it, so no fee.
+        let (new_from_balance, fee) = if from == to {
+            (from_user.checked_debit(from_balance, amount)?, Money::ZERO)
+        } else {
+            from_user.debit_with_fee(from_balance, amount)?
+        };
+
+        let credited = self.exchange_rates.convert(
+            amount,
+            from_user.currency,
+            to_user.currency,
+            self.fx_rounding,
+        )?;
+
+        staged.insert(from.to_string(), new_from_balance);
+
+        let to_balance = staged.get(to).copied().unwrap_or(to_user.balance);
+
+        let Some(new_to_balance) = to_balance.checked_add(credited) else {
+            return Err(BankError::TransferOverflow(to_user.name.clone()));
+        };
+
+        staged.insert(to.to_string(), new_to_balance);
+
+        Ok(StagedTransfer {
+            from_balance: new_from_balance,
+            to_balance: new_to_balance,
+            credited,
+            fee,
+        })
+    }
+
+    fn commit_staged(&mut self, staged: HashMap<String, Money>) {
+        for (name, balance) in staged {
+            if let Some(user) = self.users.get_mut(&name) {
+                user.balance = balance;
             }
         }
-
-        Ok(())
     }
 
-    pub fn merge_bank(&mut self, other: Bank) -> Result<(), String> {
-        for (name, user) in other.users {
-            if let Some(existing_user) = self.users.get_mut(&name) {
-                existing_user.balance = match existing_user.balance.checked_add(user.balance) {
-                    Some(result) => result,
-                    None => return Err(format!("Balance overflow when merging user {}", name)),
-                };
-            } else {
-                self.users.insert(name, user);
+    // Only active accounts accrue interest.
+    pub fn accrue_interest(&mut self) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot();
+
+        // Worked out for every account before any is touched, in account
+        // order, so an overflow leaves the bank as it was and always names
+        // the same account.
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.is_active())
+            .collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut accruals = Vec::new();
+        for user in users {
+            let interest = user.accrued_interest(self.credit_interest, self.debit_interest)?;
+            if interest.is_zero() {
+                continue;
             }
+
+            let Some(balance) = user.balance.checked_add(interest) else {
+                return Err(BankError::InterestOverflow(user.name.clone()));
+            };
+            accruals.push((user.id.to_string(), interest, balance));
         }
 
-        self.credit_interest = match self.credit_interest.checked_add(other.credit_interest) {
-            Some(result) => result / 2,
-            None => return Err("Credit interest overflow during merge".to_string()),
-        };
+        for (id, interest, balance) in accruals {
+            let user = self.users.get_mut(&id).expect("Accrued account exists");
+            let before = user.clone();
+            user.balance = balance;
 
-        self.debit_interest = match self.debit_interest.checked_add(other.debit_interest) {
-            Some(result) => result / 2,
-            None => return Err("Debit interest overflow during merge".to_string()),
-        };
+            let entry_id =
+                self.ledger
+                    .record_adjustment(EntryKind::Interest, &id, interest, user.balance);
+            self.events.publish(BankEvent::InterestAccrued {
+                entry_id,
+                account: AccountChange {
+                    before,
+                    after: user.clone(),
+                },
+                interest,
+            });
+        }
 
+        self.checkpoint(snapshot);
         Ok(())
     }
+
+    pub fn merge_bank(&mut self, other: Bank) -> Result<(), BankError> {
+        self.merge_bank_with(other, &MergePolicy::default())
+            .map(|_| ())
+    }
+
+    // Ids of the accounts whose balance doesn't match the ledger.
+    pub fn reconcile(&self) -> Result<Vec<String>, BankError> {
+        let replayed = self.ledger.replay()?;
+
+        let mut mismatched: Vec<String> = self
+            .users
+            .values()
+            .filter(|user| {
+                replayed.get(user.id.as_str()).copied().unwrap_or_default() != user.balance
+            })
+            .map(|user| user.id.to_string())
+            .collect();
+        mismatched.sort();
+
+        Ok(mismatched)
+    }
 }
 
 #[cfg(test)]
@@ -263,78 +595,174 @@ mod tests {
     #[test]
     fn test_add_user() {
         let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
-        let user = User {
-            name: "Alice".to_string(),
-            credit_line: 1000,
-            balance: 500,
-        };
+        let user = User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(500),
+        );
 
         assert!(bank.add_user(user).is_ok());
         assert_eq!(bank.users.len(), 1);
 
-        let duplicate_user = User {
-            name: "Alice".to_string(),
-            credit_line: 2000,
-            balance: 1000,
-        };
-        assert!(bank.add_user(duplicate_user).is_err());
+        let duplicate_user = User::new(
+            "Alice".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(1000),
+        );
+        assert_eq!(
+            bank.add_user(duplicate_user),
+            Err(BankError::DuplicateUser("Alice".to_string()))
+        );
+
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(alice.credit_line, Money::from_minor(1000));
+        assert_eq!(alice.balance, Money::from_minor(500));
 
-        let alice = bank.users.get("Alice").unwrap();
-        assert_eq!(alice.credit_line, 1000);
-        assert_eq!(alice.balance, 500);
+        assert_eq!(
+            bank.add_user(User::new(
+                "Bob".to_string(),
+                Money::from_minor(-1),
+                Money::ZERO
+            )),
+            Err(BankError::NegativeCreditLine("Bob".to_string()))
+        );
     }
 
     #[test]
     fn test_transfer_funds() {
         let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
 
-        let alice = User {
-            name: "Alice".to_string(),
-            credit_line: 1000,
-            balance: 500,
-        };
-        let bob = User {
-            name: "Bob".to_string(),
-            credit_line: 2000,
-            balance: 200,
-        };
+        let alice = User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(500),
+        );
+        let bob = User::new(
+            "Bob".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(200),
+        );
 
         bank.add_user(alice).unwrap();
         bank.add_user(bob).unwrap();
 
-        assert!(bank.transfer_funds("Alice", "Bob", 300).is_ok());
+        assert!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+                .is_ok()
+        );
 
-        assert_eq!(bank.users.get("Alice").unwrap().balance, 200);
-        assert_eq!(bank.users.get("Bob").unwrap().balance, 500);
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(200));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(500));
 
-        assert!(bank.transfer_funds("Alice", "Bob", 1500).is_err());
+        assert_eq!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(1500)),
+            Err(BankError::InsufficientCredit {
+                user: "Alice".to_string(),
+                amount: Money::from_minor(1500)
+            })
+        );
+        assert_eq!(
+            bank.transfer_funds("Alice", "Carol", Money::from_minor(100)),
+            Err(BankError::UserNotFound("Carol".to_string()))
+        );
 
-        assert_eq!(bank.users.get("Alice").unwrap().balance, 200);
-        assert_eq!(bank.users.get("Bob").unwrap().balance, 500);
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(200));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(500));
+    }
+
+    #[test]
+    fn test_transfer_batch() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+
+        for (name, credit_line, balance) in [("Alice", 0, 1000), ("Bob", 100, 0), ("Carol", 0, 0)] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::from_minor(credit_line),
+                Money::from_minor(balance),
+            ))
+            .unwrap();
+        }
+
+        // Bob can only pay Carol thanks to the earlier entry from Alice.
+        let batch = vec![
+            Transfer::new("Alice", "Bob", Money::from_minor(400)),
+            Transfer::new("Bob", "Carol", Money::from_minor(450)),
+            Transfer::new("Alice", "Carol", Money::from_minor(600)),
+        ];
+        assert!(bank.transfer_batch(&batch).is_ok());
+
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(0));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(-50));
+        assert_eq!(bank.user("Carol").unwrap().balance, Money::from_minor(1050));
+        assert_eq!(bank.ledger().len(), 6);
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_batch_rolls_back() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+
+        for (name, credit_line, balance) in [("Alice", 0, 1000), ("Bob", 100, 0)] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::from_minor(credit_line),
+                Money::from_minor(balance),
+            ))
+            .unwrap();
+        }
+
+        let batch = vec![
+            Transfer::new("Alice", "Bob", Money::from_minor(500)),
+            Transfer::new("Bob", "Alice", Money::from_minor(200)),
+            Transfer::new("Alice", "Bob", Money::from_minor(800)),
+        ];
+        assert_eq!(
+            bank.transfer_batch(&batch),
+            Err(BankError::BatchTransferFailed {
+                index: 2,
+                error: Box::new(BankError::InsufficientCredit {
+                    user: "Alice".to_string(),
+                    amount: Money::from_minor(800)
+                })
+            })
+        );
+
+        let unknown = vec![
+            Transfer::new("Alice", "Bob", Money::from_minor(1)),
+            Transfer::new("Alice", "Dave", Money::from_minor(1)),
+        ];
+        assert!(matches!(
+            bank.transfer_batch(&unknown),
+            Err(BankError::BatchTransferFailed { index: 1, .. })
+        ));
+
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(1000));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(0));
+        assert_eq!(bank.ledger().len(), 2);
     }
 
     #[test]
     fn test_accrue_interest() {
         let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
 
-        let alice = User {
-            name: "Alice".to_string(),
-            credit_line: 1000,
-            balance: 1000,
-        };
-        let bob = User {
-            name: "Bob".to_string(),
-            credit_line: 2000,
-            balance: -500,
-        };
+        let alice = User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(1000),
+        );
+        let bob = User::new(
+            "Bob".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(-500),
+        );
 
         bank.add_user(alice).unwrap();
         bank.add_user(bob).unwrap();
 
         assert!(bank.accrue_interest().is_ok());
 
-        assert_eq!(bank.users.get("Alice").unwrap().balance, 1030);
-        assert_eq!(bank.users.get("Bob").unwrap().balance, -525);
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(1030));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(-525));
     }
 
     #[test]
@@ -342,30 +770,30 @@ mod tests {
         let mut bank1 = Bank::new("Bank 1".to_string(), 500, 300);
         let mut bank2 = Bank::new("Bank 2".to_string(), 600, 400);
 
-        let alice = User {
-            name: "Alice".to_string(),
-            credit_line: 1000,
-            balance: 1000,
-        };
-        let bob = User {
-            name: "Bob".to_string(),
-            credit_line: 2000,
-            balance: -500,
-        };
+        let alice = User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(1000),
+        );
+        let bob = User::new(
+            "Bob".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(-500),
+        );
 
         bank1.add_user(alice).unwrap();
         bank1.add_user(bob).unwrap();
 
-        let charlie = User {
-            name: "Charlie".to_string(),
-            credit_line: 1500,
-            balance: 800,
-        };
-        let alice_bank2 = User {
-            name: "Alice".to_string(),
-            credit_line: 500,
-            balance: 500,
-        };
+        let charlie = User::new(
+            "Charlie".to_string(),
+            Money::from_minor(1500),
+            Money::from_minor(800),
+        );
+        let alice_bank2 = User::new(
+            "Alice".to_string(),
+            Money::from_minor(500),
+            Money::from_minor(500),
+        );
 
         bank2.add_user(charlie).unwrap();
         bank2.add_user(alice_bank2).unwrap();
@@ -376,7 +804,277 @@ mod tests {
         assert_eq!(bank1.credit_interest, 550);
         assert_eq!(bank1.debit_interest, 350);
 
-        assert_eq!(bank1.users.get("Alice").unwrap().balance, 1500);
-        assert_eq!(bank1.users.get("Charlie").unwrap().balance, 800);
+        assert_eq!(
+            bank1.user("Alice").unwrap().balance,
+            Money::from_minor(1500)
+        );
+        assert_eq!(
+            bank1.user("Charlie").unwrap().balance,
+            Money::from_minor(800)
+        );
+    }
+
+    #[test]
+    fn test_overflow_errors() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(100),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(i64::MAX),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(-1)),
+            Err(BankError::NegativeAmount(Money::from_minor(-1)))
+        );
+        assert_eq!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(50)),
+            Err(BankError::TransferOverflow("Bob".to_string()))
+        );
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(100));
+
+        assert_eq!(
+            bank.accrue_interest(),
+            Err(BankError::InterestOverflow("Bob".to_string()))
+        );
+
+        let other = Bank::new("Other".to_string(), u64::MAX, 0);
+        assert_eq!(bank.merge_bank(other), Err(BankError::InterestRateOverflow));
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            BankError::UserNotFound("Alice".to_string()).to_string(),
+            "User 'Alice' not found"
+        );
+        assert_eq!(
+            BankError::InsufficientCredit {
+                user: "Bob".to_string(),
+                amount: Money::from_minor(10)
+            }
+            .to_string(),
+            "Insufficient credit line for user 'Bob' to transfer 0.10"
+        );
+    }
+
+    #[test]
+    fn test_cross_currency_transfer() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+        bank.exchange_rates
+            .set_rate(Currency::EUR, Currency::AED, 3_985_000);
+        bank.fx_rounding = Rounding::HalfUp;
+
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(1000),
+        ))
+        .unwrap();
+        bank.add_user(
+            User::new(
+                "Omar".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(0),
+            )
+            .with_currency(Currency::AED),
+        )
+        .unwrap();
+        bank.add_user(
+            User::new(
+                "Sam".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(0),
+            )
+            .with_currency(Currency::USD),
+        )
+        .unwrap();
+
+        // 250 EUR * 3.985 = 996.25 AED
+        bank.transfer_funds("Alice", "Omar", Money::from_minor(250))
+            .unwrap();
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(750));
+        assert_eq!(bank.user("Omar").unwrap().balance, Money::from_minor(996));
+
+        // 1 EUR * 3.985 = 3.985 AED
+        bank.transfer_funds("Alice", "Omar", Money::from_minor(1))
+            .unwrap();
+        assert_eq!(bank.user("Omar").unwrap().balance, Money::from_minor(1000));
+
+        let entry = bank.ledger().entries().last().unwrap();
+        assert_eq!(entry.amount, Money::from_minor(1));
+        assert_eq!(entry.converted_amount, Some(Money::from_minor(4)));
+        assert!(bank.reconcile().unwrap().is_empty());
+
+        assert_eq!(
+            bank.transfer_funds("Alice", "Sam", Money::from_minor(10)),
+            Err(BankError::NoExchangeRate {
+                from: Currency::EUR,
+                to: Currency::USD
+            })
+        );
+        assert_eq!(
+            bank.transfer_funds("Omar", "Alice", Money::from_minor(10)),
+            Err(BankError::NoExchangeRate {
+                from: Currency::AED,
+                to: Currency::EUR
+            })
+        );
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(749));
+        assert_eq!(bank.user("Sam").unwrap().balance, Money::from_minor(0));
+    }
+
+    #[test]
+    fn test_calc_balance_across_currencies() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+        bank.exchange_rates
+            .set_rate(Currency::USD, Currency::EUR, 900_000);
+
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(1000),
+        ))
+        .unwrap();
+        bank.add_user(
+            User::new(
+                "Sam".to_string(),
+                Money::from_minor(1000),
+                Money::from_minor(-500),
+            )
+            .with_currency(Currency::USD),
+        )
+        .unwrap();
+
+        assert_eq!(
+            bank.calc_balance(),
+            Ok((Money::from_minor(1000), Money::from_minor(450)))
+        );
+        assert_eq!(
+            bank.calc_balance_in(Currency::USD),
+            Err(BankError::NoExchangeRate {
+                from: Currency::EUR,
+                to: Currency::USD
+            })
+        );
+
+        // A missing rate to the bank's own currency is an error, not a panic.
+        bank.add_user(
+            User::new("Gus".to_string(), Money::ZERO, Money::from_minor(100))
+                .with_currency(Currency::AED),
+        )
+        .unwrap();
+        assert_eq!(
+            bank.calc_balance(),
+            Err(BankError::NoExchangeRate {
+                from: Currency::AED,
+                to: Currency::EUR
+            })
+        );
+    }
+
+    #[test]
+    fn test_ledger_records_operations() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+
+        let alice = User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(1000),
+        );
+        let bob = User::new(
+            "Bob".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(-500),
+        );
+
+        bank.add_user(alice).unwrap();
+        bank.add_user(bob).unwrap();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+            .unwrap();
+        assert!(matches!(
+            bank.transfer_funds("Bob", "Alice", Money::from_minor(5000)),
+            Err(BankError::InsufficientCredit { .. })
+        ));
+        bank.accrue_interest().unwrap();
+
+        let kinds: Vec<EntryKind> = bank.ledger().entries().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EntryKind::Opening,
+                EntryKind::Opening,
+                EntryKind::Transfer,
+                EntryKind::Interest,
+                EntryKind::Interest,
+            ]
+        );
+
+        let (alice, bob) = (
+            bank.account_id("Alice").unwrap(),
+            bank.account_id("Bob").unwrap(),
+        );
+        let transfer = &bank.ledger().entries()[2];
+        assert_eq!(transfer.id, 3);
+        assert_eq!(transfer.from.as_deref(), Some(alice.as_str()));
+        assert_eq!(transfer.to.as_deref(), Some(bob.as_str()));
+        assert_eq!(transfer.amount, Money::from_minor(300));
+        assert_eq!(transfer.from_balance, Some(Money::from_minor(700)));
+        assert_eq!(transfer.to_balance, Some(Money::from_minor(-200)));
+
+        assert_eq!(bank.ledger().entries_for(bob.as_str()).count(), 3);
+    }
+
+    #[test]
+    fn test_ledger_replay_matches_balances() {
+        let mut bank1 = Bank::new("Bank 1".to_string(), 500, 300);
+        let mut bank2 = Bank::new("Bank 2".to_string(), 600, 400);
+
+        bank1
+            .add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(1000),
+                Money::from_minor(1000),
+            ))
+            .unwrap();
+        bank1
+            .add_user(User::new(
+                "Bob".to_string(),
+                Money::from_minor(2000),
+                Money::from_minor(-500),
+            ))
+            .unwrap();
+        bank2
+            .add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(500),
+                Money::from_minor(-300),
+            ))
+            .unwrap();
+
+        bank1
+            .transfer_funds("Bob", "Alice", Money::from_minor(700))
+            .unwrap();
+        bank1.accrue_interest().unwrap();
+        bank1.merge_bank(bank2).unwrap();
+
+        let replayed = bank1.ledger().replay().unwrap();
+        for user in bank1.users.values() {
+            assert_eq!(replayed[user.id.as_str()], user.balance);
+        }
+        assert!(bank1.reconcile().unwrap().is_empty());
+
+        let alice = bank1.user_mut("Alice").unwrap();
+        alice.balance = alice.balance.checked_add(Money::from_minor(1)).unwrap();
+        let alice = alice.id.to_string();
+        assert_eq!(bank1.reconcile().unwrap(), vec![alice]);
     }
 }
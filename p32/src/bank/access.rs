@@ -0,0 +1,398 @@
+use super::{
+    AccountId, Bank, BankError, BankEvent, BankReport, KycStatus, Money, OverdraftPolicy,
+    Statement, TrialBalance, User,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    // Holds the accounts of this customer number and nothing else.
+    Customer(u64),
+    // Serves customers: moves their money, opens and manages accounts.
+    Teller,
+    // Anything, including rates and credit lines.
+    Admin,
+    // Sees everything, changes nothing.
+    Auditor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    ViewAccount(AccountId),
+    // Reports and books covering the whole bank.
+    ViewBank,
+    Transfer { from: AccountId },
+    OpenAccount,
+    // Freezing, unfreezing and KYC status.
+    ManageAccount(AccountId),
+    // Credit line and overdraft policy.
+    SetLimits(AccountId),
+    SetInterestRates,
+    AccrueInterest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Denial {
+    pub principal: String,
+    pub role: Role,
+    pub action: Action,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Operations on a bank on behalf of a principal. Each one is checked
+// against the principal's role first; a denied one fails with
+// `BankError::AccessDenied`, is added to `Bank::denials` and published as
+// `BankEvent::AccessDenied`.
+pub struct Session<'a> {
+    bank: &'a mut Bank,
+    principal: Principal,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Customer(customer) => write!(f, "customer #{}", customer),
+            Role::Teller => write!(f, "teller"),
+            Role::Admin => write!(f, "admin"),
+            Role::Auditor => write!(f, "auditor"),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::ViewAccount(id) => write!(f, "view account {}", id),
+            Action::ViewBank => write!(f, "view bank-wide reports"),
+            Action::Transfer { from } => write!(f, "transfer from account {}", from),
+            Action::OpenAccount => write!(f, "open accounts"),
+            Action::ManageAccount(id) => write!(f, "manage account {}", id),
+            Action::SetLimits(id) => write!(f, "change the limits of account {}", id),
+            Action::SetInterestRates => write!(f, "change interest rates"),
+            Action::AccrueInterest => write!(f, "accrue interest"),
+        }
+    }
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) may not {}",
+            self.principal, self.role, self.action
+        )
+    }
+}
+
+impl Principal {
+    pub fn new(name: &str, role: Role) -> Self {
+        Principal {
+            name: name.to_string(),
+            role,
+        }
+    }
+}
+
+impl Role {
+    pub fn permits(&self, action: &Action, bank: &Bank) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Teller => matches!(
+                action,
+                Action::ViewAccount(_)
+                    | Action::ViewBank
+                    | Action::Transfer { .. }
+                    | Action::OpenAccount
+                    | Action::ManageAccount(_)
+            ),
+            Role::Auditor => matches!(action, Action::ViewAccount(_) | Action::ViewBank),
+            Role::Customer(customer) => match action {
+                Action::ViewAccount(id) | Action::Transfer { from: id } => bank
+                    .users
+                    .get(id.as_str())
+                    .is_some_and(|user| user.customer == *customer),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Bank {
+    pub fn session(&mut self, principal: Principal) -> Session<'_> {
+        Session {
+            bank: self,
+            principal,
+        }
+    }
+
+    // Every action refused to a session, oldest first. They're saved with
+    // the bank, and undo leaves them alone.
+    pub fn denials(&self) -> &[Denial] {
+        &self.denials
+    }
+}
+
+impl Session<'_> {
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+
+    fn authorize(&mut self, action: Action) -> Result<(), BankError> {
+        if self.principal.role.permits(&action, self.bank) {
+            return Ok(());
+        }
+
+        let denial = Denial {
+            principal: self.principal.name.clone(),
+            role: self.principal.role,
+            action,
+            timestamp: Utc::now(),
+        };
+        self.bank.denials.push(denial.clone());
+        self.bank.events.publish(BankEvent::AccessDenied {
+            denial: denial.clone(),
+        });
+        Err(BankError::AccessDenied(denial))
+    }
+
+    // Resolves `reference` and checks the action on the account it names.
+    fn authorize_on(
+        &mut self,
+        reference: &str,
+        action: fn(AccountId) -> Action,
+    ) -> Result<AccountId, BankError> {
+        let id = self.bank.account_id(reference)?;
+        self.authorize(action(id.clone()))?;
+        Ok(id)
+    }
+
+    pub fn user(&mut self, reference: &str) -> Result<&User, BankError> {
+        let id = self.authorize_on(reference, Action::ViewAccount)?;
+        self.bank.user(id.as_str())
+    }
+
+    pub fn statement(
+        &mut self,
+        reference: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Statement, BankError> {
+        let id = self.authorize_on(reference, Action::ViewAccount)?;
+        self.bank.statement(id.as_str(), start, end)
+    }
+
+    pub fn report(&mut self) -> Result<BankReport, BankError> {
+        self.authorize(Action::ViewBank)?;
+        self.bank.report()
+    }
+
+    pub fn trial_balance(&mut self) -> Result<TrialBalance, BankError> {
+        self.authorize(Action::ViewBank)?;
+        self.bank.trial_balance()
+    }
+
+    pub fn transfer_funds(&mut self, from: &str, to: &str, amount: Money) -> Result<(), BankError> {
+        let from = self.authorize_on(from, |from| Action::Transfer { from })?;
+        self.bank.transfer_funds(from.as_str(), to, amount)
+    }
+
+    pub fn open_account(&mut self, user: User) -> Result<AccountId, BankError> {
+        self.authorize(Action::OpenAccount)?;
+        self.bank.open_account(user)
+    }
+
+    pub fn freeze_account(&mut self, reference: &str) -> Result<(), BankError> {
+        let id = self.authorize_on(reference, Action::ManageAccount)?;
+        self.bank.freeze_account(id.as_str())
+    }
+
+    pub fn unfreeze_account(&mut self, reference: &str) -> Result<(), BankError> {
+        let id = self.authorize_on(reference, Action::ManageAccount)?;
+        self.bank.unfreeze_account(id.as_str())
+    }
+
+    pub fn set_kyc_status(&mut self, reference: &str, kyc: KycStatus) -> Result<(), BankError> {
+        let id = self.authorize_on(reference, Action::ManageAccount)?;
+        self.bank.set_kyc_status(id.as_str(), kyc)
+    }
+
+    pub fn set_credit_line(
+        &mut self,
+        reference: &str,
+        credit_line: Money,
+    ) -> Result<(), BankError> {
+        let id = self.authorize_on(reference, Action::SetLimits)?;
+        self.bank.set_credit_line(id.as_str(), credit_line)
+    }
+
+    pub fn set_overdraft_policy(
+        &mut self,
+        reference: &str,
+        overdraft: OverdraftPolicy,
+    ) -> Result<(), BankError> {
+        let id = self.authorize_on(reference, Action::SetLimits)?;
+        self.bank.set_overdraft_policy(id.as_str(), overdraft)
+    }
+
+    pub fn set_interest_rates(
+        &mut self,
+        credit_interest: u64,
+        debit_interest: u64,
+    ) -> Result<(), BankError> {
+        self.authorize(Action::SetInterestRates)?;
+        self.bank
+            .set_interest_rates(credit_interest, debit_interest);
+        Ok(())
+    }
+
+    pub fn accrue_interest(&mut self) -> Result<(), BankError> {
+        self.authorize(Action::AccrueInterest)?;
+        self.bank.accrue_interest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(10_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::ZERO,
+            Money::from_minor(10_000),
+        ))
+        .unwrap();
+        bank
+    }
+
+    fn denied(result: Result<(), BankError>) -> Action {
+        match result {
+            Err(BankError::AccessDenied(denial)) => denial.action,
+            result => panic!("expected a denial, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_customers_only_use_their_own_accounts() {
+        let mut bank = bank();
+        let alice_id = bank.account_id("Alice").unwrap();
+        let bob_id = bank.account_id("Bob").unwrap();
+        let customer = bank.user("Alice").unwrap().customer;
+        let mut session = bank.session(Principal::new("alice", Role::Customer(customer)));
+
+        session
+            .transfer_funds("Alice", "Bob", Money::from_minor(2500))
+            .unwrap();
+        assert_eq!(
+            session.user("Alice").unwrap().balance,
+            Money::from_minor(7500)
+        );
+        assert_eq!(
+            denied(session.transfer_funds("Bob", "Alice", Money::from_minor(100))),
+            Action::Transfer {
+                from: bob_id.clone()
+            }
+        );
+        assert_eq!(
+            denied(session.user("Bob").map(|_| ())),
+            Action::ViewAccount(bob_id)
+        );
+        assert_eq!(
+            denied(session.set_credit_line("Alice", Money::from_minor(100_000))),
+            Action::SetLimits(alice_id)
+        );
+        assert_eq!(denied(session.report().map(|_| ())), Action::ViewBank);
+        assert!(matches!(
+            session.transfer_funds("Nobody", "Bob", Money::from_minor(1)),
+            Err(BankError::UserNotFound(_))
+        ));
+
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(12_500));
+        assert_eq!(bank.user("Alice").unwrap().credit_line, Money::ZERO);
+        let denials = bank.denials();
+        assert_eq!(denials.len(), 4);
+        assert_eq!(denials[0].principal, "alice");
+        assert!(
+            denials[0]
+                .to_string()
+                .starts_with("alice (customer #1) may not transfer from account PB")
+        );
+    }
+
+    #[test]
+    fn test_staff_roles() {
+        let mut bank = bank();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        bank.subscribe(crate::bank::Delivery::Sync, move |event| {
+            if let BankEvent::AccessDenied { denial } = event {
+                recorded.lock().unwrap().push(denial.clone());
+            }
+        });
+
+        let mut auditor = bank.session(Principal::new("audrey", Role::Auditor));
+        assert!(auditor.report().is_ok());
+        assert!(auditor.trial_balance().is_ok());
+        assert!(auditor.user("Bob").is_ok());
+        assert_eq!(
+            denied(auditor.transfer_funds("Alice", "Bob", Money::from_minor(1))),
+            Action::Transfer {
+                from: bank.account_id("Alice").unwrap()
+            }
+        );
+
+        let mut teller = bank.session(Principal::new("terry", Role::Teller));
+        teller
+            .transfer_funds("Bob", "Alice", Money::from_minor(1000))
+            .unwrap();
+        teller.freeze_account("Bob").unwrap();
+        teller
+            .open_account(User::new("Carol".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        assert_eq!(
+            denied(teller.set_interest_rates(500, 100)),
+            Action::SetInterestRates
+        );
+        assert_eq!(denied(teller.accrue_interest()), Action::AccrueInterest);
+
+        let mut admin = bank.session(Principal::new("ada", Role::Admin));
+        admin.set_interest_rates(500, 100).unwrap();
+        admin
+            .set_credit_line("Alice", Money::from_minor(5000))
+            .unwrap();
+        assert!(matches!(
+            admin.set_credit_line("Alice", Money::from_minor(-1)),
+            Err(BankError::NegativeCreditLine(_))
+        ));
+        admin.accrue_interest().unwrap();
+
+        assert_eq!(bank.credit_interest, 500);
+        assert_eq!(
+            bank.user("Alice").unwrap().credit_line,
+            Money::from_minor(5000)
+        );
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(11_110)
+        );
+        assert_eq!(bank.denials().len(), 3);
+        assert_eq!(*events.lock().unwrap(), bank.denials());
+    }
+}
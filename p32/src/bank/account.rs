@@ -0,0 +1,529 @@
+use super::{AccountId, Bank, BankError, BankEvent, EntryKind, Money, User};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Frozen accounts can't send, receive or earn interest until they are
+// unfrozen. Closed accounts are settled to zero and stay in `Bank::users` so
+// their history remains readable, but can never be used again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Frozen,
+    Closed,
+}
+
+// Outcome of the customer's identity check. Rejecting it freezes the account,
+// and a rejected customer's account can't be unfrozen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KycStatus {
+    #[default]
+    Pending,
+    Verified,
+    Rejected,
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Frozen => "frozen",
+            AccountStatus::Closed => "closed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl fmt::Display for KycStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            KycStatus::Pending => "pending",
+            KycStatus::Verified => "verified",
+            KycStatus::Rejected => "rejected",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for KycStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pending" => Ok(KycStatus::Pending),
+            "verified" => Ok(KycStatus::Verified),
+            "rejected" => Ok(KycStatus::Rejected),
+            _ => Err(format!("Unknown KYC status '{}'", s)),
+        }
+    }
+}
+
+impl User {
+    pub fn is_active(&self) -> bool {
+        self.status == AccountStatus::Active
+    }
+
+    // Every account a transfer touches, on either side, has to be active.
+    pub(crate) fn check_active(&self) -> Result<(), BankError> {
+        match self.status {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Frozen => Err(BankError::AccountFrozen(self.name.clone())),
+            AccountStatus::Closed => Err(BankError::AccountClosed(self.name.clone())),
+        }
+    }
+}
+
+impl Bank {
+    // Resolves an account id (in any spacing or case) or a holder's name. A
+    // name only resolves when exactly one account carries it, not counting
+    // closed ones if that settles it.
+    pub fn account_id(&self, reference: &str) -> Result<AccountId, BankError> {
+        if let Some(user) = self.users.get(reference) {
+            return Ok(user.id.clone());
+        }
+        if let Ok(id) = reference.parse::<AccountId>()
+            && self.users.contains_key(id.as_str())
+        {
+            return Ok(id);
+        }
+
+        let mut named = self.find_by_name(reference);
+        if named.len() > 1 {
+            named.retain(|user| user.status != AccountStatus::Closed);
+        }
+        match named.as_slice() {
+            [user] => Ok(user.id.clone()),
+            [] => Err(BankError::UserNotFound(reference.to_string())),
+            _ => Err(BankError::AmbiguousUser(reference.to_string())),
+        }
+    }
+
+    pub fn user(&self, reference: &str) -> Result<&User, BankError> {
+        let id = self.account_id(reference)?;
+        Ok(&self.users[id.as_str()])
+    }
+
+    pub fn user_mut(&mut self, reference: &str) -> Result<&mut User, BankError> {
+        let id = self.account_id(reference)?;
+        Ok(self
+            .users
+            .get_mut(id.as_str())
+            .expect("Resolved account exists"))
+    }
+
+    // Accounts held under `name`, in the order they were opened.
+    pub fn find_by_name(&self, name: &str) -> Vec<&User> {
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.name == name)
+            .collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+        users
+    }
+
+    pub fn accounts_of(&self, customer: u64) -> Vec<&User> {
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.customer == customer)
+            .collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+        users
+    }
+
+    // Ids of accounts that were opened and then undone stay in the ledger, so
+    // they aren't handed out again.
+    pub(crate) fn next_account_id(&self) -> AccountId {
+        let undone = self
+            .ledger
+            .reversed_entries()
+            .flat_map(|entry| [entry.from.as_deref(), entry.to.as_deref()])
+            .flatten()
+            .filter_map(|id| id.parse::<AccountId>().ok()?.sequence());
+        let last = self
+            .users
+            .values()
+            .filter_map(|user| user.id.sequence())
+            .chain(undone)
+            .max()
+            .unwrap_or(0);
+        AccountId::new(last + 1)
+    }
+
+    pub(crate) fn next_customer(&self) -> u64 {
+        self.users
+            .values()
+            .map(|user| user.customer)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    // Opens an account under a fresh id. Unless `user.customer` names an
+    // existing customer, the account starts a new one.
+    pub fn open_account(&mut self, mut user: User) -> Result<AccountId, BankError> {
+        let snapshot = self.operation_snapshot();
+        if user.credit_line.is_negative() {
+            return Err(BankError::NegativeCreditLine(user.name));
+        }
+        if user.customer == 0 {
+            user.customer = self.next_customer();
+        } else if self.accounts_of(user.customer).is_empty() {
+            return Err(BankError::CustomerNotFound(user.customer));
+        }
+
+        user.id = self.next_account_id();
+        self.ledger.record_adjustment(
+            EntryKind::Opening,
+            user.id.as_str(),
+            user.balance,
+            user.balance,
+        );
+
+        let id = user.id.clone();
+        self.events
+            .publish(BankEvent::UserAdded { user: user.clone() });
+        self.users.insert(id.to_string(), user);
+        self.checkpoint(snapshot);
+        Ok(id)
+    }
+
+    // Only the display name changes; the ledger refers to accounts by id.
+    pub fn rename_account(&mut self, reference: &str, name: &str) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        self.user_mut(reference)?.name = name.to_string();
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+
+    pub fn freeze_account(&mut self, reference: &str) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        let user = self.user_mut(reference)?;
+        if user.status == AccountStatus::Closed {
+            return Err(BankError::AccountClosed(user.name.clone()));
+        }
+
+        user.status = AccountStatus::Frozen;
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+
+    pub fn unfreeze_account(&mut self, reference: &str) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        let user = self.user_mut(reference)?;
+        if user.status == AccountStatus::Closed {
+            return Err(BankError::AccountClosed(user.name.clone()));
+        }
+        if user.kyc == KycStatus::Rejected {
+            return Err(BankError::KycRejected(user.name.clone()));
+        }
+
+        user.status = AccountStatus::Active;
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+
+    pub fn set_kyc_status(&mut self, reference: &str, kyc: KycStatus) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        let user = self.user_mut(reference)?;
+        user.kyc = kyc;
+        if kyc == KycStatus::Rejected && user.status == AccountStatus::Active {
+            user.status = AccountStatus::Frozen;
+        }
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+
+    pub fn set_credit_line(
+        &mut self,
+        reference: &str,
+        credit_line: Money,
+    ) -> Result<(), BankError> {
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        let user = self.user_mut(reference)?;
+        if credit_line.is_negative() {
+            return Err(BankError::NegativeCreditLine(user.name.clone()));
+        }
+
+        user.credit_line = credit_line;
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+
+    // Closes an active account. A positive balance is paid out to
+    // `settle_to` as a final settlement transfer, whose ledger entry id is
+    // returned; an overdrawn account, or one with a loan outstanding, has to
+    // be repaid first. The account's
+    // credit line, interest carry and standing orders go with it.
+    pub fn close_account(
+        &mut self,
+        reference: &str,
+        settle_to: Option<&str>,
+    ) -> Result<Option<u64>, BankError> {
+        let snapshot = self
+            .operation_snapshot()
+            .with_account(self, reference)
+            .with_standing_orders(self);
+        let user = self.user(reference)?;
+        user.check_active()?;
+
+        let id = user.id.clone();
+        let balance = user.balance;
+        let unsettled = || BankError::AccountNotSettled {
+            user: user.name.clone(),
+            balance,
+        };
+        if balance.is_negative() {
+            return Err(unsettled());
+        }
+        let outstanding = self
+            .loans
+            .iter()
+            .filter(|loan| loan.account == id.as_str())
+            .try_fold(Money::ZERO, |total, loan| {
+                total.checked_add(loan.outstanding)
+            })
+            .unwrap_or(Money::MAX);
+        if outstanding.is_positive() {
+            return Err(BankError::LoanOutstanding {
+                user: user.name.clone(),
+                outstanding,
+            });
+        }
+
+        let mut entry_id = None;
+        if balance.is_positive() {
+            let settle_to = self.account_id(settle_to.ok_or_else(unsettled)?)?;
+            if settle_to == id {
+                return Err(unsettled());
+            }
+
+            let mut staged = HashMap::new();
+            let staged_transfer =
+                self.stage_transfer(&mut staged, id.as_str(), settle_to.as_str(), balance)?;
+            self.commit_staged(staged);
+            entry_id = Some(self.ledger.record_staged(
+                EntryKind::Settlement,
+                id.as_str(),
+                settle_to.as_str(),
+                balance,
+                staged_transfer,
+            ));
+        }
+
+        let user = self.users.get_mut(id.as_str()).expect("Account exists");
+        user.status = AccountStatus::Closed;
+        user.credit_line = Money::ZERO;
+        self.interest_carry.remove(id.as_str());
+        self.standing_orders
+            .retain(|order| order.from != id.as_str() && order.to != id.as_str());
+
+        self.checkpoint(snapshot);
+        Ok(entry_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Schedule, StandingOrder};
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(700),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(-200),
+        ))
+        .unwrap();
+        bank
+    }
+
+    #[test]
+    fn test_freeze_and_kyc() {
+        let mut bank = bank();
+
+        bank.freeze_account("Alice").unwrap();
+        assert_eq!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(100)),
+            Err(BankError::AccountFrozen("Alice".to_string()))
+        );
+        assert_eq!(
+            bank.transfer_funds("Bob", "Alice", Money::from_minor(100)),
+            Err(BankError::AccountFrozen("Alice".to_string()))
+        );
+
+        // Frozen accounts don't accrue interest.
+        bank.accrue_interest().unwrap();
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(700));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(-210));
+
+        bank.unfreeze_account("Alice").unwrap();
+        assert!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(100))
+                .is_ok()
+        );
+
+        bank.set_kyc_status("Bob", KycStatus::Rejected).unwrap();
+        assert_eq!(bank.user("Bob").unwrap().status, AccountStatus::Frozen);
+        assert_eq!(
+            bank.unfreeze_account("Bob"),
+            Err(BankError::KycRejected("Bob".to_string()))
+        );
+        bank.set_kyc_status("Bob", KycStatus::Verified).unwrap();
+        assert!(bank.unfreeze_account("Bob").is_ok());
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_close_account() {
+        let mut bank = bank();
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        bank.add_standing_order(StandingOrder::new(
+            "Bob",
+            "Alice",
+            Money::from_minor(50),
+            Schedule::Daily,
+            start,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            bank.close_account("Bob", Some("Alice")),
+            Err(BankError::AccountNotSettled {
+                user: "Bob".to_string(),
+                balance: Money::from_minor(-200)
+            })
+        );
+        assert_eq!(
+            bank.close_account("Alice", None),
+            Err(BankError::AccountNotSettled {
+                user: "Alice".to_string(),
+                balance: Money::from_minor(700)
+            })
+        );
+
+        let entry_id = bank.close_account("Alice", Some("Bob")).unwrap().unwrap();
+        let entry = &bank.ledger().entries()[entry_id as usize - 1];
+        assert_eq!(entry.kind, EntryKind::Settlement);
+        assert_eq!(entry.amount, Money::from_minor(700));
+
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(alice.status, AccountStatus::Closed);
+        assert_eq!(alice.balance, Money::ZERO);
+        assert_eq!(alice.credit_line, Money::ZERO);
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(500));
+        assert!(bank.standing_orders().is_empty());
+
+        assert_eq!(
+            bank.close_account("Alice", Some("Bob")),
+            Err(BankError::AccountClosed("Alice".to_string()))
+        );
+        assert_eq!(
+            bank.transfer_funds("Bob", "Alice", Money::from_minor(1)),
+            Err(BankError::AccountClosed("Alice".to_string()))
+        );
+        assert_eq!(
+            bank.unfreeze_account("Alice"),
+            Err(BankError::AccountClosed("Alice".to_string()))
+        );
+        assert_eq!(
+            bank.add_user(User::new("Alice".to_string(), Money::ZERO, Money::ZERO)),
+            Err(BankError::DuplicateUser("Alice".to_string()))
+        );
+
+        // Nothing to settle at a zero balance.
+        bank.add_user(User::new("Carol".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        assert_eq!(bank.close_account("Carol", None), Ok(None));
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_multiple_accounts() {
+        let mut bank = bank();
+        let alice = bank.account_id("Alice").unwrap();
+        assert_eq!(alice, AccountId::new(1));
+        assert_eq!(bank.user(alice.as_str()).unwrap().name, "Alice");
+
+        // add_user keeps names unique; open_account doesn't.
+        let savings = User::new("Alice".to_string(), Money::ZERO, Money::ZERO)
+            .for_customer(bank.user("Alice").unwrap().customer);
+        assert_eq!(
+            bank.add_user(savings.clone()),
+            Err(BankError::DuplicateUser("Alice".to_string()))
+        );
+        let savings = bank.open_account(savings).unwrap();
+        assert_eq!(savings, AccountId::new(3));
+        assert_eq!(
+            bank.account_id("Alice"),
+            Err(BankError::AmbiguousUser("Alice".to_string()))
+        );
+        assert_eq!(bank.find_by_name("Alice").len(), 2);
+        assert_eq!(bank.accounts_of(1).len(), 2);
+
+        // Ids are accepted in the printed form, lower case included.
+        let spaced = "pb50 0000 0000 0000 03";
+        assert_eq!(savings.to_string(), spaced.to_uppercase().replace(' ', ""));
+        bank.transfer_funds(alice.as_str(), spaced, Money::from_minor(200))
+            .unwrap();
+        assert_eq!(
+            bank.user(savings.as_str()).unwrap().balance,
+            Money::from_minor(200)
+        );
+
+        bank.rename_account(savings.as_str(), "Alice (savings)")
+            .unwrap();
+        assert_eq!(bank.account_id("Alice"), Ok(alice.clone()));
+        assert_eq!(bank.account_id("Alice (savings)"), Ok(savings));
+
+        // Accounts come in the order they were opened, whatever their check
+        // digits.
+        for _ in 0..20 {
+            bank.open_account(
+                User::new("Alice".to_string(), Money::ZERO, Money::ZERO).for_customer(1),
+            )
+            .unwrap();
+        }
+        let opened: Vec<u64> = [1].into_iter().chain(3..=23).collect();
+        let listed = |users: Vec<&User>| {
+            users
+                .iter()
+                .filter_map(|user| user.id.sequence())
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(listed(bank.accounts_of(1)), opened);
+        // Less the savings account, which was renamed.
+        assert_eq!(
+            listed(bank.find_by_name("Alice")),
+            [1].into_iter().chain(4..=23).collect::<Vec<u64>>()
+        );
+        let mut by_string = bank.accounts_of(1);
+        by_string.sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+        assert_ne!(listed(by_string), opened);
+
+        assert_eq!(
+            bank.open_account(
+                User::new("Dan".to_string(), Money::ZERO, Money::ZERO).for_customer(9)
+            ),
+            Err(BankError::CustomerNotFound(9))
+        );
+        assert_eq!(
+            bank.account_id("PB00 0000 0000 0000 01"),
+            Err(BankError::UserNotFound(
+                "PB00 0000 0000 0000 01".to_string()
+            ))
+        );
+    }
+}
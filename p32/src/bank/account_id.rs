@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::str::FromStr;
+
+// Country-code position of an IBAN; not a real country.
+const PREFIX: &str = "PB";
+const SEQUENCE_DIGITS: usize = 14;
+
+// An IBAN-shaped account number: "PB", two ISO 7064 mod 97-10 check digits
+// and a zero-padded sequence number, e.g. "PB39 0000 0000 0000 07" (stored
+// without the spaces). The check digits catch typos and swapped digits when
+// an id is typed in by hand.
+//
+// The default, empty id marks an account that hasn't been opened yet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AccountId(String);
+
+// Remainder of the IBAN's numeric form (letters become 10..=35) mod 97.
+fn mod97(chars: impl Iterator<Item = char>) -> Option<u32> {
+    let mut remainder = 0;
+    for c in chars {
+        let value = c.to_digit(36)?;
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    Some(remainder)
+}
+
+impl AccountId {
+    pub fn new(sequence: u64) -> Self {
+        let bban = format!("{:0width$}", sequence, width = SEQUENCE_DIGITS);
+        let remainder = mod97(bban.chars().chain(PREFIX.chars()).chain("00".chars()))
+            .expect("Digits and letters only");
+        AccountId(format!("{}{:02}{}", PREFIX, 98 - remainder, bban))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_assigned(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    // The sequence number the bank allocated this id from.
+    pub fn sequence(&self) -> Option<u64> {
+        self.0.get(4..)?.parse().ok()
+    }
+}
+
+// Ids sort in the order they were allocated, not by their check digits.
+impl Ord for AccountId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.sequence(), &self.0).cmp(&(other.sequence(), &other.0))
+    }
+}
+
+impl PartialOrd for AccountId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Borrow<str> for AccountId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+// Accepts lower case and the usual groups of four separated by spaces.
+impl FromStr for AccountId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid account id '{}'", s);
+
+        let compact: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let well_formed = compact.len() == PREFIX.len() + 2 + SEQUENCE_DIGITS
+            && compact.starts_with(PREFIX)
+            && compact[PREFIX.len()..].chars().all(|c| c.is_ascii_digit());
+        if !well_formed {
+            return Err(invalid());
+        }
+
+        let (head, bban) = compact.split_at(4);
+        if mod97(bban.chars().chain(head.chars())) != Some(1) {
+            return Err(invalid());
+        }
+        Ok(AccountId(compact))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_id() {
+        let id = AccountId::new(7);
+        assert_eq!(id.as_str().len(), 18);
+        assert_eq!(id.sequence(), Some(7));
+        assert_eq!(id.to_string().parse(), Ok(id.clone()));
+
+        let spaced: String = id
+            .as_str()
+            .to_lowercase()
+            .chars()
+            .enumerate()
+            .flat_map(|(index, c)| {
+                (index > 0 && index % 4 == 0)
+                    .then_some(' ')
+                    .into_iter()
+                    .chain([c])
+            })
+            .collect();
+        assert_eq!(spaced.parse(), Ok(id.clone()));
+
+        // A single wrong digit or two swapped digits fail the check.
+        let mut typo = id.to_string().into_bytes();
+        typo[17] = b'8';
+        assert!(
+            String::from_utf8(typo)
+                .unwrap()
+                .parse::<AccountId>()
+                .is_err()
+        );
+        let swapped = AccountId::new(12).to_string().replace("12", "21");
+        assert!(swapped.parse::<AccountId>().is_err());
+
+        assert!("PB00".parse::<AccountId>().is_err());
+        assert!("Alice".parse::<AccountId>().is_err());
+        assert!(!AccountId::default().is_assigned());
+
+        // Sequence order, even where the check digits would say otherwise.
+        let mut ids: Vec<AccountId> = (1..=30).rev().map(AccountId::new).collect();
+        assert!(
+            ids.windows(2)
+                .any(|pair| pair[0].as_str() < pair[1].as_str())
+        );
+        ids.sort();
+        assert_eq!(ids, (1..=30).map(AccountId::new).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,602 @@
+use super::{AccountId, Bank, BankError, Currency, EntryKind, Money, User};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Pending,
+    Cleared,
+    // The receiving bank couldn't credit it, so the sender got it back.
+    Returned,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterbankPayment {
+    pub id: u64,
+    pub from_bank: String,
+    pub from: AccountId,
+    pub to_bank: String,
+    pub to: AccountId,
+    // In the sender's currency.
+    pub amount: Money,
+    // What the sending bank owes the receiving one for it, in the clearing
+    // currency.
+    pub cleared: Money,
+    pub status: PaymentStatus,
+    // Why the payment couldn't be credited, when it couldn't.
+    pub reason: Option<String>,
+}
+
+// One bank's share of a clearing cycle. A positive `net` is owed to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetPosition {
+    pub bank: String,
+    pub sent: Money,
+    pub received: Money,
+    pub net: Money,
+}
+
+// What `from` pays `to` once the payments between them are netted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Obligation {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettlementReport {
+    pub cycle: u64,
+    pub currency: Currency,
+    // Every payment the cycle dealt with, cleared or returned.
+    pub payments: Vec<InterbankPayment>,
+    pub positions: Vec<NetPosition>,
+    pub obligations: Vec<Obligation>,
+}
+
+// Banks that pay each other through one clearing house. Each member keeps a
+// vostro account for every other member: the other bank's money held here,
+// which is also that bank's nostro account with this one. Payments are
+// collected until `clear` credits them all, nets what the banks owe each
+// other and settles the difference.
+pub struct ClearingHouse {
+    pub currency: Currency,
+    // How far a member's vostro account at another bank may go into debit
+    // between settlements.
+    pub debit_cap: Money,
+    banks: BTreeMap<String, Bank>,
+    // The account of the second bank at the first one.
+    vostros: BTreeMap<(String, String), AccountId>,
+    pending: Vec<InterbankPayment>,
+    next_payment: u64,
+    cycle: u64,
+}
+
+impl fmt::Display for SettlementReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cleared = self
+            .payments
+            .iter()
+            .filter(|payment| payment.status == PaymentStatus::Cleared)
+            .count();
+        write!(
+            f,
+            "Settlement cycle {}: {} payments cleared, {} returned",
+            self.cycle,
+            cleared,
+            self.payments.len() - cleared
+        )?;
+        for position in &self.positions {
+            write!(
+                f,
+                "\n  {}: sent {}, received {}, net {}",
+                position.bank,
+                position.sent.format(self.currency),
+                position.received.format(self.currency),
+                position.net.format(self.currency)
+            )?;
+        }
+        for obligation in &self.obligations {
+            write!(
+                f,
+                "\n  {} pays {} {}",
+                obligation.from,
+                obligation.to,
+                obligation.amount.format(self.currency)
+            )?;
+        }
+        for payment in &self.payments {
+            if let Some(reason) = &payment.reason {
+                write!(f, "\n  Payment #{} returned: {}", payment.id, reason)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SettlementReport {
+    // Everything cleared, before netting.
+    pub fn gross(&self) -> Money {
+        self.positions.iter().fold(Money::ZERO, |total, position| {
+            total.checked_add(position.sent).unwrap_or(Money::MAX)
+        })
+    }
+
+    // What actually changes hands after netting.
+    pub fn net(&self) -> Money {
+        self.obligations
+            .iter()
+            .fold(Money::ZERO, |total, obligation| {
+                total.checked_add(obligation.amount).unwrap_or(Money::MAX)
+            })
+    }
+}
+
+fn vostro_name(counterparty: &str) -> String {
+    format!("Vostro {}", counterparty)
+}
+
+impl ClearingHouse {
+    pub fn new(currency: Currency, debit_cap: Money) -> Self {
+        ClearingHouse {
+            currency,
+            debit_cap,
+            banks: BTreeMap::new(),
+            vostros: BTreeMap::new(),
+            pending: Vec::new(),
+            next_payment: 1,
+            cycle: 0,
+        }
+    }
+
+    // Opens vostro accounts between `bank` and every member. An account
+    // already named after the counterparty is used instead, so banks can
+    // leave and join again.
+    pub fn join(&mut self, mut bank: Bank) -> Result<(), BankError> {
+        if self.banks.contains_key(&bank.name) {
+            return Err(BankError::DuplicateBank(bank.name));
+        }
+        if self.debit_cap.is_negative() {
+            return Err(BankError::NegativeAmount(self.debit_cap));
+        }
+
+        // Checked up front so no account is opened if any can't be.
+        for member in self.banks.values() {
+            for (at, counterparty) in [(&bank, &member.name), (member, &bank.name)] {
+                let name = vostro_name(counterparty);
+                if at.find_by_name(&name).len() > 1 {
+                    return Err(BankError::AmbiguousUser(name));
+                }
+            }
+        }
+
+        let (currency, debit_cap) = (self.currency, self.debit_cap);
+        for member in self.banks.values_mut() {
+            let id = vostro_at(&mut bank, &member.name, currency, debit_cap)?;
+            self.vostros
+                .insert((bank.name.clone(), member.name.clone()), id);
+            let id = vostro_at(member, &bank.name, currency, debit_cap)?;
+            self.vostros
+                .insert((member.name.clone(), bank.name.clone()), id);
+        }
+
+        self.banks.insert(bank.name.clone(), bank);
+        Ok(())
+    }
+
+    // Takes a bank out, as long as none of its payments are still pending.
+    // Its vostro accounts stay open in both directions.
+    pub fn leave(&mut self, name: &str) -> Result<Bank, BankError> {
+        if let Some(payment) = self
+            .pending
+            .iter()
+            .find(|payment| payment.from_bank == name || payment.to_bank == name)
+        {
+            return Err(BankError::InvalidPayment(format!(
+                "payment #{} is still pending",
+                payment.id
+            )));
+        }
+
+        let bank = self
+            .banks
+            .remove(name)
+            .ok_or_else(|| BankError::BankNotFound(name.to_string()))?;
+        self.vostros
+            .retain(|(at, counterparty), _| at != name && counterparty != name);
+        Ok(bank)
+    }
+
+    pub fn bank(&self, name: &str) -> Result<&Bank, BankError> {
+        self.banks
+            .get(name)
+            .ok_or_else(|| BankError::BankNotFound(name.to_string()))
+    }
+
+    pub fn bank_mut(&mut self, name: &str) -> Result<&mut Bank, BankError> {
+        self.banks
+            .get_mut(name)
+            .ok_or_else(|| BankError::BankNotFound(name.to_string()))
+    }
+
+    pub fn banks(&self) -> impl Iterator<Item = &Bank> {
+        self.banks.values()
+    }
+
+    pub fn into_banks(self) -> Vec<Bank> {
+        self.banks.into_values().collect()
+    }
+
+    // The account `counterparty` holds at `bank`.
+    pub fn vostro(&self, bank: &str, counterparty: &str) -> Result<&User, BankError> {
+        let id = self.vostro_id(bank, counterparty)?;
+        self.bank(bank)?.user(id.as_str())
+    }
+
+    fn vostro_id(&self, bank: &str, counterparty: &str) -> Result<AccountId, BankError> {
+        self.bank(bank)?;
+        self.bank(counterparty)?;
+        self.vostros
+            .get(&(bank.to_string(), counterparty.to_string()))
+            .cloned()
+            .ok_or_else(|| BankError::UserNotFound(vostro_name(counterparty)))
+    }
+
+    pub fn pending(&self) -> &[InterbankPayment] {
+        &self.pending
+    }
+
+    // Takes the money out of `from` straight away, into the receiving bank's
+    // vostro account, and queues the payment for the next `clear`.
+    pub fn submit(
+        &mut self,
+        from_bank: &str,
+        from: &str,
+        to_bank: &str,
+        to: &str,
+        amount: Money,
+    ) -> Result<u64, BankError> {
+        if from_bank == to_bank {
+            return Err(BankError::InvalidPayment(format!(
+                "both accounts are at {}",
+                from_bank
+            )));
+        }
+        let vostro = self.vostro_id(from_bank, to_bank)?;
+        let receiver = self.bank(to_bank)?.user(to)?;
+        receiver.check_active()?;
+        let to = receiver.id.clone();
+
+        let sender = self.bank_mut(from_bank)?;
+        let from = sender.account_id(from)?;
+        sender.transfer_funds(from.as_str(), vostro.as_str(), amount)?;
+        let cleared = sender
+            .ledger()
+            .entries()
+            .last()
+            .expect("Transfer was recorded")
+            .credited_amount();
+
+        let id = self.next_payment;
+        self.next_payment += 1;
+        self.pending.push(InterbankPayment {
+            id,
+            from_bank: from_bank.to_string(),
+            from,
+            to_bank: to_bank.to_string(),
+            to,
+            amount,
+            cleared,
+            status: PaymentStatus::Pending,
+            reason: None,
+        });
+        Ok(id)
+    }
+
+    // Credits every pending payment out of the sending bank's vostro account
+    // at the receiving bank, in the order they were submitted. Payments that
+    // can't be credited go back to the sender. What each pair of banks owes
+    // the other is then netted and settled, leaving their vostro accounts
+    // as they were before the cycle.
+    pub fn clear(&mut self) -> Result<SettlementReport, BankError> {
+        self.cycle += 1;
+        let mut payments = Vec::new();
+        let mut unsettled = Vec::new();
+        for mut payment in std::mem::take(&mut self.pending) {
+            let vostro = self.vostro_id(&payment.to_bank, &payment.from_bank)?;
+            let credited = self.bank_mut(&payment.to_bank)?.transfer_funds(
+                vostro.as_str(),
+                payment.to.as_str(),
+                payment.cleared,
+            );
+            let Err(error) = credited else {
+                payment.status = PaymentStatus::Cleared;
+                payments.push(payment);
+                continue;
+            };
+
+            let vostro = self.vostro_id(&payment.from_bank, &payment.to_bank)?;
+            let refunded = self.bank_mut(&payment.from_bank)?.transfer_funds(
+                vostro.as_str(),
+                payment.from.as_str(),
+                payment.cleared,
+            );
+            payment.reason = Some(error.to_string());
+            // If the sender can't take it back either, it is tried again
+            // next cycle.
+            if refunded.is_ok() {
+                payment.status = PaymentStatus::Returned;
+                payments.push(payment);
+            } else {
+                unsettled.push(payment);
+            }
+        }
+        self.pending = unsettled;
+
+        // Owed by the first bank to the second.
+        let mut owed: BTreeMap<(&str, &str), i128> = BTreeMap::new();
+        let mut totals: BTreeMap<&str, (i128, i128)> = self
+            .banks
+            .keys()
+            .map(|name| (name.as_str(), (0, 0)))
+            .collect();
+        for payment in &payments {
+            if payment.status != PaymentStatus::Cleared {
+                continue;
+            }
+            let cleared = i128::from(payment.cleared.minor());
+            *owed
+                .entry((payment.from_bank.as_str(), payment.to_bank.as_str()))
+                .or_default() += cleared;
+            if let Some((sent, _)) = totals.get_mut(payment.from_bank.as_str()) {
+                *sent += cleared;
+            }
+            if let Some((_, received)) = totals.get_mut(payment.to_bank.as_str()) {
+                *received += cleared;
+            }
+        }
+
+        let money = |amount: i128| {
+            i64::try_from(amount)
+                .map(Money::from_minor)
+                .map_err(|_| BankError::BalanceOverflow)
+        };
+        let mut obligations = Vec::new();
+        for (&(from, to), &amount) in &owed {
+            let amount = amount - owed.get(&(to, from)).copied().unwrap_or(0);
+            if amount > 0 {
+                obligations.push(Obligation {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    amount: money(amount)?,
+                });
+            }
+        }
+        let positions = totals
+            .into_iter()
+            .map(|(bank, (sent, received))| {
+                Ok(NetPosition {
+                    bank: bank.to_string(),
+                    sent: money(sent)?,
+                    received: money(received)?,
+                    net: money(received - sent)?,
+                })
+            })
+            .collect::<Result<Vec<_>, BankError>>()?;
+
+        for obligation in &obligations {
+            let payer = self.vostro_id(&obligation.from, &obligation.to)?;
+            let payee = self.vostro_id(&obligation.to, &obligation.from)?;
+            let amount = obligation.amount;
+            self.bank_mut(&obligation.from)?
+                .settle_position(&payer, Money::from_minor(-amount.minor()))?;
+            self.bank_mut(&obligation.to)?
+                .settle_position(&payee, amount)?;
+        }
+
+        Ok(SettlementReport {
+            cycle: self.cycle,
+            currency: self.currency,
+            payments,
+            positions,
+            obligations,
+        })
+    }
+}
+
+fn vostro_at(
+    bank: &mut Bank,
+    counterparty: &str,
+    currency: Currency,
+    debit_cap: Money,
+) -> Result<AccountId, BankError> {
+    let name = vostro_name(counterparty);
+    if let [existing] = bank.find_by_name(&name).as_slice() {
+        return Ok(existing.id.clone());
+    }
+    bank.add_user(User::new(name, debit_cap, Money::ZERO).with_currency(currency))
+}
+
+impl Bank {
+    // Moves money between a vostro account and the outside world when the
+    // clearing house settles.
+    fn settle_position(&mut self, account: &AccountId, delta: Money) -> Result<u64, BankError> {
+        let snapshot = self.operation_snapshot();
+        let user = self.user_mut(account.as_str())?;
+        user.balance = user
+            .balance
+            .checked_add(delta)
+            .ok_or_else(|| BankError::TransferOverflow(user.name.clone()))?;
+
+        let balance = user.balance;
+        let entry_id =
+            self.ledger
+                .record_adjustment(EntryKind::Settlement, account.as_str(), delta, balance);
+        self.checkpoint(snapshot);
+        Ok(entry_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank(name: &str, customers: &[(&str, i64)]) -> Bank {
+        let mut bank = Bank::new(name.to_string(), 0, 0);
+        for (customer, balance) in customers {
+            bank.add_user(User::new(
+                customer.to_string(),
+                Money::ZERO,
+                Money::from_minor(*balance),
+            ))
+            .unwrap();
+        }
+        bank
+    }
+
+    fn house() -> ClearingHouse {
+        let mut house = ClearingHouse::new(Currency::EUR, Money::from_minor(100_000));
+        house.join(bank("North", &[("Alice", 50_000)])).unwrap();
+        house.join(bank("South", &[("Bob", 50_000)])).unwrap();
+        house.join(bank("West", &[("Carol", 50_000)])).unwrap();
+        house
+    }
+
+    fn balance(house: &ClearingHouse, bank: &str, user: &str) -> Money {
+        house.bank(bank).unwrap().user(user).unwrap().balance
+    }
+
+    #[test]
+    fn test_clearing_nets_and_settles() {
+        let mut house = house();
+        assert_eq!(house.bank("North").unwrap().users.len(), 3);
+
+        house
+            .submit("North", "Alice", "South", "Bob", Money::from_minor(10_000))
+            .unwrap();
+        house
+            .submit("South", "Bob", "North", "Alice", Money::from_minor(3000))
+            .unwrap();
+        house
+            .submit("South", "Bob", "West", "Carol", Money::from_minor(5000))
+            .unwrap();
+        house
+            .submit("West", "Carol", "North", "Alice", Money::from_minor(2000))
+            .unwrap();
+
+        // Senders are debited straight away, receivers only once cleared.
+        assert_eq!(balance(&house, "North", "Alice"), Money::from_minor(40_000));
+        assert_eq!(balance(&house, "South", "Bob"), Money::from_minor(42_000));
+        assert_eq!(
+            house.vostro("North", "South").unwrap().balance,
+            Money::from_minor(10_000)
+        );
+        assert_eq!(house.pending().len(), 4);
+
+        let report = house.clear().unwrap();
+        assert!(house.pending().is_empty());
+        assert_eq!(balance(&house, "North", "Alice"), Money::from_minor(45_000));
+        assert_eq!(balance(&house, "South", "Bob"), Money::from_minor(52_000));
+        assert_eq!(balance(&house, "West", "Carol"), Money::from_minor(53_000));
+
+        assert_eq!(report.cycle, 1);
+        assert_eq!(report.gross(), Money::from_minor(20_000));
+        assert_eq!(report.net(), Money::from_minor(14_000));
+        let obligations: Vec<(&str, &str, Money)> = report
+            .obligations
+            .iter()
+            .map(|obligation| {
+                (
+                    obligation.from.as_str(),
+                    obligation.to.as_str(),
+                    obligation.amount,
+                )
+            })
+            .collect();
+        assert_eq!(
+            obligations,
+            vec![
+                ("North", "South", Money::from_minor(7000)),
+                ("South", "West", Money::from_minor(5000)),
+                ("West", "North", Money::from_minor(2000)),
+            ]
+        );
+        let nets: Vec<Money> = report
+            .positions
+            .iter()
+            .map(|position| position.net)
+            .collect();
+        assert_eq!(
+            nets,
+            vec![
+                Money::from_minor(-5000),
+                Money::from_minor(2000),
+                Money::from_minor(3000)
+            ]
+        );
+        assert!(report.to_string().contains("North pays South €70.00"));
+
+        // Settlement leaves every vostro account flat and the books intact.
+        for bank in house.banks() {
+            for other in house.banks().filter(|other| other.name != bank.name) {
+                assert_eq!(
+                    house.vostro(&bank.name, &other.name).unwrap().balance,
+                    Money::ZERO
+                );
+            }
+            assert!(bank.reconcile().unwrap().is_empty());
+            assert!(bank.verify_books().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_returned_payments_and_errors() {
+        let mut house = ClearingHouse::new(Currency::EUR, Money::from_minor(5000));
+        house.join(bank("North", &[("Alice", 50_000)])).unwrap();
+        house.join(bank("South", &[("Bob", 0)])).unwrap();
+        assert!(matches!(
+            house.join(bank("South", &[])),
+            Err(BankError::DuplicateBank(_))
+        ));
+
+        let first = house
+            .submit("North", "Alice", "South", "Bob", Money::from_minor(4000))
+            .unwrap();
+        // More than North's vostro account at South may owe before settling.
+        let second = house
+            .submit("North", "Alice", "South", "Bob", Money::from_minor(2000))
+            .unwrap();
+        assert!(matches!(
+            house.submit("North", "Alice", "North", "Alice", Money::from_minor(1)),
+            Err(BankError::InvalidPayment(_))
+        ));
+        assert!(matches!(
+            house.submit("North", "Alice", "East", "Bob", Money::from_minor(1)),
+            Err(BankError::BankNotFound(_))
+        ));
+        assert!(matches!(
+            house.submit("North", "Alice", "South", "Nobody", Money::from_minor(1)),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert!(matches!(
+            house.leave("South"),
+            Err(BankError::InvalidPayment(_))
+        ));
+
+        let report = house.clear().unwrap();
+        assert_eq!(report.payments[0].id, first);
+        assert_eq!(report.payments[0].status, PaymentStatus::Cleared);
+        assert_eq!(report.payments[1].id, second);
+        assert_eq!(report.payments[1].status, PaymentStatus::Returned);
+        assert!(report.to_string().contains("Payment #2 returned"));
+        assert_eq!(balance(&house, "North", "Alice"), Money::from_minor(46_000));
+        assert_eq!(balance(&house, "South", "Bob"), Money::from_minor(4000));
+
+        // The vostro accounts are found again after leaving and rejoining.
+        let south = house.leave("South").unwrap();
+        house.join(south).unwrap();
+        assert_eq!(house.bank("South").unwrap().users.len(), 2);
+    }
+}
@@ -0,0 +1,634 @@
+use super::events::EventBus;
+use super::snapshot::History;
+use super::{
+    AccountChange, AccountId, AccountStatus, Bank, BankError, BankEvent, Currency, Denial,
+    EntryKind, ExchangeRates, InterestConfig, Ledger, Loan, Money, Rounding, StagedTransfer,
+    StandingOrder, TransferCheck, TransferRule, User, rules,
+};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+// A bank that can be shared between threads. Every account sits behind its
+// own mutex so transfers between disjoint pairs of users run in parallel; the
+// user map itself is only write-locked to add new accounts.
+//
+// Transfers always lock the two accounts in id order, so two threads moving
+// money between the same pair in opposite directions can't deadlock.
+//
+// Nothing done here can be undone on its own. The undo history is carried
+// through, so once the bank is converted back, undoing an operation from
+// before also takes back everything done concurrently since.
+pub struct ConcurrentBank {
+    pub name: String,
+    pub credit_interest: u64,
+    pub debit_interest: u64,
+    exchange_rates: ExchangeRates,
+    fx_rounding: Rounding,
+    currency: Currency,
+    // Date-range accrual, standing orders, sessions and undo aren't offered
+    // concurrently; these are only kept so that converting back into a
+    // `Bank` doesn't lose them.
+    interest_config: InterestConfig,
+    interest_carry: HashMap<String, i64>,
+    standing_orders: Vec<StandingOrder>,
+    loans: Vec<Loan>,
+    denials: Vec<Denial>,
+    rules: Vec<Box<dyn TransferRule>>,
+    history: History,
+    // Handlers are registered on the `Bank` before it is shared. They are
+    // called after the operation has released its locks.
+    events: EventBus,
+    users: RwLock<HashMap<String, Arc<Mutex<User>>>>,
+    // Account ids by holder name. Never locked together with `users` by a
+    // reader; `add_user` takes `users` first.
+    names: RwLock<HashMap<String, Vec<String>>>,
+    last_sequence: AtomicU64,
+    last_customer: AtomicU64,
+    ledger: Mutex<Ledger>,
+}
+
+impl From<Bank> for ConcurrentBank {
+    fn from(bank: Bank) -> Self {
+        let last_sequence = bank.next_account_id().sequence().unwrap_or(1) - 1;
+        let last_customer = bank.next_customer() - 1;
+
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        for user in bank.users.values() {
+            names
+                .entry(user.name.clone())
+                .or_default()
+                .push(user.id.to_string());
+        }
+
+        let users = bank
+            .users
+            .into_iter()
+            .map(|(id, user)| (id, Arc::new(Mutex::new(user))))
+            .collect();
+
+        ConcurrentBank {
+            name: bank.name,
+            credit_interest: bank.credit_interest,
+            debit_interest: bank.debit_interest,
+            exchange_rates: bank.exchange_rates,
+            fx_rounding: bank.fx_rounding,
+            currency: bank.currency,
+            interest_config: bank.interest_config,
+            interest_carry: bank.interest_carry,
+            standing_orders: bank.standing_orders,
+            loans: bank.loans,
+            denials: bank.denials,
+            rules: bank.rules,
+            history: bank.history,
+            events: bank.events,
+            users: RwLock::new(users),
+            names: RwLock::new(names),
+            last_sequence: AtomicU64::new(last_sequence),
+            last_customer: AtomicU64::new(last_customer),
+            ledger: Mutex::new(bank.ledger),
+        }
+    }
+}
+
+fn lock(account: &Mutex<User>) -> MutexGuard<'_, User> {
+    account.lock().expect("Account lock poisoned")
+}
+
+impl ConcurrentBank {
+    pub fn new(name: String, credit_interest: u64, debit_interest: u64) -> Self {
+        ConcurrentBank::from(Bank::new(name, credit_interest, debit_interest))
+    }
+
+    pub fn into_bank(self) -> Bank {
+        let users = self
+            .users
+            .into_inner()
+            .expect("User map lock poisoned")
+            .into_iter()
+            .map(|(id, account)| {
+                let user = match Arc::try_unwrap(account) {
+                    Ok(account) => account.into_inner().expect("Account lock poisoned"),
+                    Err(account) => lock(&account).clone(),
+                };
+                (id, user)
+            })
+            .collect();
+
+        let mut bank = Bank::new(self.name, self.credit_interest, self.debit_interest);
+        bank.currency = self.currency;
+        bank.exchange_rates = self.exchange_rates;
+        bank.fx_rounding = self.fx_rounding;
+        bank.interest_config = self.interest_config;
+        bank.interest_carry = self.interest_carry;
+        bank.standing_orders = self.standing_orders;
+        bank.loans = self.loans;
+        bank.denials = self.denials;
+        bank.rules = self.rules;
+        bank.history = self.history;
+        bank.events = self.events;
+        bank.users = users;
+        bank.ledger = self.ledger.into_inner().expect("Ledger lock poisoned");
+        bank
+    }
+
+    fn by_id(&self, id: &str) -> Option<Arc<Mutex<User>>> {
+        self.users
+            .read()
+            .expect("User map lock poisoned")
+            .get(id)
+            .cloned()
+    }
+
+    // Same resolution as `Bank::account_id`: an id, or a name carried by
+    // exactly one (open, if that settles it) account.
+    fn account(&self, reference: &str) -> Result<(String, Arc<Mutex<User>>), BankError> {
+        if let Some(account) = self.by_id(reference) {
+            return Ok((reference.to_string(), account));
+        }
+        if let Ok(id) = reference.parse::<AccountId>()
+            && let Some(account) = self.by_id(id.as_str())
+        {
+            return Ok((id.to_string(), account));
+        }
+
+        let ids = self
+            .names
+            .read()
+            .expect("Name index lock poisoned")
+            .get(reference)
+            .cloned()
+            .unwrap_or_default();
+        let mut named: Vec<(String, Arc<Mutex<User>>)> = ids
+            .into_iter()
+            .filter_map(|id| self.by_id(&id).map(|account| (id, account)))
+            .collect();
+        if named.len() > 1 {
+            named.retain(|(_, account)| lock(account).status != AccountStatus::Closed);
+        }
+        match named.len() {
+            1 => Ok(named.remove(0)),
+            0 => Err(BankError::UserNotFound(reference.to_string())),
+            _ => Err(BankError::AmbiguousUser(reference.to_string())),
+        }
+    }
+
+    // Like `Bank::add_user`, refuses names that are already taken.
+    pub fn add_user(&self, mut user: User) -> Result<AccountId, BankError> {
+        let mut users = self.users.write().expect("User map lock poisoned");
+        let mut names = self.names.write().expect("Name index lock poisoned");
+        if names.contains_key(&user.name) {
+            return Err(BankError::DuplicateUser(user.name));
+        }
+        if user.credit_line.is_negative() {
+            return Err(BankError::NegativeCreditLine(user.name));
+        }
+
+        user.id = AccountId::new(self.last_sequence.fetch_add(1, Ordering::Relaxed) + 1);
+        user.customer = self.last_customer.fetch_add(1, Ordering::Relaxed) + 1;
+        self.ledger
+            .lock()
+            .expect("Ledger lock poisoned")
+            .record_adjustment(
+                EntryKind::Opening,
+                user.id.as_str(),
+                user.balance,
+                user.balance,
+            );
+
+        let id = user.id.clone();
+        let event = BankEvent::UserAdded { user: user.clone() };
+        names.insert(user.name.clone(), vec![id.to_string()]);
+        users.insert(id.to_string(), Arc::new(Mutex::new(user)));
+        drop((users, names));
+
+        self.events.publish(event);
+        Ok(id)
+    }
+
+    pub fn user(&self, reference: &str) -> Option<User> {
+        self.account(reference)
+            .ok()
+            .map(|(_, account)| lock(&account).clone())
+    }
+
+    pub fn balance(&self, reference: &str) -> Option<Money> {
+        self.account(reference)
+            .ok()
+            .map(|(_, account)| lock(&account).balance)
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.users.read().expect("User map lock poisoned").len()
+    }
+
+    pub fn transfer_funds(&self, from: &str, to: &str, amount: Money) -> Result<(), BankError> {
+        match self.apply_transfer(from, to, amount) {
+            Ok(event) => {
+                self.events.publish(event);
+                Ok(())
+            }
+            Err(error) => {
+                self.events
+                    .publish(BankEvent::rejected(from, to, amount, &error));
+                Err(error)
+            }
+        }
+    }
+
+    fn apply_transfer(&self, from: &str, to: &str, amount: Money) -> Result<BankEvent, BankError> {
+        let (from, from_account) = self.account(from)?;
+        let (to, to_account) = self.account(to)?;
+        let (from, to) = (from.as_str(), to.as_str());
+
+        if Arc::ptr_eq(&from_account, &to_account) {
+            let user = lock(&from_account);
+            user.check_active()?;
+            // Moving money to the same account only has to respect the
+            // credit line; the balance itself is unchanged.
+            user.checked_debit(user.balance, amount)?;
+            self.check_rules(&user, &user, amount)?;
+
+            let balance = user.balance;
+            let staged = StagedTransfer {
+                from_balance: balance,
+                to_balance: balance,
+                credited: amount,
+                fee: Money::ZERO,
+            };
+            let entry_id = self
+                .ledger
+                .lock()
+                .expect("Ledger lock poisoned")
+                .record_transfer(from, to, amount, staged);
+            return Ok(BankEvent::transfer(
+                entry_id,
+                (&user, &user),
+                amount,
+                (balance, balance),
+                &staged,
+            ));
+        }
+
+        let (mut from_user, mut to_user) = if from < to {
+            let from_user = lock(&from_account);
+            (from_user, lock(&to_account))
+        } else {
+            let to_user = lock(&to_account);
+            (lock(&from_account), to_user)
+        };
+
+        from_user.check_active()?;
+        to_user.check_active()?;
+        let (new_from_balance, fee) = from_user.debit_with_fee(from_user.balance, amount)?;
+
+        // Rules look at what the sender has sent, and the sender stays locked
+        // from the checks until the transfer is recorded, so two transfers
+        // can't both slip under a limit.
+        self.check_rules(&from_user, &to_user, amount)?;
+
+        let credited = self.exchange_rates.convert(
+            amount,
+            from_user.currency,
+            to_user.currency,
+            self.fx_rounding,
+        )?;
+
+        let Some(new_to_balance) = to_user.balance.checked_add(credited) else {
+            return Err(BankError::TransferOverflow(to_user.name.clone()));
+        };
+
+        let before = (from_user.balance, to_user.balance);
+        from_user.balance = new_from_balance;
+        to_user.balance = new_to_balance;
+
+        // Recorded while both accounts are still locked so the balances in the
+        // ledger follow the order in which the transfers were applied.
+        let staged = StagedTransfer {
+            from_balance: new_from_balance,
+            to_balance: new_to_balance,
+            credited,
+            fee,
+        };
+        let entry_id = self
+            .ledger
+            .lock()
+            .expect("Ledger lock poisoned")
+            .record_transfer(from, to, amount, staged);
+
+        Ok(BankEvent::transfer(
+            entry_id,
+            (&from_user, &to_user),
+            amount,
+            before,
+            &staged,
+        ))
+    }
+
+    // Only takes the ledger lock when there are rules to look at it.
+    fn check_rules(&self, from: &User, to: &User, amount: Money) -> Result<(), BankError> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+
+        let ledger = self.ledger.lock().expect("Ledger lock poisoned");
+        rules::check_rules(
+            &self.rules,
+            &TransferCheck {
+                from,
+                to,
+                amount,
+                at: Utc::now(),
+                ledger: &ledger,
+                pending: &[],
+            },
+        )
+    }
+
+    // Like `Bank::accrue_interest`, every account's interest is worked out
+    // before any is applied, so an overflow leaves all balances as they were.
+    pub fn accrue_interest(&self) -> Result<(), BankError> {
+        let mut accounts: Vec<(String, Arc<Mutex<User>>)> = self
+            .users
+            .read()
+            .expect("User map lock poisoned")
+            .iter()
+            .map(|(id, account)| (id.clone(), Arc::clone(account)))
+            .collect();
+
+        // All accounts stay locked for the run, taken in the same order as
+        // transfers take them.
+        accounts.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut users: Vec<MutexGuard<'_, User>> =
+            accounts.iter().map(|(_, account)| lock(account)).collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut accruals = Vec::new();
+        for (index, user) in users.iter().enumerate() {
+            if !user.is_active() {
+                continue;
+            }
+            let interest = user.accrued_interest(self.credit_interest, self.debit_interest)?;
+            if interest.is_zero() {
+                continue;
+            }
+
+            let Some(balance) = user.balance.checked_add(interest) else {
+                return Err(BankError::InterestOverflow(user.name.clone()));
+            };
+            accruals.push((index, interest, balance));
+        }
+
+        let mut ledger = self.ledger.lock().expect("Ledger lock poisoned");
+        let mut events = Vec::new();
+        for (index, interest, balance) in accruals {
+            let user = &mut users[index];
+            let before = User::clone(user);
+            user.balance = balance;
+
+            let entry_id =
+                ledger.record_adjustment(EntryKind::Interest, user.id.as_str(), interest, balance);
+            events.push(BankEvent::InterestAccrued {
+                entry_id,
+                account: AccountChange {
+                    before,
+                    after: User::clone(user),
+                },
+                interest,
+            });
+        }
+        drop((ledger, users));
+
+        for event in events {
+            self.events.publish(event);
+        }
+        Ok(())
+    }
+
+    // Delivers events queued for buffered handlers, like `Bank::flush_events`.
+    pub fn flush_events(&self) -> usize {
+        self.events.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Principal, Role};
+    use std::thread;
+
+    #[test]
+    fn test_transfer_funds() {
+        let bank = ConcurrentBank::new("Test Bank".to_string(), 500, 300);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(500),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(2000),
+            Money::from_minor(200),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            bank.add_user(User::new(
+                "Bob".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(0)
+            )),
+            Err(BankError::DuplicateUser("Bob".to_string()))
+        );
+
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+            .unwrap();
+        assert_eq!(bank.balance("Alice"), Some(Money::from_minor(200)));
+        assert_eq!(bank.balance("Bob"), Some(Money::from_minor(500)));
+
+        assert_eq!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(1500)),
+            Err(BankError::InsufficientCredit {
+                user: "Alice".to_string(),
+                amount: Money::from_minor(1500)
+            })
+        );
+        assert_eq!(
+            bank.transfer_funds("Alice", "Carol", Money::from_minor(1)),
+            Err(BankError::UserNotFound("Carol".to_string()))
+        );
+
+        bank.transfer_funds("Bob", "Bob", Money::from_minor(100))
+            .unwrap();
+        assert_eq!(bank.balance("Bob"), Some(Money::from_minor(500)));
+
+        bank.accrue_interest().unwrap();
+        assert_eq!(bank.balance("Alice"), Some(Money::from_minor(206)));
+        assert_eq!(bank.balance("Bob"), Some(Money::from_minor(515)));
+
+        let bank = bank.into_bank();
+        assert_eq!(bank.ledger().len(), 6);
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_interest_overflow_changes_nothing() {
+        let bank = ConcurrentBank::new("Test Bank".to_string(), 0, 1000);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(1000),
+        ))
+        .unwrap();
+        bank.add_user(User::new("Bob".to_string(), Money::ZERO, Money::MAX))
+            .unwrap();
+
+        assert_eq!(
+            bank.accrue_interest(),
+            Err(BankError::InterestOverflow("Bob".to_string()))
+        );
+        assert_eq!(bank.balance("Alice"), Some(Money::from_minor(1000)));
+        assert_eq!(bank.balance("Bob"), Some(Money::MAX));
+        assert_eq!(bank.into_bank().ledger().len(), 2);
+    }
+
+    #[test]
+    fn test_undo_history_is_kept() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        for name in ["Alice", "Bob"] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::ZERO,
+                Money::from_minor(1000),
+            ))
+            .unwrap();
+        }
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(100))
+            .unwrap();
+
+        let mut bank = ConcurrentBank::from(bank).into_bank();
+        assert_eq!(bank.undo_depth(), 3);
+        bank.undo(1).unwrap();
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(1000));
+
+        // Undoing goes back past whatever was done concurrently.
+        let concurrent = ConcurrentBank::from(bank);
+        concurrent
+            .transfer_funds("Bob", "Alice", Money::from_minor(300))
+            .unwrap();
+        let mut bank = concurrent.into_bank();
+        assert_eq!(bank.undo_depth(), 2);
+        bank.undo(1).unwrap();
+        assert!(bank.user("Bob").is_err());
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(1000));
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rules_hold_under_contention() {
+        let mut bank = Bank::new("Limit Bank".to_string(), 0, 0);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(100_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new("Bob".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.add_rule(crate::bank::DailyLimit {
+            limit: Money::from_minor(1_000),
+        });
+        bank.session(Principal::new("bob", Role::Customer(2)))
+            .accrue_interest()
+            .unwrap_err();
+
+        let bank = Arc::new(ConcurrentBank::from(bank));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let bank = Arc::clone(&bank);
+                thread::spawn(move || {
+                    (0..10)
+                        .filter(|_| {
+                            bank.transfer_funds("Alice", "Bob", Money::from_minor(30))
+                                .is_ok()
+                        })
+                        .count()
+                })
+            })
+            .collect();
+        let accepted: usize = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum();
+
+        assert_eq!(accepted, 33);
+        assert_eq!(bank.balance("Bob"), Some(Money::from_minor(990)));
+        let bank = Arc::try_unwrap(bank).ok().unwrap().into_bank();
+        assert_eq!(bank.rules().collect::<Vec<_>>(), vec!["daily-limit"]);
+        assert_eq!(bank.denials().len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_transfers_conserve_money() {
+        const USERS: usize = 8;
+        const THREADS: u64 = 8;
+        const TRANSFERS: u64 = 2000;
+
+        let bank = Arc::new(ConcurrentBank::new("Stress Bank".to_string(), 0, 0));
+        for index in 0..USERS {
+            bank.add_user(User::new(
+                format!("user{}", index),
+                Money::from_minor(500),
+                Money::from_minor(1000),
+            ))
+            .unwrap();
+        }
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|thread_id| {
+                let bank = Arc::clone(&bank);
+                thread::spawn(move || {
+                    // Small LCG so every thread walks its own deterministic path.
+                    let mut state = thread_id.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    let mut next = move || {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        state >> 33
+                    };
+
+                    for _ in 0..TRANSFERS {
+                        let from = format!("user{}", next() as usize % USERS);
+                        let to = format!("user{}", next() as usize % USERS);
+                        let amount = Money::from_minor((next() % 700) as i64);
+
+                        match bank.transfer_funds(&from, &to, amount) {
+                            Ok(()) | Err(BankError::InsufficientCredit { .. }) => {}
+                            Err(error) => panic!("Unexpected transfer error: {}", error),
+                        }
+
+                        // Opposite direction between the same pair, to provoke
+                        // lock-order deadlocks if there were any.
+                        let _ =
+                            bank.transfer_funds(&to, &from, Money::from_minor(amount.minor() / 2));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let bank = Arc::try_unwrap(bank).ok().unwrap().into_bank();
+        let total: i64 = bank.users.values().map(|user| user.balance.minor()).sum();
+        assert_eq!(total, USERS as i64 * 1000);
+
+        for user in bank.users.values() {
+            assert!(user.balance.minor() >= -user.credit_line.minor());
+        }
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use super::{BankError, Money, Rounding};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Exchange rates are stored as fixed-point integers: a rate of 1_000_000 means
+// one unit of the source currency buys one unit of the target currency.
+pub const RATE_SCALE: u64 = 1_000_000;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum Currency {
+    #[default]
+    EUR,
+    USD,
+    AED,
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Currency::EUR => "EUR",
+            Currency::USD => "USD",
+            Currency::AED => "AED",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl Currency {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Currency::EUR => "€",
+            Currency::USD => "$",
+            Currency::AED => "AED ",
+        }
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "EUR" => Ok(Currency::EUR),
+            "USD" => Ok(Currency::USD),
+            "AED" => Ok(Currency::AED),
+            _ => Err(format!("Unknown currency '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExchangeRates {
+    rates: HashMap<(Currency, Currency), u64>,
+}
+
+impl ExchangeRates {
+    pub fn new() -> Self {
+        ExchangeRates {
+            rates: HashMap::new(),
+        }
+    }
+
+    // Rates are directional; the inverse pair has to be configured separately
+    // so that buy and sell rates can differ.
+    pub fn set_rate(&mut self, from: Currency, to: Currency, rate: u64) {
+        self.rates.insert((from, to), rate);
+    }
+
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<u64> {
+        if from == to {
+            return Some(RATE_SCALE);
+        }
+        self.rates.get(&(from, to)).copied()
+    }
+
+    pub fn rates(&self) -> impl Iterator<Item = (Currency, Currency, u64)> + '_ {
+        self.rates
+            .iter()
+            .map(|(&(from, to), &rate)| (from, to, rate))
+    }
+
+    pub fn convert(
+        &self,
+        amount: Money,
+        from: Currency,
+        to: Currency,
+        rounding: Rounding,
+    ) -> Result<Money, BankError> {
+        let rate = self
+            .rate(from, to)
+            .ok_or(BankError::NoExchangeRate { from, to })?;
+
+        let converted = rounding.divide(
+            i128::from(amount.minor()) * i128::from(rate),
+            i128::from(RATE_SCALE),
+        );
+
+        i64::try_from(converted)
+            .map(Money::from_minor)
+            .map_err(|_| BankError::ConversionOverflow { from, to })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_currency() {
+        assert_eq!("usd".parse::<Currency>(), Ok(Currency::USD));
+        assert_eq!("AED".parse::<Currency>(), Ok(Currency::AED));
+        assert!("GBP".parse::<Currency>().is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut rates = ExchangeRates::new();
+        rates.set_rate(Currency::EUR, Currency::USD, 1_085_000);
+
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(1000),
+                Currency::EUR,
+                Currency::USD,
+                Rounding::HalfEven
+            ),
+            Ok(Money::from_minor(1085))
+        );
+        // 15 * 1.085 = 16.275
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(15),
+                Currency::EUR,
+                Currency::USD,
+                Rounding::Truncate
+            ),
+            Ok(Money::from_minor(16))
+        );
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(-15),
+                Currency::EUR,
+                Currency::USD,
+                Rounding::Truncate
+            ),
+            Ok(Money::from_minor(-16))
+        );
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(42),
+                Currency::AED,
+                Currency::AED,
+                Rounding::HalfUp
+            ),
+            Ok(Money::from_minor(42))
+        );
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(100),
+                Currency::USD,
+                Currency::EUR,
+                Rounding::HalfEven
+            ),
+            Err(BankError::NoExchangeRate {
+                from: Currency::USD,
+                to: Currency::EUR
+            })
+        );
+        assert_eq!(
+            rates.convert(
+                Money::from_minor(i64::MAX),
+                Currency::EUR,
+                Currency::USD,
+                Rounding::HalfEven
+            ),
+            Err(BankError::ConversionOverflow {
+                from: Currency::EUR,
+                to: Currency::USD
+            })
+        );
+    }
+}
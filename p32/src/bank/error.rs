@@ -0,0 +1,161 @@
+use super::{Currency, Denial, Money, Rejection};
+use chrono::NaiveDate;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BankError {
+    UserNotFound(String),
+    DuplicateUser(String),
+    InsufficientCredit { user: String, amount: Money },
+    NegativeAmount(Money),
+    NegativeCreditLine(String),
+    CreditLineTooLarge(String),
+    TransferOverflow(String),
+    InterestOverflow(String),
+    MergeOverflow(String),
+    MergeConflict(String),
+    InterestRateOverflow,
+    LedgerOverflow(u64),
+    BatchTransferFailed { index: usize, error: Box<BankError> },
+    NoExchangeRate { from: Currency, to: Currency },
+    ConversionOverflow { from: Currency, to: Currency },
+    BalanceOverflow,
+    InvalidDateRange { start: NaiveDate, end: NaiveDate },
+    StandingOrderNotFound(u64),
+    InvalidSchedule(String),
+    AccountFrozen(String),
+    AccountClosed(String),
+    AccountNotSettled { user: String, balance: Money },
+    KycRejected(String),
+    TransferRejected(Rejection),
+    AmbiguousUser(String),
+    CustomerNotFound(u64),
+    LoanNotFound(u64),
+    InvalidLoan(String),
+    LoanRepaid(u64),
+    LoanOutstanding { user: String, outstanding: Money },
+    BooksUnbalanced(String),
+    SnapshotUnavailable(u64),
+    NothingToUndo { requested: usize, available: usize },
+    BankNotFound(String),
+    DuplicateBank(String),
+    InvalidPayment(String),
+    AccessDenied(Denial),
+}
+
+impl fmt::Display for BankError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BankError::UserNotFound(name) => write!(f, "User '{}' not found", name),
+            BankError::DuplicateUser(name) => write!(f, "User '{}' already exists", name),
+            BankError::InsufficientCredit { user, amount } => write!(
+                f,
+                "Insufficient credit line for user '{}' to transfer {}",
+                user, amount
+            ),
+            BankError::NegativeAmount(amount) => {
+                write!(f, "Amount {} must not be negative", amount)
+            }
+            BankError::NegativeCreditLine(name) => {
+                write!(f, "Credit line of user '{}' must not be negative", name)
+            }
+            BankError::CreditLineTooLarge(name) => {
+                write!(f, "Credit line too large to process for user '{}'", name)
+            }
+            BankError::TransferOverflow(name) => {
+                write!(f, "Arithmetic overflow in transfer for user '{}'", name)
+            }
+            BankError::InterestOverflow(name) => {
+                write!(f, "Interest calculation overflow for user '{}'", name)
+            }
+            BankError::MergeOverflow(name) => {
+                write!(f, "Balance overflow when merging user '{}'", name)
+            }
+            BankError::MergeConflict(name) => {
+                write!(f, "User '{}' conflicts with an existing account", name)
+            }
+            BankError::InterestRateOverflow => write!(f, "Interest rate overflow during merge"),
+            BankError::LedgerOverflow(id) => {
+                write!(f, "Balance overflow replaying ledger entry #{}", id)
+            }
+            BankError::BatchTransferFailed { index, error } => {
+                write!(f, "Batch transfer #{} failed: {}", index, error)
+            }
+            BankError::NoExchangeRate { from, to } => {
+                write!(f, "No exchange rate configured from {} to {}", from, to)
+            }
+            BankError::ConversionOverflow { from, to } => {
+                write!(f, "Overflow converting from {} to {}", from, to)
+            }
+            BankError::BalanceOverflow => write!(f, "Overflow in balance calculation"),
+            BankError::InvalidDateRange { start, end } => {
+                write!(f, "Invalid date range: {} is after {}", start, end)
+            }
+            BankError::StandingOrderNotFound(id) => {
+                write!(f, "Standing order #{} not found", id)
+            }
+            BankError::InvalidSchedule(schedule) => write!(f, "Invalid schedule '{}'", schedule),
+            BankError::AccountFrozen(name) => write!(f, "Account '{}' is frozen", name),
+            BankError::AccountClosed(name) => write!(f, "Account '{}' is closed", name),
+            BankError::AccountNotSettled { user, balance } => write!(
+                f,
+                "Account '{}' can't be closed with a balance of {}",
+                user, balance
+            ),
+            BankError::KycRejected(name) => {
+                write!(f, "KYC check for '{}' was rejected", name)
+            }
+            BankError::TransferRejected(rejection) => {
+                write!(f, "Transfer rejected by {}", rejection)
+            }
+            BankError::AmbiguousUser(name) => write!(
+                f,
+                "Several accounts are named '{}'; use an account id",
+                name
+            ),
+            BankError::CustomerNotFound(customer) => {
+                write!(f, "Customer #{} not found", customer)
+            }
+            BankError::LoanNotFound(id) => write!(f, "Loan #{} not found", id),
+            BankError::InvalidLoan(reason) => write!(f, "Invalid loan: {}", reason),
+            BankError::LoanRepaid(id) => write!(f, "Loan #{} is already repaid", id),
+            BankError::LoanOutstanding { user, outstanding } => write!(
+                f,
+                "Account '{}' can't be closed with {} of loans outstanding",
+                user, outstanding
+            ),
+            BankError::BooksUnbalanced(reason) => {
+                write!(f, "Books don't balance: {}", reason)
+            }
+            BankError::SnapshotUnavailable(entry_id) => write!(
+                f,
+                "Snapshot at ledger entry #{} can't be restored any more",
+                entry_id
+            ),
+            BankError::NothingToUndo {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Can't undo {} operations, only {} recorded",
+                requested, available
+            ),
+            BankError::BankNotFound(name) => write!(f, "Bank '{}' not found", name),
+            BankError::DuplicateBank(name) => {
+                write!(f, "Bank '{}' is already a member", name)
+            }
+            BankError::InvalidPayment(reason) => write!(f, "Invalid payment: {}", reason),
+            BankError::AccessDenied(denial) => write!(f, "Access denied: {}", denial),
+        }
+    }
+}
+
+impl Error for BankError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BankError::BatchTransferFailed { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
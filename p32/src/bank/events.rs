@@ -0,0 +1,382 @@
+use super::{Bank, BankError, Denial, MergePlan, Money, StagedTransfer, User};
+use std::mem;
+use std::sync::Mutex;
+
+// An account as it was right before and right after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountChange {
+    pub before: User,
+    pub after: User,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BankEvent {
+    UserAdded {
+        user: User,
+    },
+    // The accounts are boxed so the other events don't pay for four users.
+    TransferCompleted {
+        entry_id: u64,
+        from: Box<AccountChange>,
+        to: Box<AccountChange>,
+        // In the sender's currency; `to` was credited the converted amount.
+        amount: Money,
+    },
+    // Any transfer that didn't go through, whether a rule refused it or the
+    // accounts couldn't cover it. `from` and `to` are as the caller gave them.
+    TransferRejected {
+        from: String,
+        to: String,
+        amount: Money,
+        error: BankError,
+    },
+    InterestAccrued {
+        entry_id: u64,
+        account: AccountChange,
+        interest: Money,
+    },
+    BankMerged {
+        plan: MergePlan,
+    },
+    // A session tried something its role doesn't allow.
+    AccessDenied {
+        denial: Denial,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    // Called from inside the operation, before it returns.
+    Sync,
+    // Queued until `flush_events` is called.
+    Buffered,
+}
+
+struct Subscription {
+    id: u64,
+    delivery: Delivery,
+    handler: Box<dyn Fn(&BankEvent) + Send + Sync>,
+}
+
+// Handlers registered on a bank. Like transfer rules, they aren't saved with
+// the bank or carried over by a merge.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscriptions: Vec<Subscription>,
+    // Events waiting for the buffered handlers. Behind a lock so that shared
+    // references can publish, as `ConcurrentBank` does.
+    pending: Mutex<Vec<BankEvent>>,
+}
+
+impl AccountChange {
+    pub fn delta(&self) -> Money {
+        self.after
+            .balance
+            .checked_sub(self.before.balance)
+            .unwrap_or(Money::MAX)
+    }
+}
+
+impl BankEvent {
+    // A completed transfer between two accounts as they are now, given what
+    // their balances were before it and what it left them at.
+    pub(crate) fn transfer(
+        entry_id: u64,
+        (from, to): (&User, &User),
+        amount: Money,
+        (from_before, to_before): (Money, Money),
+        staged: &StagedTransfer,
+    ) -> Self {
+        let change = |user: &User, before: Money, after: Money| {
+            Box::new(AccountChange {
+                before: User {
+                    balance: before,
+                    ..user.clone()
+                },
+                after: User {
+                    balance: after,
+                    ..user.clone()
+                },
+            })
+        };
+        BankEvent::TransferCompleted {
+            entry_id,
+            from: change(from, from_before, staged.from_balance),
+            to: change(to, to_before, staged.to_balance),
+            amount,
+        }
+    }
+
+    pub(crate) fn rejected(from: &str, to: &str, amount: Money, error: &BankError) -> Self {
+        BankEvent::TransferRejected {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            error: error.clone(),
+        }
+    }
+}
+
+impl EventBus {
+    pub(crate) fn publish(&self, event: BankEvent) {
+        let mut buffered = false;
+        for subscription in &self.subscriptions {
+            match subscription.delivery {
+                Delivery::Sync => (subscription.handler)(&event),
+                Delivery::Buffered => buffered = true,
+            }
+        }
+        if buffered {
+            self.pending
+                .lock()
+                .expect("Event queue lock poisoned")
+                .push(event);
+        }
+    }
+
+    pub(crate) fn flush(&self) -> usize {
+        let events = mem::take(&mut *self.pending.lock().expect("Event queue lock poisoned"));
+        for event in &events {
+            for subscription in &self.subscriptions {
+                if subscription.delivery == Delivery::Buffered {
+                    (subscription.handler)(event);
+                }
+            }
+        }
+        events.len()
+    }
+}
+
+impl Bank {
+    // Registers `handler` for every event from now on and returns an id for
+    // `unsubscribe`. Sync handlers run in registration order.
+    pub fn subscribe(
+        &mut self,
+        delivery: Delivery,
+        handler: impl Fn(&BankEvent) + Send + Sync + 'static,
+    ) -> u64 {
+        let id = self
+            .events
+            .subscriptions
+            .iter()
+            .map(|subscription| subscription.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        self.events.subscriptions.push(Subscription {
+            id,
+            delivery,
+            handler: Box::new(handler),
+        });
+        id
+    }
+
+    // Events already queued for a removed buffered handler are dropped with
+    // it, unless other buffered handlers are still waiting for them.
+    pub fn unsubscribe(&mut self, id: u64) -> bool {
+        let count = self.events.subscriptions.len();
+        self.events
+            .subscriptions
+            .retain(|subscription| subscription.id != id);
+        let buffered = self
+            .events
+            .subscriptions
+            .iter()
+            .any(|subscription| subscription.delivery == Delivery::Buffered);
+        if !buffered {
+            self.events
+                .pending
+                .lock()
+                .expect("Event queue lock poisoned")
+                .clear();
+        }
+        self.events.subscriptions.len() != count
+    }
+
+    // Hands every queued event to the buffered handlers, oldest first, and
+    // returns how many there were.
+    pub fn flush_events(&self) -> usize {
+        self.events.flush()
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.events
+            .pending
+            .lock()
+            .expect("Event queue lock poisoned")
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{ConcurrentBank, MergePolicy, Transfer};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 1000);
+        for (name, balance) in [("Alice", 1000), ("Bob", 0)] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::from_minor(500),
+                Money::from_minor(balance),
+            ))
+            .unwrap();
+        }
+        bank
+    }
+
+    fn recorder(bank: &mut Bank, delivery: Delivery) -> Arc<Mutex<Vec<BankEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        bank.subscribe(delivery, move |event| {
+            sink.lock().unwrap().push(event.clone())
+        });
+        events
+    }
+
+    #[test]
+    fn test_sync_handlers() {
+        let mut bank = bank();
+        let events = recorder(&mut bank, Delivery::Sync);
+
+        bank.add_user(User::new("Carol".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+            .unwrap();
+        bank.transfer_funds("Bob", "Alice", Money::from_minor(5000))
+            .unwrap_err();
+        bank.transfer_batch(&[Transfer::new("Bob", "Carol", Money::from_minor(100))])
+            .unwrap();
+        bank.accrue_interest().unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(matches!(&events[0], BankEvent::UserAdded { user } if user.name == "Carol"));
+
+        let BankEvent::TransferCompleted {
+            entry_id, from, to, ..
+        } = &events[1]
+        else {
+            panic!("expected a transfer, got {:?}", events[1]);
+        };
+        assert_eq!(*entry_id, 4);
+        assert_eq!(from.before.balance, Money::from_minor(1000));
+        assert_eq!(from.after.balance, Money::from_minor(700));
+        assert_eq!(to.delta(), Money::from_minor(300));
+
+        assert!(matches!(
+            &events[2],
+            BankEvent::TransferRejected {
+                error: BankError::InsufficientCredit { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            &events[3],
+            BankEvent::TransferCompleted { to, .. } if to.after.name == "Carol"
+        ));
+
+        let mut interest: Vec<(String, Money, Money)> = events[4..]
+            .iter()
+            .map(|event| match event {
+                BankEvent::InterestAccrued {
+                    account, interest, ..
+                } => {
+                    assert_eq!(account.delta(), *interest);
+                    (
+                        account.after.name.clone(),
+                        account.before.balance,
+                        *interest,
+                    )
+                }
+                event => panic!("expected interest, got {:?}", event),
+            })
+            .collect();
+        interest.sort();
+        assert_eq!(
+            interest,
+            vec![
+                (
+                    "Alice".to_string(),
+                    Money::from_minor(700),
+                    Money::from_minor(70)
+                ),
+                (
+                    "Bob".to_string(),
+                    Money::from_minor(200),
+                    Money::from_minor(20)
+                ),
+                (
+                    "Carol".to_string(),
+                    Money::from_minor(100),
+                    Money::from_minor(10)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buffered_handlers() {
+        let mut bank = bank();
+        let buffered = recorder(&mut bank, Delivery::Buffered);
+        let sync = recorder(&mut bank, Delivery::Sync);
+
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(100))
+            .unwrap();
+        let mut other = Bank::new("Other".to_string(), 1000, 0);
+        other
+            .add_user(User::new("Dave".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.merge_bank_with(other, &MergePolicy::default())
+            .unwrap();
+
+        assert_eq!(sync.lock().unwrap().len(), 2);
+        assert!(buffered.lock().unwrap().is_empty());
+        assert_eq!(bank.pending_events(), 2);
+
+        assert_eq!(bank.flush_events(), 2);
+        assert_eq!(bank.flush_events(), 0);
+        let events = buffered.lock().unwrap();
+        assert!(matches!(
+            &events[1],
+            BankEvent::BankMerged { plan } if plan.source == "Other"
+        ));
+        drop(events);
+
+        assert!(bank.unsubscribe(1));
+        assert!(!bank.unsubscribe(1));
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(100))
+            .unwrap();
+        assert_eq!(bank.pending_events(), 0);
+        assert_eq!(sync.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_concurrent_bank_publishes() {
+        let mut bank = bank();
+        let events = recorder(&mut bank, Delivery::Buffered);
+        let bank = ConcurrentBank::from(bank);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..5 {
+                        bank.transfer_funds("Alice", "Bob", Money::from_minor(100))
+                            .ok();
+                    }
+                });
+            }
+        });
+
+        // 15 transfers fit within Alice's balance and credit line.
+        assert_eq!(bank.flush_events(), 20);
+        let events = events.lock().unwrap();
+        let completed = events
+            .iter()
+            .filter(|event| matches!(event, BankEvent::TransferCompleted { .. }))
+            .count();
+        assert_eq!(completed, 15);
+    }
+}
@@ -0,0 +1,530 @@
+use super::{Bank, BankError, Currency, EntryKind, LedgerEntry, Money};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// The bank's own books. Customer accounts are summarised in one deposits
+// control account; everything else is the bank's side of each operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlAccount {
+    // Money entering or leaving the bank: opening balances, merged-in
+    // balances and anything else without a counterparty inside the bank.
+    Cash,
+    LoansReceivable,
+    // Owed to customers; overdrafts show up as negative deposits.
+    CustomerDeposits,
+    // Balances the two legs of a cross-currency transfer, one per currency.
+    FxPosition,
+    InterestIncome,
+    InterestExpense,
+    // Overdraft fees charged to customers.
+    FeeIncome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Posting {
+    pub account: GlAccount,
+    pub currency: Currency,
+    pub side: Side,
+    pub amount: Money,
+}
+
+// The postings for one ledger entry; they balance within each currency.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JournalEntry {
+    pub entry_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: EntryKind,
+    pub postings: Vec<Posting>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TrialBalanceRow {
+    pub account: GlAccount,
+    pub currency: Currency,
+    pub debit: Money,
+    pub credit: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrialBalance {
+    pub rows: Vec<TrialBalanceRow>,
+}
+
+impl fmt::Display for GlAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let account = match self {
+            GlAccount::Cash => "cash",
+            GlAccount::LoansReceivable => "loans-receivable",
+            GlAccount::CustomerDeposits => "customer-deposits",
+            GlAccount::FxPosition => "fx-position",
+            GlAccount::InterestIncome => "interest-income",
+            GlAccount::InterestExpense => "interest-expense",
+            GlAccount::FeeIncome => "fee-income",
+        };
+        write!(f, "{}", account)
+    }
+}
+
+impl Posting {
+    fn debit(account: GlAccount, currency: Currency, amount: Money) -> Self {
+        Posting {
+            account,
+            currency,
+            side: Side::Debit,
+            amount,
+        }
+    }
+
+    fn credit(account: GlAccount, currency: Currency, amount: Money) -> Self {
+        Posting {
+            account,
+            currency,
+            side: Side::Credit,
+            amount,
+        }
+    }
+
+    // The same amount on the other side.
+    fn reversed(self) -> Self {
+        let side = match self.side {
+            Side::Debit => Side::Credit,
+            Side::Credit => Side::Debit,
+        };
+        Posting { side, ..self }
+    }
+
+    // Debits count positive, credits negative.
+    fn signed(&self) -> i128 {
+        let amount = i128::from(self.amount.minor());
+        match self.side {
+            Side::Debit => amount,
+            Side::Credit => -amount,
+        }
+    }
+}
+
+impl TrialBalance {
+    // Debit and credit totals per currency.
+    pub fn totals(&self) -> BTreeMap<Currency, (Money, Money)> {
+        let mut totals: BTreeMap<Currency, (Money, Money)> = BTreeMap::new();
+        for row in &self.rows {
+            let (debit, credit) = totals.entry(row.currency).or_default();
+            *debit = debit.checked_add(row.debit).unwrap_or(Money::MAX);
+            *credit = credit.checked_add(row.credit).unwrap_or(Money::MAX);
+        }
+        totals
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.totals()
+            .values()
+            .all(|(debit, credit)| debit == credit)
+    }
+
+    // Net debit balance of `account` in `currency`.
+    pub fn balance(&self, account: GlAccount, currency: Currency) -> Money {
+        self.rows
+            .iter()
+            .find(|row| row.account == account && row.currency == currency)
+            .and_then(|row| row.debit.checked_sub(row.credit))
+            .unwrap_or(Money::ZERO)
+    }
+}
+
+impl fmt::Display for TrialBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>16} {:>16}",
+            "Account", "Currency", "Debit", "Credit"
+        )?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>16} {:>16}",
+                row.account.to_string(),
+                row.currency.to_string(),
+                row.debit.to_string(),
+                row.credit.to_string()
+            )?;
+        }
+        for (currency, (debit, credit)) in self.totals() {
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>16} {:>16}",
+                "Total",
+                currency.to_string(),
+                debit.to_string(),
+                credit.to_string()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Bank {
+    pub(crate) fn currency_of(&self, account: &str) -> Currency {
+        self.users
+            .get(account)
+            .map_or(self.currency, |user| user.currency)
+    }
+
+    // Books one ledger entry. The customer side always goes to the deposits
+    // account; what the other side is depends on the kind of entry.
+    fn journal_entry(&self, entry: &LedgerEntry) -> JournalEntry {
+        use GlAccount::*;
+
+        // A reversal takes back the postings of the entry it undoes.
+        if let Some(reversed) = entry.reverses.and_then(|id| self.ledger.entry(id)) {
+            return JournalEntry {
+                entry_id: entry.id,
+                timestamp: entry.timestamp,
+                kind: entry.kind,
+                postings: self
+                    .journal_entry(reversed)
+                    .postings
+                    .into_iter()
+                    .map(Posting::reversed)
+                    .collect(),
+            };
+        }
+
+        // The bank's side when money comes into a customer account from
+        // outside, and when it goes out.
+        let (source, sink) = match entry.kind {
+            EntryKind::Interest => (InterestExpense, InterestIncome),
+            EntryKind::Disbursement | EntryKind::Repayment => (LoansReceivable, LoansReceivable),
+            EntryKind::Fee => (FeeIncome, FeeIncome),
+            _ => (Cash, Cash),
+        };
+
+        let mut postings = Vec::new();
+        match (&entry.from, &entry.to) {
+            // A loan taken over in a merge: the account is unchanged and the
+            // bank takes on what is still owed.
+            (Some(from), Some(to)) if entry.kind == EntryKind::Merge && from == to => {
+                let currency = self.currency_of(from);
+                postings.push(Posting::debit(LoansReceivable, currency, entry.amount));
+                postings.push(Posting::credit(Cash, currency, entry.amount));
+            }
+            (Some(from), Some(to)) => {
+                let (from_currency, to_currency) = (self.currency_of(from), self.currency_of(to));
+                let credited = entry.credited_amount();
+                postings.push(Posting::debit(
+                    CustomerDeposits,
+                    from_currency,
+                    entry.amount,
+                ));
+                if from_currency != to_currency || credited != entry.amount {
+                    postings.push(Posting::credit(FxPosition, from_currency, entry.amount));
+                    postings.push(Posting::debit(FxPosition, to_currency, credited));
+                }
+                postings.push(Posting::credit(CustomerDeposits, to_currency, credited));
+            }
+            (None, Some(to)) => {
+                let currency = self.currency_of(to);
+                postings.push(Posting::debit(source, currency, entry.amount));
+                postings.push(Posting::credit(CustomerDeposits, currency, entry.amount));
+            }
+            (Some(from), None) => {
+                let currency = self.currency_of(from);
+                postings.push(Posting::debit(CustomerDeposits, currency, entry.amount));
+                postings.push(Posting::credit(sink, currency, entry.amount));
+            }
+            (None, None) => {}
+        }
+
+        JournalEntry {
+            entry_id: entry.id,
+            timestamp: entry.timestamp,
+            kind: entry.kind,
+            postings,
+        }
+    }
+
+    // The general ledger journal, one entry per ledger entry.
+    pub fn journal(&self) -> Vec<JournalEntry> {
+        self.ledger
+            .entries()
+            .iter()
+            .map(|entry| self.journal_entry(entry))
+            .collect()
+    }
+
+    pub fn trial_balance(&self) -> Result<TrialBalance, BankError> {
+        let mut balances: BTreeMap<(GlAccount, Currency), i128> = BTreeMap::new();
+        for entry in self.ledger.entries() {
+            for posting in self.journal_entry(entry).postings {
+                *balances
+                    .entry((posting.account, posting.currency))
+                    .or_default() += posting.signed();
+            }
+        }
+
+        let rows = balances
+            .into_iter()
+            .map(|((account, currency), balance)| {
+                let amount = i64::try_from(balance.abs())
+                    .map(Money::from_minor)
+                    .map_err(|_| BankError::BalanceOverflow)?;
+                let (debit, credit) = if balance >= 0 {
+                    (amount, Money::ZERO)
+                } else {
+                    (Money::ZERO, amount)
+                };
+                Ok(TrialBalanceRow {
+                    account,
+                    currency,
+                    debit,
+                    credit,
+                })
+            })
+            .collect::<Result<_, BankError>>()?;
+
+        Ok(TrialBalance { rows })
+    }
+
+    // Proves the books: debits equal credits in every currency, customer
+    // deposits agree with the account balances and loans receivable with
+    // what borrowers still owe.
+    pub fn verify_books(&self) -> Result<TrialBalance, BankError> {
+        let trial_balance = self.trial_balance()?;
+        let unbalanced = |reason: String| Err(BankError::BooksUnbalanced(reason));
+
+        for (currency, (debit, credit)) in trial_balance.totals() {
+            if debit != credit {
+                return unbalanced(format!(
+                    "{} debits of {} against credits of {}",
+                    currency, debit, credit
+                ));
+            }
+        }
+
+        let mut deposits: BTreeMap<Currency, Money> = BTreeMap::new();
+        for user in self.users.values() {
+            let total = deposits.entry(user.currency).or_default();
+            *total = total
+                .checked_add(user.balance)
+                .ok_or(BankError::BalanceOverflow)?;
+        }
+        let mut receivable: BTreeMap<Currency, Money> = BTreeMap::new();
+        for loan in &self.loans {
+            let total = receivable
+                .entry(self.currency_of(&loan.account))
+                .or_default();
+            *total = total
+                .checked_add(loan.outstanding)
+                .ok_or(BankError::BalanceOverflow)?;
+        }
+
+        for row in &trial_balance.rows {
+            let (expected, account) = match row.account {
+                GlAccount::CustomerDeposits => (
+                    deposits
+                        .get(&row.currency)
+                        .and_then(|total| total.checked_neg()),
+                    "customer deposits",
+                ),
+                GlAccount::LoansReceivable => {
+                    (receivable.get(&row.currency).copied(), "loans receivable")
+                }
+                _ => continue,
+            };
+            let booked = trial_balance.balance(row.account, row.currency);
+            if booked != expected.unwrap_or(Money::ZERO) {
+                return unbalanced(format!(
+                    "{} {} booked at {} but accounts say {}",
+                    row.currency,
+                    account,
+                    booked,
+                    expected.unwrap_or(Money::ZERO)
+                ));
+            }
+        }
+
+        Ok(trial_balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Amortization, Loan, Transfer, User};
+    use chrono::NaiveDate;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 1000, 500);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(10_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(5000),
+            Money::from_minor(-2000),
+        ))
+        .unwrap();
+        bank
+    }
+
+    #[test]
+    fn test_journal_postings() {
+        let mut bank = bank();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(500))
+            .unwrap();
+        bank.accrue_interest().unwrap();
+
+        let journal = bank.journal();
+        for entry in &journal {
+            let net: i128 = entry.postings.iter().map(Posting::signed).sum();
+            assert_eq!(net, 0, "entry #{} doesn't balance", entry.entry_id);
+        }
+
+        // Bob opened overdrawn, so the bank paid out cash for him.
+        assert_eq!(
+            journal[1].postings,
+            vec![
+                Posting::debit(
+                    GlAccount::CustomerDeposits,
+                    Currency::EUR,
+                    Money::from_minor(2000)
+                ),
+                Posting::credit(GlAccount::Cash, Currency::EUR, Money::from_minor(2000)),
+            ]
+        );
+
+        let trial_balance = bank.verify_books().unwrap();
+        assert!(trial_balance.is_balanced());
+        // Alice earns 5% on 95.00, Bob pays 10% on 15.00.
+        assert_eq!(
+            trial_balance.balance(GlAccount::InterestExpense, Currency::EUR),
+            Money::from_minor(475)
+        );
+        assert_eq!(
+            trial_balance.balance(GlAccount::InterestIncome, Currency::EUR),
+            Money::from_minor(-150)
+        );
+        let (liabilities, assets) = bank.calc_balance().unwrap();
+        assert_eq!(
+            trial_balance.balance(GlAccount::CustomerDeposits, Currency::EUR),
+            assets.checked_sub(liabilities).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_books_balance_after_every_operation() {
+        let mut bank = bank();
+        bank.exchange_rates
+            .set_rate(Currency::EUR, Currency::USD, 1_100_000);
+        bank.exchange_rates
+            .set_rate(Currency::USD, Currency::EUR, 909_091);
+        bank.add_user(
+            User::new("Omar".to_string(), Money::ZERO, Money::ZERO).with_currency(Currency::USD),
+        )
+        .unwrap();
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+
+        type Step = Box<dyn Fn(&mut Bank)>;
+        let steps: Vec<Step> = vec![
+            Box::new(|bank| {
+                bank.transfer_funds("Alice", "Omar", Money::from_minor(1000))
+                    .unwrap()
+            }),
+            Box::new(|bank| {
+                bank.transfer_batch(&[
+                    Transfer::new("Bob", "Alice", Money::from_minor(300)),
+                    Transfer::new("Alice", "Bob", Money::from_minor(50)),
+                ])
+                .unwrap()
+            }),
+            Box::new(move |bank| {
+                bank.grant_loan(Loan::new(
+                    "Bob",
+                    Money::from_minor(12_000),
+                    600,
+                    6,
+                    Amortization::Annuity,
+                    start,
+                ))
+                .unwrap();
+            }),
+            Box::new(move |bank| {
+                bank.collect_installments(start + chrono::Days::new(70));
+            }),
+            Box::new(move |bank| {
+                bank.repay_loan(1, Money::from_minor(1000), start + chrono::Days::new(75))
+                    .unwrap();
+            }),
+            Box::new(|bank| bank.accrue_interest().unwrap()),
+            Box::new(move |bank| {
+                bank.accrue_interest_between(start, start + chrono::Days::new(31))
+                    .unwrap()
+            }),
+            Box::new(|bank| {
+                let mut other = Bank::new("Other".to_string(), 0, 0);
+                other
+                    .add_user(User::new(
+                        "Dave".to_string(),
+                        Money::ZERO,
+                        Money::from_minor(700),
+                    ))
+                    .unwrap();
+                other
+                    .grant_loan(Loan::new(
+                        "Dave",
+                        Money::from_minor(5000),
+                        0,
+                        5,
+                        Amortization::Linear,
+                        NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+                    ))
+                    .unwrap();
+                bank.merge_bank(other).unwrap();
+            }),
+            Box::new(|bank| {
+                bank.close_account("Alice", Some("Dave")).unwrap();
+            }),
+        ];
+
+        for (index, step) in steps.iter().enumerate() {
+            step(&mut bank);
+            let trial_balance = bank
+                .verify_books()
+                .unwrap_or_else(|error| panic!("step {}: {}", index, error));
+            assert!(trial_balance.is_balanced());
+        }
+
+        let trial_balance = bank.trial_balance().unwrap();
+        assert_ne!(
+            trial_balance.balance(GlAccount::FxPosition, Currency::USD),
+            Money::ZERO
+        );
+        assert_eq!(
+            trial_balance.balance(GlAccount::LoansReceivable, Currency::EUR),
+            bank.loans()
+                .iter()
+                .map(Loan::outstanding)
+                .try_fold(Money::ZERO, Money::checked_add)
+                .unwrap()
+        );
+
+        // Tampering with a balance outside the ledger is caught.
+        bank.user_mut("Bob").unwrap().balance = Money::ZERO;
+        assert!(matches!(
+            bank.verify_books(),
+            Err(BankError::BooksUnbalanced(_))
+        ));
+    }
+}
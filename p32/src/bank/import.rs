@@ -0,0 +1,617 @@
+use super::{AccountId, Bank, Currency, Money, User};
+use serde::Serialize;
+use std::fmt;
+
+// What an import did. Rows that fail are skipped and reported; the rest are
+// applied, each through the same `add_user` and `transfer_funds` as any
+// other caller, so rules, limits and events all apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub users_added: Vec<AccountId>,
+    // Ledger entry ids of the imported transfers.
+    pub transfers: Vec<u64>,
+    pub failures: Vec<ImportFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportFailure {
+    // 1-based line of the input the failure refers to.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl ImportReport {
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn fail(&mut self, line: usize, reason: impl ToString) {
+        self.failures.push(ImportFailure {
+            line,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Imported {} users and {} transfers, {} failed",
+            self.users_added.len(),
+            self.transfers.len(),
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  line {}: {}", failure.line, failure.reason)?;
+        }
+        Ok(())
+    }
+}
+
+// Splits one CSV line into fields, honouring double quotes and `""` escapes.
+// Quoted fields can't span lines.
+fn csv_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+// The data rows of a CSV file with a header, as column-name lookups. Fails
+// if a required column is missing from the header.
+struct CsvRows<'a> {
+    columns: Vec<String>,
+    lines: Vec<(usize, &'a str)>,
+}
+
+struct CsvRow<'a> {
+    columns: &'a [String],
+    fields: Vec<String>,
+}
+
+impl<'a> CsvRows<'a> {
+    fn parse(input: &'a str, required: &[&str]) -> Result<Self, String> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let (_, header) = lines.next().ok_or("Missing header row")?;
+        let columns: Vec<String> = csv_fields(header)?
+            .iter()
+            .map(|column| column.trim().to_ascii_lowercase())
+            .collect();
+        for column in required {
+            if !columns.iter().any(|existing| existing == column) {
+                return Err(format!("Missing column '{}'", column));
+            }
+        }
+
+        Ok(CsvRows {
+            columns,
+            lines: lines.collect(),
+        })
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (usize, Result<CsvRow<'_>, String>)> {
+        self.lines.iter().map(|(number, line)| {
+            let row = csv_fields(line).and_then(|fields| {
+                if fields.len() != self.columns.len() {
+                    return Err(format!(
+                        "Expected {} fields, found {}",
+                        self.columns.len(),
+                        fields.len()
+                    ));
+                }
+                Ok(CsvRow {
+                    columns: &self.columns,
+                    fields,
+                })
+            });
+            (*number, row)
+        })
+    }
+}
+
+impl CsvRow<'_> {
+    // The trimmed field in `column`, `None` if the column is absent or the
+    // field empty.
+    fn get(&self, column: &str) -> Option<&str> {
+        self.columns
+            .iter()
+            .position(|existing| existing == column)
+            .map(|index| self.fields[index].trim())
+            .filter(|field| !field.is_empty())
+    }
+
+    fn required(&self, column: &str) -> Result<&str, String> {
+        self.get(column)
+            .ok_or_else(|| format!("Missing value for '{}'", column))
+    }
+
+    fn parsed<T: std::str::FromStr<Err = String>>(
+        &self,
+        column: &str,
+        default: T,
+    ) -> Result<T, String> {
+        self.get(column).map_or(Ok(default), str::parse)
+    }
+}
+
+// One `:tag:value` field of an MT940 message, with continuation lines
+// joined on.
+struct Mt940Field {
+    line: usize,
+    tag: String,
+    value: String,
+}
+
+// A field parsed from the line it starts on.
+type Parsed<T> = (usize, Result<T, String>);
+
+// The transactions of one statement, from its `:20:` to the next.
+struct Mt940Statement {
+    line: usize,
+    account: Option<(usize, String)>,
+    opening: Option<Parsed<(Currency, Money)>>,
+    closing: Option<Parsed<(Currency, Money)>>,
+    // Signed amount (negative for debits) and counterparty.
+    entries: Vec<Parsed<(Money, String)>>,
+}
+
+fn mt940_fields(input: &str) -> Vec<Mt940Field> {
+    let mut fields: Vec<Mt940Field> = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let tagged = line
+            .strip_prefix(':')
+            .and_then(|rest| rest.split_once(':'))
+            .filter(|(tag, _)| tag.len() <= 3 && tag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        match (tagged, fields.last_mut()) {
+            (Some((tag, value)), _) => fields.push(Mt940Field {
+                line: index + 1,
+                tag: tag.to_string(),
+                value: value.to_string(),
+            }),
+            // `-` ends a message; anything else continues the last field.
+            (None, _) if line.trim() == "-" => {}
+            (None, Some(field)) => field.value.push_str(line),
+            (None, None) => {}
+        }
+    }
+    fields
+}
+
+// An MT940 amount uses a comma as its decimal separator: "1250,5".
+fn mt940_amount(value: &str) -> Result<Money, String> {
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit() || c == ',') {
+        return Err(format!("Invalid amount '{}'", value));
+    }
+    value.replacen(',', ".", 1).parse()
+}
+
+// A balance field (`:60F:`, `:62F:`): mark, YYMMDD date, currency, amount.
+fn mt940_balance(value: &str) -> Result<(Currency, Money), String> {
+    let invalid = || format!("Invalid balance '{}'", value);
+    let sign = value.get(..1).ok_or_else(invalid)?;
+    let currency: Currency = value.get(7..10).ok_or_else(invalid)?.parse()?;
+    let amount = mt940_amount(value.get(10..).ok_or_else(invalid)?)?;
+    match sign {
+        "C" => Ok((currency, amount)),
+        "D" => Ok((currency, amount.checked_neg().ok_or_else(invalid)?)),
+        _ => Err(invalid()),
+    }
+}
+
+// A statement line (`:61:`): YYMMDD value date, optional MMDD entry date,
+// debit/credit mark (with R for reversals), optional funds code, amount and
+// transaction type. Returns the amount signed from the account's side.
+fn mt940_entry(value: &str) -> Result<Money, String> {
+    let invalid = || format!("Invalid statement line '{}'", value);
+    let mut rest = value.get(6..).ok_or_else(invalid)?;
+    if let Some(entry_date) = rest.get(..4)
+        && entry_date.chars().all(|c| c.is_ascii_digit())
+    {
+        rest = &rest[entry_date.len()..];
+    }
+
+    let (debit, rest) = ["RC", "RD", "C", "D"]
+        .iter()
+        .find_map(|mark| {
+            rest.strip_prefix(mark)
+                .map(|rest| (matches!(*mark, "D" | "RC"), rest))
+        })
+        .ok_or_else(invalid)?;
+    let rest = rest
+        .strip_prefix(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(rest);
+
+    let amount = rest
+        .split(|c: char| !c.is_ascii_digit() && c != ',')
+        .next()
+        .unwrap_or_default();
+    let amount = mt940_amount(amount)?;
+    if debit {
+        amount.checked_neg().ok_or_else(invalid)
+    } else {
+        Ok(amount)
+    }
+}
+
+// The counterparty of a transaction from its `:86:` narrative: the `/NAME/`
+// subfield if there is one, otherwise the whole narrative.
+fn mt940_counterparty(narrative: &str) -> Option<String> {
+    let counterparty = match narrative.split_once("/NAME/") {
+        Some((_, name)) => name.split('/').next().unwrap_or_default(),
+        None => narrative,
+    };
+    Some(counterparty.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+fn mt940_statements(input: &str) -> Vec<Mt940Statement> {
+    let mut statements: Vec<Mt940Statement> = Vec::new();
+    let fields = mt940_fields(input);
+
+    for (index, field) in fields.iter().enumerate() {
+        if field.tag == "20" {
+            statements.push(Mt940Statement {
+                line: field.line,
+                account: None,
+                opening: None,
+                closing: None,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let Some(statement) = statements.last_mut() else {
+            continue;
+        };
+
+        match field.tag.as_str() {
+            "25" => statement.account = Some((field.line, field.value.trim().to_string())),
+            "60F" | "60M" => statement.opening = Some((field.line, mt940_balance(&field.value))),
+            "62F" | "62M" => statement.closing = Some((field.line, mt940_balance(&field.value))),
+            "61" => {
+                let counterparty = fields
+                    .get(index + 1)
+                    .filter(|next| next.tag == "86")
+                    .and_then(|next| mt940_counterparty(&next.value))
+                    .ok_or_else(|| "Missing counterparty in :86:".to_string());
+                let entry =
+                    mt940_entry(&field.value).and_then(|amount| Ok((amount, counterparty?)));
+                statement.entries.push((field.line, entry));
+            }
+            _ => {}
+        }
+    }
+
+    statements
+}
+
+impl Bank {
+    // Opens an account for every row of a CSV with a `name` column and
+    // optional `credit_line`, `balance` and `currency` columns.
+    pub fn import_users_csv(&mut self, input: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        let rows = match CsvRows::parse(input, &["name"]) {
+            Ok(rows) => rows,
+            Err(reason) => {
+                report.fail(1, reason);
+                return report;
+            }
+        };
+
+        for (line, row) in rows.rows() {
+            let added = row
+                .and_then(|row| {
+                    let user = User::new(
+                        row.required("name")?.to_string(),
+                        row.parsed("credit_line", Money::ZERO)?,
+                        row.parsed("balance", Money::ZERO)?,
+                    );
+                    Ok(user.with_currency(row.parsed("currency", Currency::default())?))
+                })
+                .and_then(|user| self.add_user(user).map_err(|error| error.to_string()));
+            match added {
+                Ok(id) => report.users_added.push(id),
+                Err(reason) => report.fail(line, reason),
+            }
+        }
+
+        report
+    }
+
+    // Makes a transfer for every row of a CSV with `from`, `to` and `amount`
+    // columns. Accounts are given by id or unambiguous name.
+    pub fn import_transfers_csv(&mut self, input: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        let rows = match CsvRows::parse(input, &["from", "to", "amount"]) {
+            Ok(rows) => rows,
+            Err(reason) => {
+                report.fail(1, reason);
+                return report;
+            }
+        };
+
+        for (line, row) in rows.rows() {
+            let transferred = row.and_then(|row| {
+                let amount: Money = row.required("amount")?.parse()?;
+                self.transfer_funds(row.required("from")?, row.required("to")?, amount)
+                    .map_err(|error| error.to_string())
+            });
+            match transferred {
+                Ok(()) => report.transfers.push(self.ledger.len() as u64),
+                Err(reason) => report.fail(line, reason),
+            }
+        }
+
+        report
+    }
+
+    // Imports MT940 customer statements. The `:25:` account is looked up by
+    // id or name and opened with the `:60F:` opening balance if the bank
+    // doesn't have it yet; if it does, the opening balance has to match or
+    // the statement is skipped. Each `:61:` line becomes a transfer to or
+    // from the counterparty named in the `:86:` after it, and the `:62F:`
+    // closing balance is checked once all of them are in.
+    pub fn import_mt940(&mut self, input: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        let statements = mt940_statements(input);
+        if statements.is_empty() {
+            report.fail(1, "No statement found (expected a :20: field)");
+        }
+
+        for statement in statements {
+            let Some((line, account)) = statement.account else {
+                report.fail(statement.line, "Missing account in :25:");
+                continue;
+            };
+            let opening = match statement.opening {
+                Some((_, Ok(opening))) => opening,
+                Some((line, Err(reason))) => {
+                    report.fail(line, reason);
+                    continue;
+                }
+                None => {
+                    report.fail(statement.line, "Missing opening balance in :60F:");
+                    continue;
+                }
+            };
+
+            let id = match self.account_id(&account) {
+                Ok(id) => {
+                    let user = &self.users[id.as_str()];
+                    if (user.currency, user.balance) != opening {
+                        report.fail(
+                            line,
+                            format!(
+                                "Opening balance {} {} doesn't match the balance of {}, {}; \
+                                 statement skipped",
+                                opening.0, opening.1, account, user.balance
+                            ),
+                        );
+                        continue;
+                    }
+                    id
+                }
+                Err(_) => {
+                    let user =
+                        User::new(account.clone(), Money::ZERO, opening.1).with_currency(opening.0);
+                    match self.add_user(user) {
+                        Ok(id) => {
+                            report.users_added.push(id.clone());
+                            id
+                        }
+                        Err(error) => {
+                            report.fail(line, error);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            for (line, entry) in statement.entries {
+                let transferred = entry.and_then(|(amount, counterparty)| {
+                    let (from, to) = if amount.is_negative() {
+                        (id.as_str(), counterparty.as_str())
+                    } else {
+                        (counterparty.as_str(), id.as_str())
+                    };
+                    self.transfer_funds(from, to, amount.abs())
+                        .map_err(|error| error.to_string())
+                });
+                match transferred {
+                    Ok(()) => report.transfers.push(self.ledger.len() as u64),
+                    Err(reason) => report.fail(line, reason),
+                }
+            }
+
+            match statement.closing {
+                Some((line, Ok((_, closing)))) => {
+                    let balance = self.users[id.as_str()].balance;
+                    if balance != closing {
+                        report.fail(
+                            line,
+                            format!(
+                                "Closing balance {} doesn't match {} after the import",
+                                closing, balance
+                            ),
+                        );
+                    }
+                }
+                Some((line, Err(reason))) => report.fail(line, reason),
+                None => {}
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(10_000),
+            Money::ZERO,
+        ))
+        .unwrap();
+        bank
+    }
+
+    #[test]
+    fn test_csv_fields() {
+        assert_eq!(
+            csv_fields(r#"a,"b, c","say ""hi""",,"#).unwrap(),
+            vec!["a", "b, c", "say \"hi\"", "", ""]
+        );
+        assert!(csv_fields(r#"a,"b"#).is_err());
+    }
+
+    #[test]
+    fn test_import_users_and_transfers_csv() {
+        let mut bank = bank();
+        let report = bank.import_users_csv(
+            "Name,Balance,Credit_Line,Currency\n\
+             \"Smith, Alice\",\"1,250.00\",100,EUR\n\
+             Carol,10,,usd\n\
+             \n\
+             Bob,0,0,EUR\n\
+             Dave,ten,0,EUR\n\
+             Erin,5\n",
+        );
+        assert_eq!(report.users_added.len(), 2);
+        assert_eq!(
+            report.failures,
+            vec![
+                ImportFailure {
+                    line: 5,
+                    reason: "User 'Bob' already exists".to_string()
+                },
+                ImportFailure {
+                    line: 6,
+                    reason: "Invalid amount 'ten'".to_string()
+                },
+                ImportFailure {
+                    line: 7,
+                    reason: "Expected 4 fields, found 2".to_string()
+                },
+            ]
+        );
+        let alice = bank.user("Smith, Alice").unwrap();
+        assert_eq!(alice.balance, Money::from_minor(125_000));
+        assert_eq!(alice.credit_line, Money::from_minor(10_000));
+        assert_eq!(bank.user("Carol").unwrap().currency, Currency::USD);
+
+        let report = bank.import_transfers_csv(
+            "from,to,amount\n\
+             \"Smith, Alice\",Bob,250\n\
+             Bob,Nobody,1\n\
+             Bob,\"Smith, Alice\",-1\n\
+             Bob,\"Smith, Alice\",50.5\n",
+        );
+        assert_eq!(report.transfers, vec![4, 5]);
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].line, 3);
+        assert_eq!(
+            bank.user("Bob").unwrap().balance,
+            Money::from_minor(25_000 - 5_050)
+        );
+        assert_eq!(
+            report.to_string().lines().next().unwrap(),
+            "Imported 0 users and 2 transfers, 2 failed"
+        );
+
+        let report = bank.import_transfers_csv("from,amount\nBob,1\n");
+        assert_eq!(report.failures[0].reason, "Missing column 'to'");
+        assert!(report.transfers.is_empty());
+    }
+
+    const STATEMENT: &str = "\
+:20:STMT-1
+:25:Alice
+:28C:1/1
+:60F:C260101EUR1000,00
+:61:2601020102D250,00NTRFNONREF
+:86:/NAME/Bob/REMI/Rent January
+:61:260103C99,5NTRFNONREF//B123
+:86:Bob
+:61:260104D10,00NTRFNONREF
+:86:/NAME/Nobody/
+:61:260105D5,00NTRF
+:62F:C260131EUR834,50
+-
+:20:STMT-2
+:25:Alice
+:60F:C260201EUR0,00
+:61:260202C1,00NTRF
+:86:Bob
+-";
+
+    #[test]
+    fn test_import_mt940() {
+        let mut bank = bank();
+        let report = bank.import_mt940(STATEMENT);
+
+        assert_eq!(report.users_added.len(), 1);
+        assert_eq!(report.transfers.len(), 2);
+        let lines: Vec<usize> = report.failures.iter().map(|failure| failure.line).collect();
+        // The unknown counterparty, the missing :86:, the closing balance
+        // that no longer adds up and the second statement whose opening
+        // balance doesn't match.
+        assert_eq!(lines, vec![9, 11, 12, 15]);
+        assert!(report.failures[1].reason.contains(":86:"));
+        assert!(report.failures[3].reason.contains("statement skipped"));
+
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(alice.balance, Money::from_minor(84_950));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(15_050));
+
+        assert_eq!(
+            mt940_entry("2601020102RD1,NTRF"),
+            Ok(Money::from_minor(100))
+        );
+        assert_eq!(mt940_entry("260102CE3,1NTRF"), Ok(Money::from_minor(310)));
+        assert!(mt940_entry("260102X3,1NTRF").is_err());
+        // Bad rows are reported, not panicked on, whatever the characters.
+        assert!(mt940_entry("260102€é1,00").is_err());
+        assert!(mt940_entry("26010€").is_err());
+        assert!(mt940_entry("260102Cé1,00").is_err());
+        let report = bank.import_mt940(
+            ":20:STMT-3\n:25:Alice\n:60F:C260301EUR849,50\n:61:260302€é1,00\n:86:Bob\n-",
+        );
+        assert!(report.transfers.is_empty());
+        assert!(
+            report
+                .failures
+                .iter()
+                .any(|failure| failure.reason.contains("€é"))
+        );
+        assert!(bank.import_mt940("not a statement").failures[0].line == 1);
+    }
+}
@@ -0,0 +1,534 @@
+use super::{AccountChange, Bank, BankError, BankEvent, EntryKind, Money, Rounding, User};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Interest is computed exactly as a fraction of a minor unit over this common
+// denominator: 10_000 for basis points times lcm(360, 365) days, so both day
+// count conventions divide it without loss. Whatever can't be posted after
+// rounding is carried to the next period as a numerator over it.
+pub const CARRY_DENOMINATOR: i128 = 10_000 * 26_280;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compounding {
+    Daily,
+    Monthly,
+    Annually,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DayCount {
+    #[serde(rename = "ACT/360")]
+    Act360,
+    #[serde(rename = "ACT/365")]
+    Act365,
+}
+
+// A balance band: `rate` (in bp) applies to the part of the absolute balance
+// above `threshold` and below the next tier's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tier {
+    pub threshold: Money,
+    pub rate: u64,
+}
+
+// Tiers left empty fall back to the bank's flat `debit_interest` (deposits)
+// and `credit_interest` (overdrafts).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterestConfig {
+    pub compounding: Compounding,
+    pub day_count: DayCount,
+    pub rounding: Rounding,
+    #[serde(default)]
+    pub deposit_tiers: Vec<Tier>,
+    #[serde(default)]
+    pub overdraft_tiers: Vec<Tier>,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        InterestConfig {
+            compounding: Compounding::Monthly,
+            day_count: DayCount::Act365,
+            rounding: Rounding::Truncate,
+            deposit_tiers: Vec::new(),
+            overdraft_tiers: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for DayCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DayCount::Act360 => write!(f, "ACT/360"),
+            DayCount::Act365 => write!(f, "ACT/365"),
+        }
+    }
+}
+
+impl DayCount {
+    // Scales a day count so that it can be put over `CARRY_DENOMINATOR`.
+    fn day_factor(self) -> i128 {
+        match self {
+            DayCount::Act360 => 26_280 / 360,
+            DayCount::Act365 => 26_280 / 365,
+        }
+    }
+}
+
+impl Compounding {
+    fn period_end(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Compounding::Daily => start.succ_opt().unwrap_or(NaiveDate::MAX),
+            Compounding::Monthly => start
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(1)))
+                .unwrap_or(NaiveDate::MAX),
+            Compounding::Annually => {
+                NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap_or(NaiveDate::MAX)
+            }
+        }
+    }
+}
+
+// Splits [start, end) into compounding periods.
+pub fn compounding_periods(
+    start: NaiveDate,
+    end: NaiveDate,
+    compounding: Compounding,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut periods = Vec::new();
+    let mut period_start = start;
+
+    while period_start < end {
+        let period_end = compounding.period_end(period_start).min(end);
+        periods.push((period_start, period_end));
+        period_start = period_end;
+    }
+
+    periods
+}
+
+// Sum of `portion * rate` over the tiers the balance reaches, or `None` if
+// it doesn't fit in an i128.
+fn tiered_rate_amount(balance: Money, tiers: &[Tier], flat_rate: u64) -> Option<i128> {
+    if tiers.is_empty() {
+        return i128::from(balance.minor()).checked_mul(i128::from(flat_rate));
+    }
+
+    let mut tiers = tiers.to_vec();
+    tiers.sort_by_key(|tier| tier.threshold);
+
+    let mut total: i128 = 0;
+    for (index, tier) in tiers.iter().enumerate() {
+        if balance <= tier.threshold {
+            break;
+        }
+        let upper = tiers
+            .get(index + 1)
+            .map_or(balance, |next| next.threshold.min(balance));
+        let portion = i128::from(upper.minor()) - i128::from(tier.threshold.minor());
+        total = total.checked_add(portion.checked_mul(i128::from(tier.rate))?)?;
+    }
+
+    Some(total)
+}
+
+impl InterestConfig {
+    // Exact signed interest on `balance` for `days` days, as a numerator over
+    // `CARRY_DENOMINATOR`, or `None` if that doesn't fit in an i128.
+    pub fn interest_numerator(
+        &self,
+        balance: Money,
+        days: i64,
+        deposit_rate: u64,
+        overdraft_rate: u64,
+    ) -> Option<i128> {
+        let rate_amount = if balance.is_negative() {
+            -tiered_rate_amount(balance.abs(), &self.overdraft_tiers, overdraft_rate)?
+        } else {
+            tiered_rate_amount(balance, &self.deposit_tiers, deposit_rate)?
+        };
+
+        rate_amount
+            .checked_mul(i128::from(days))?
+            .checked_mul(self.day_count.day_factor())
+    }
+}
+
+impl Bank {
+    // Rates in bp for `accrue_interest`: `credit_interest` is charged on
+    // overdrafts and `debit_interest` paid on deposits.
+    pub fn set_interest_rates(&mut self, credit_interest: u64, debit_interest: u64) {
+        let snapshot = self.operation_snapshot();
+        self.credit_interest = credit_interest;
+        self.debit_interest = debit_interest;
+        self.checkpoint(snapshot);
+    }
+
+    pub fn interest_carry(&self, reference: &str) -> i64 {
+        self.account_id(reference)
+            .ok()
+            .and_then(|id| self.interest_carry.get(id.as_str()).copied())
+            .unwrap_or(0)
+    }
+
+    // Accrues interest from `start` (inclusive) to `end` (exclusive) using
+    // `interest_config`, posting one ledger entry per user and compounding
+    // period dated at the end of that period. Frozen and closed accounts are
+    // skipped.
+    pub fn accrue_interest_between(
+        &mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<(), BankError> {
+        if end < start {
+            return Err(BankError::InvalidDateRange { start, end });
+        }
+        let snapshot = self.snapshot();
+
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.is_active())
+            .collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+
+        // Every posting is worked out on working balances and carries before
+        // any account is touched, so an overflow leaves the bank as it was.
+        let mut balances: Vec<(&User, Money, i64)> = users
+            .into_iter()
+            .map(|user| {
+                let carry = self.interest_carry.get(user.id.as_str()).copied();
+                (user, user.balance, carry.unwrap_or(0))
+            })
+            .collect();
+        let mut postings = Vec::new();
+        for (period_start, period_end) in
+            compounding_periods(start, end, self.interest_config.compounding)
+        {
+            let days = (period_end - period_start).num_days();
+            let timestamp = period_end
+                .and_hms_opt(0, 0, 0)
+                .expect("Midnight is a valid time")
+                .and_utc();
+
+            for (user, balance, carry) in &mut balances {
+                let total = self
+                    .interest_config
+                    .interest_numerator(*balance, days, self.debit_interest, self.credit_interest)
+                    .and_then(|numerator| numerator.checked_add(i128::from(*carry)))
+                    .ok_or_else(|| BankError::InterestOverflow(user.name.clone()))?;
+                let posted = self
+                    .interest_config
+                    .rounding
+                    .divide(total, CARRY_DENOMINATOR);
+                let remainder = total - posted * CARRY_DENOMINATOR;
+
+                let interest = i64::try_from(posted)
+                    .map(Money::from_minor)
+                    .map_err(|_| BankError::InterestOverflow(user.name.clone()))?;
+                *balance = balance
+                    .checked_add(interest)
+                    .ok_or_else(|| BankError::InterestOverflow(user.name.clone()))?;
+                *carry = i64::try_from(remainder).expect("Carry is below one minor unit");
+
+                if !interest.is_zero() {
+                    postings.push((timestamp, user.id.to_string(), interest, *balance));
+                }
+            }
+        }
+        let carries: Vec<(String, i64)> = balances
+            .into_iter()
+            .map(|(user, _, carry)| (user.id.to_string(), carry))
+            .collect();
+
+        for (timestamp, id, interest, balance) in postings {
+            let user = self.users.get_mut(&id).expect("Account exists");
+            let before = user.clone();
+            user.balance = balance;
+
+            let entry_id = self.ledger.record_adjustment_at(
+                timestamp,
+                EntryKind::Interest,
+                &id,
+                interest,
+                balance,
+            );
+            self.events.publish(BankEvent::InterestAccrued {
+                entry_id,
+                account: AccountChange {
+                    before,
+                    after: user.clone(),
+                },
+                interest,
+            });
+        }
+        self.interest_carry.extend(carries);
+        self.interest_carry.retain(|_, carry| *carry != 0);
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::User;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_compounding_periods() {
+        let periods =
+            compounding_periods(date(2025, 1, 15), date(2025, 3, 10), Compounding::Monthly);
+        assert_eq!(
+            periods,
+            vec![
+                (date(2025, 1, 15), date(2025, 2, 1)),
+                (date(2025, 2, 1), date(2025, 3, 1)),
+                (date(2025, 3, 1), date(2025, 3, 10)),
+            ]
+        );
+
+        assert_eq!(
+            compounding_periods(date(2024, 12, 30), date(2025, 1, 2), Compounding::Daily).len(),
+            3
+        );
+        assert_eq!(
+            compounding_periods(date(2024, 6, 1), date(2026, 6, 1), Compounding::Annually).len(),
+            3
+        );
+        assert!(
+            compounding_periods(date(2025, 1, 1), date(2025, 1, 1), Compounding::Daily).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_tiered_rates() {
+        let tiers = [
+            Tier {
+                threshold: Money::from_minor(0),
+                rate: 100,
+            },
+            Tier {
+                threshold: Money::from_minor(1000),
+                rate: 200,
+            },
+            Tier {
+                threshold: Money::from_minor(5000),
+                rate: 300,
+            },
+        ];
+
+        assert_eq!(
+            tiered_rate_amount(Money::from_minor(500), &tiers, 0),
+            Some(500 * 100)
+        );
+        assert_eq!(
+            tiered_rate_amount(Money::from_minor(3000), &tiers, 0),
+            Some(1000 * 100 + 2000 * 200)
+        );
+        assert_eq!(
+            tiered_rate_amount(Money::from_minor(6000), &tiers, 0),
+            Some(1000 * 100 + 4000 * 200 + 1000 * 300)
+        );
+        assert_eq!(
+            tiered_rate_amount(Money::from_minor(6000), &[], 50),
+            Some(6000 * 50)
+        );
+    }
+
+    #[test]
+    fn test_annual_accrual_matches_flat_rate() {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+        bank.interest_config.compounding = Compounding::Annually;
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(10_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::from_minor(10_000),
+            Money::from_minor(-10_000),
+        ))
+        .unwrap();
+
+        bank.accrue_interest_between(date(2025, 1, 1), date(2026, 1, 1))
+            .unwrap();
+
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(10_300)
+        );
+        assert_eq!(
+            bank.user("Bob").unwrap().balance,
+            Money::from_minor(-10_500)
+        );
+
+        let entry = bank.ledger().entries().last().unwrap();
+        assert_eq!(entry.kind, EntryKind::Interest);
+        assert_eq!(entry.timestamp.date_naive(), date(2026, 1, 1));
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_day_count_conventions() {
+        let mut act360 = Bank::new("Test Bank".to_string(), 0, 3600);
+        act360.interest_config.compounding = Compounding::Annually;
+        act360.interest_config.day_count = DayCount::Act360;
+        act360
+            .add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(100_000),
+            ))
+            .unwrap();
+
+        // 365 days at 36% over a 360 day year: 100_000 * 0.36 * 365 / 360
+        act360
+            .accrue_interest_between(date(2025, 1, 1), date(2026, 1, 1))
+            .unwrap();
+        assert_eq!(
+            act360.user("Alice").unwrap().balance,
+            Money::from_minor(136_500)
+        );
+    }
+
+    #[test]
+    fn test_remainders_carry_forward() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 365);
+        bank.interest_config.compounding = Compounding::Daily;
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(100),
+        ))
+        .unwrap();
+
+        // 100 * 3.65% / 365 = 0.01 per day: nothing is posted until the
+        // carried fractions add up to a full unit.
+        bank.accrue_interest_between(date(2025, 1, 1), date(2025, 4, 11))
+            .unwrap();
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(101));
+        assert_eq!(bank.interest_carry("Alice"), 0);
+        assert_eq!(bank.ledger().len(), 2);
+
+        bank.accrue_interest_between(date(2025, 4, 11), date(2025, 4, 12))
+            .unwrap();
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(101));
+        assert_eq!(i128::from(bank.interest_carry("Alice")), 101 * 365 * 72);
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        // 1500 at 10bp for a full year is exactly 1.5
+        for (rounding, balance, carry) in [
+            (Rounding::Truncate, 1501, CARRY_DENOMINATOR / 2),
+            (Rounding::HalfUp, 1502, -CARRY_DENOMINATOR / 2),
+            (Rounding::HalfEven, 1502, -CARRY_DENOMINATOR / 2),
+        ] {
+            let mut bank = Bank::new("Test Bank".to_string(), 0, 10);
+            bank.interest_config.compounding = Compounding::Annually;
+            bank.interest_config.rounding = rounding;
+            bank.add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(1500),
+            ))
+            .unwrap();
+
+            bank.accrue_interest_between(date(2025, 1, 1), date(2026, 1, 1))
+                .unwrap();
+            assert_eq!(
+                bank.user("Alice").unwrap().balance,
+                Money::from_minor(balance)
+            );
+            assert_eq!(i128::from(bank.interest_carry("Alice")), carry);
+        }
+    }
+
+    #[test]
+    fn test_overflow_changes_nothing() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 10_000);
+        bank.interest_config.compounding = Compounding::Daily;
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(1_000_000),
+        ))
+        .unwrap();
+        bank.accrue_interest_between(date(2025, 1, 1), date(2025, 1, 3))
+            .unwrap();
+        bank.add_user(User::new(
+            "Bob".to_string(),
+            Money::ZERO,
+            Money::from_minor(i64::MAX / 2),
+        ))
+        .unwrap();
+
+        let alice = bank.user("Alice").unwrap().balance;
+        let carry = bank.interest_carry("Alice");
+        assert_ne!(carry, 0);
+        let entries = bank.ledger().len();
+        let depth = bank.undo_depth();
+
+        // Alice comes first and accrues fine; Bob overflows later in the year.
+        assert_eq!(
+            bank.accrue_interest_between(date(2026, 1, 1), date(2027, 1, 1)),
+            Err(BankError::InterestOverflow("Bob".to_string()))
+        );
+        assert_eq!(bank.user("Alice").unwrap().balance, alice);
+        assert_eq!(
+            bank.user("Bob").unwrap().balance,
+            Money::from_minor(i64::MAX / 2)
+        );
+        assert_eq!(bank.interest_carry("Alice"), carry);
+        assert_eq!(bank.interest_carry("Bob"), 0);
+        assert_eq!(bank.ledger().len(), entries);
+        assert_eq!(bank.undo_depth(), depth);
+
+        // Undoing takes back adding Bob, not the earlier accrual.
+        bank.undo(1).unwrap();
+        assert!(bank.user("Bob").is_err());
+        assert_eq!(bank.user("Alice").unwrap().balance, alice);
+    }
+
+    #[test]
+    fn test_huge_rates_overflow() {
+        let mut bank = Bank::new("Test Bank".to_string(), u64::MAX, u64::MAX);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(i64::MAX),
+        ))
+        .unwrap();
+        assert_eq!(
+            bank.accrue_interest_between(date(2026, 1, 1), date(2026, 2, 1)),
+            Err(BankError::InterestOverflow("Alice".to_string()))
+        );
+        // A tier from the very bottom spans more than an i64.
+        let tiers = [Tier {
+            threshold: Money::MIN,
+            rate: u64::MAX,
+        }];
+        assert_eq!(tiered_rate_amount(Money::MAX, &tiers, 0), None);
+    }
+
+    #[test]
+    fn test_invalid_range() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        assert_eq!(
+            bank.accrue_interest_between(date(2025, 2, 1), date(2025, 1, 1)),
+            Err(BankError::InvalidDateRange {
+                start: date(2025, 2, 1),
+                end: date(2025, 1, 1)
+            })
+        );
+    }
+}
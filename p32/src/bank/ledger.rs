@@ -0,0 +1,437 @@
+use super::{BankError, Money, StagedTransfer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Opening,
+    Transfer,
+    Interest,
+    Merge,
+    // Pays out the remaining balance of an account being closed, or settles
+    // what another bank owes on its vostro account.
+    Settlement,
+    // Pays a loan's principal into the borrower's account.
+    Disbursement,
+    // An installment or early repayment debited for a loan.
+    Repayment,
+    // An overdraft fee charged with a transfer.
+    Fee,
+    // Undoes an earlier entry, named by `reverses`, by moving the same money
+    // back.
+    Reversal,
+}
+
+// An entry moves `amount` out of `from` and into `to`; `None` on either side
+// means the money came from (or went to) outside the bank. Cross-currency
+// transfers credit `to` with `converted_amount` instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: EntryKind,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub amount: Money,
+    pub from_balance: Option<Money>,
+    pub to_balance: Option<Money>,
+    #[serde(default)]
+    pub converted_amount: Option<Money>,
+    #[serde(default)]
+    pub reverses: Option<u64>,
+}
+
+// Entries are never removed. Undoing an operation adds reversals of its
+// entries instead, so ids are never handed out twice.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    // Ids of the entries that have been reversed.
+    reversed: HashSet<u64>,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            EntryKind::Opening => "opening",
+            EntryKind::Transfer => "transfer",
+            EntryKind::Interest => "interest",
+            EntryKind::Merge => "merge",
+            EntryKind::Settlement => "settlement",
+            EntryKind::Disbursement => "disbursement",
+            EntryKind::Repayment => "repayment",
+            EntryKind::Fee => "fee",
+            EntryKind::Reversal => "reversal",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl fmt::Display for LedgerEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} {}: {} -> {}, Amount: {}",
+            self.id,
+            self.timestamp.to_rfc3339(),
+            self.kind,
+            self.from.as_deref().unwrap_or("-"),
+            self.to.as_deref().unwrap_or("-"),
+            self.amount
+        )
+    }
+}
+
+impl LedgerEntry {
+    pub fn credited_amount(&self) -> Money {
+        self.converted_amount.unwrap_or(self.amount)
+    }
+
+    pub fn involves(&self, user: &str) -> bool {
+        self.from.as_deref() == Some(user) || self.to.as_deref() == Some(user)
+    }
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    pub(crate) fn from_entries(entries: Vec<LedgerEntry>) -> Self {
+        let reversed = entries.iter().filter_map(|entry| entry.reverses).collect();
+        Ledger { entries, reversed }
+    }
+
+    pub fn record(
+        &mut self,
+        kind: EntryKind,
+        from: Option<(&str, Money)>,
+        to: Option<(&str, Money)>,
+        amount: Money,
+    ) -> u64 {
+        self.record_at(Utc::now(), kind, from, to, amount)
+    }
+
+    pub fn record_at(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        kind: EntryKind,
+        from: Option<(&str, Money)>,
+        to: Option<(&str, Money)>,
+        amount: Money,
+    ) -> u64 {
+        let id = self.entries.len() as u64 + 1;
+
+        self.entries.push(LedgerEntry {
+            id,
+            timestamp,
+            kind,
+            from: from.map(|(name, _)| name.to_string()),
+            to: to.map(|(name, _)| name.to_string()),
+            amount,
+            from_balance: from.map(|(_, balance)| balance),
+            to_balance: to.map(|(_, balance)| balance),
+            converted_amount: None,
+            reverses: None,
+        });
+
+        id
+    }
+
+    pub(crate) fn record_transfer(
+        &mut self,
+        from: &str,
+        to: &str,
+        amount: Money,
+        staged: StagedTransfer,
+    ) -> u64 {
+        self.record_transfer_at(Utc::now(), from, to, amount, staged)
+    }
+
+    pub(crate) fn record_transfer_at(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        from: &str,
+        to: &str,
+        amount: Money,
+        staged: StagedTransfer,
+    ) -> u64 {
+        self.record_staged_at(timestamp, EntryKind::Transfer, from, to, amount, staged)
+    }
+
+    pub(crate) fn record_staged(
+        &mut self,
+        kind: EntryKind,
+        from: &str,
+        to: &str,
+        amount: Money,
+        staged: StagedTransfer,
+    ) -> u64 {
+        self.record_staged_at(Utc::now(), kind, from, to, amount, staged)
+    }
+
+    fn record_staged_at(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        kind: EntryKind,
+        from: &str,
+        to: &str,
+        amount: Money,
+        staged: StagedTransfer,
+    ) -> u64 {
+        // The fee goes first so the transfer stays the last entry.
+        if !staged.fee.is_zero() {
+            let balance = staged
+                .from_balance
+                .checked_add(amount)
+                .unwrap_or(Money::MAX);
+            self.record_adjustment_at(
+                timestamp,
+                EntryKind::Fee,
+                from,
+                staged.fee.checked_neg().unwrap_or(Money::MIN),
+                balance,
+            );
+        }
+
+        let id = self.record_at(
+            timestamp,
+            kind,
+            Some((from, staged.from_balance)),
+            Some((to, staged.to_balance)),
+            amount,
+        );
+
+        if staged.credited != amount
+            && let Some(entry) = self.entries.last_mut()
+        {
+            entry.converted_amount = Some(staged.credited);
+        }
+
+        id
+    }
+
+    pub fn record_adjustment(
+        &mut self,
+        kind: EntryKind,
+        user: &str,
+        delta: Money,
+        balance: Money,
+    ) -> u64 {
+        self.record_adjustment_at(Utc::now(), kind, user, delta, balance)
+    }
+
+    pub fn record_adjustment_at(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        kind: EntryKind,
+        user: &str,
+        delta: Money,
+        balance: Money,
+    ) -> u64 {
+        if delta.is_negative() {
+            self.record_at(timestamp, kind, Some((user, balance)), None, delta.abs())
+        } else {
+            self.record_at(timestamp, kind, None, Some((user, balance)), delta.abs())
+        }
+    }
+
+    // Reverses every entry after the first `len`, newest first, skipping
+    // reversals and entries that have been reversed already. `balance` gives
+    // the current balance of an account; the balances each account ends up
+    // with are returned. Nothing is recorded if one of them overflows.
+    pub(crate) fn reverse_since(
+        &mut self,
+        len: usize,
+        balance: impl Fn(&str) -> Money,
+    ) -> Result<HashMap<String, Money>, BankError> {
+        let mut balances: HashMap<String, Money> = HashMap::new();
+        let mut reversals = Vec::new();
+
+        for entry in self.entries[len.min(self.entries.len())..].iter().rev() {
+            if entry.kind == EntryKind::Reversal || self.reversed.contains(&entry.id) {
+                continue;
+            }
+
+            // The money goes back the way it came, in the currency it arrived
+            // in.
+            let (from, to) = (entry.to.clone(), entry.from.clone());
+            let amount = entry.credited_amount();
+            let mut from_balance = None;
+            if let Some(from) = &from {
+                let current = balances.get(from).copied().unwrap_or_else(|| balance(from));
+                let after = current
+                    .checked_sub(amount)
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+                balances.insert(from.clone(), after);
+                from_balance = Some(after);
+            }
+            let mut to_balance = None;
+            if let Some(to) = &to {
+                let current = balances.get(to).copied().unwrap_or_else(|| balance(to));
+                let after = current
+                    .checked_add(entry.amount)
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+                balances.insert(to.clone(), after);
+                to_balance = Some(after);
+            }
+            // Both sides are the same account when nothing moved.
+            if from.is_some() && from == to {
+                from_balance = to_balance;
+            }
+
+            reversals.push(LedgerEntry {
+                id: 0,
+                timestamp: Utc::now(),
+                kind: EntryKind::Reversal,
+                from,
+                to,
+                amount,
+                from_balance,
+                to_balance,
+                converted_amount: (amount != entry.amount).then_some(entry.amount),
+                reverses: Some(entry.id),
+            });
+        }
+
+        for mut reversal in reversals {
+            reversal.id = self.entries.len() as u64 + 1;
+            self.reversed.extend(reversal.reverses);
+            self.entries.push(reversal);
+        }
+        Ok(balances)
+    }
+
+    pub fn is_reversed(&self, id: u64) -> bool {
+        self.reversed.contains(&id)
+    }
+
+    pub(crate) fn reversed_entries(&self) -> impl Iterator<Item = &LedgerEntry> {
+        self.reversed.iter().filter_map(|id| self.entry(*id))
+    }
+
+    // Entry `id`, if there is one.
+    pub fn entry(&self, id: u64) -> Option<&LedgerEntry> {
+        usize::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_sub(1))
+            .and_then(|index| self.entries.get(index))
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries_for<'a>(&'a self, user: &'a str) -> impl Iterator<Item = &'a LedgerEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.involves(user))
+    }
+
+    pub fn replay(&self) -> Result<HashMap<String, Money>, BankError> {
+        let mut balances: HashMap<String, Money> = HashMap::new();
+
+        for entry in &self.entries {
+            if let Some(from) = &entry.from {
+                let balance = balances.entry(from.clone()).or_default();
+                *balance = balance
+                    .checked_sub(entry.amount)
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+            }
+
+            if let Some(to) = &entry.to {
+                let balance = balances.entry(to.clone()).or_default();
+                *balance = balance
+                    .checked_add(entry.credited_amount())
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+            }
+        }
+
+        Ok(balances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_assigns_sequential_ids() {
+        let mut ledger = Ledger::new();
+
+        assert_eq!(
+            ledger.record(
+                EntryKind::Opening,
+                None,
+                Some(("Alice", Money::from_minor(100))),
+                Money::from_minor(100)
+            ),
+            1
+        );
+        assert_eq!(
+            ledger.record(
+                EntryKind::Transfer,
+                Some(("Alice", Money::from_minor(60))),
+                Some(("Bob", Money::from_minor(40))),
+                Money::from_minor(40)
+            ),
+            2
+        );
+
+        assert_eq!(ledger.len(), 2);
+        assert_eq!(
+            ledger.entries()[1].from_balance,
+            Some(Money::from_minor(60))
+        );
+        assert_eq!(ledger.entries()[1].to_balance, Some(Money::from_minor(40)));
+    }
+
+    #[test]
+    fn test_replay_and_query() {
+        let mut ledger = Ledger::new();
+        ledger.record(
+            EntryKind::Opening,
+            None,
+            Some(("Alice", Money::from_minor(100))),
+            Money::from_minor(100),
+        );
+        ledger.record(
+            EntryKind::Opening,
+            Some(("Bob", Money::from_minor(-50))),
+            None,
+            Money::from_minor(50),
+        );
+        ledger.record(
+            EntryKind::Opening,
+            None,
+            Some(("Carol", Money::from_minor(0))),
+            Money::from_minor(0),
+        );
+        ledger.record(
+            EntryKind::Transfer,
+            Some(("Alice", Money::from_minor(70))),
+            Some(("Bob", Money::from_minor(-20))),
+            Money::from_minor(30),
+        );
+
+        let balances = ledger.replay().unwrap();
+        assert_eq!(balances["Alice"], Money::from_minor(70));
+        assert_eq!(balances["Bob"], Money::from_minor(-20));
+        assert_eq!(balances["Carol"], Money::from_minor(0));
+
+        assert_eq!(ledger.entries_for("Bob").count(), 2);
+        assert_eq!(ledger.entries_for("Carol").count(), 1);
+        assert_eq!(ledger.entries_for("Dave").count(), 0);
+    }
+}
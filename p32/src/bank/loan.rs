@@ -0,0 +1,725 @@
+use super::{Bank, BankError, EntryKind, Money, Rounding};
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Amortization {
+    // Equal installments; the interest share shrinks as the principal does.
+    #[default]
+    Annuity,
+    // Equal principal repayments plus interest on what is still owed.
+    Linear,
+}
+
+// A term loan paid out into the borrower's account on `start` and repaid in
+// `term_months` monthly installments, the first one a month after `start`.
+// `rate` is the yearly interest rate in basis points.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Loan {
+    pub id: u64,
+    // Account id or name; names are resolved to ids when the loan is granted.
+    pub account: String,
+    pub principal: Money,
+    pub rate: u64,
+    pub term_months: u32,
+    pub amortization: Amortization,
+    pub start: NaiveDate,
+    pub(crate) outstanding: Money,
+    pub(crate) installments_paid: u32,
+    pub(crate) interest_paid: Money,
+    // The fixed part of each installment: the whole payment of an annuity,
+    // the principal share of a linear loan. Set again after an early
+    // repayment.
+    pub(crate) level: Money,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Installment {
+    pub number: u32,
+    pub due: NaiveDate,
+    pub principal: Money,
+    pub interest: Money,
+    // Principal still owed once this installment is paid.
+    pub outstanding: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallmentOutcome {
+    // `entry_id` is the repayment of principal; the interest is charged in
+    // the ledger entry right after it.
+    Paid { entry_id: u64 },
+    // The installment stays due and is collected again on the next run.
+    Missed { error: BankError },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallmentRecord {
+    pub loan_id: u64,
+    pub installment: Installment,
+    pub outcome: InstallmentOutcome,
+}
+
+impl fmt::Display for Amortization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Amortization::Annuity => write!(f, "annuity"),
+            Amortization::Linear => write!(f, "linear"),
+        }
+    }
+}
+
+impl FromStr for Amortization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "annuity" => Ok(Amortization::Annuity),
+            "linear" => Ok(Amortization::Linear),
+            _ => Err(format!("Unknown amortization '{}'", s)),
+        }
+    }
+}
+
+impl Installment {
+    pub fn payment(&self) -> Money {
+        self.principal
+            .checked_add(self.interest)
+            .unwrap_or(Money::MAX)
+    }
+}
+
+impl fmt::Display for Installment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} due {}: {} ({} principal, {} interest), {} outstanding",
+            self.number,
+            self.due,
+            self.payment(),
+            self.principal,
+            self.interest,
+            self.outstanding
+        )
+    }
+}
+
+impl fmt::Display for InstallmentRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Loan #{} installment {}: ",
+            self.loan_id, self.installment
+        )?;
+        match &self.outcome {
+            InstallmentOutcome::Paid { entry_id } => {
+                write!(f, "paid, ledger entry #{}", entry_id)
+            }
+            InstallmentOutcome::Missed { error } => write!(f, "missed: {}", error),
+        }
+    }
+}
+
+// Fixed-point scale of the discount factor in `annuity_payment`.
+const DISCOUNT_SCALE: i128 = 1_000_000_000_000_000;
+
+// Payment that pays off `outstanding` in `remaining` equal monthly
+// installments at `rate` bp a year: outstanding * r / (1 - (1 + r)^-n), with
+// r the monthly rate. The discount factor (1 + r)^-n is worked out in fixed
+// point, and the payment rounded half up to a minor unit; the last
+// installment settles whatever is left. A payment too large to represent
+// saturates, so the whole loan falls due at once.
+fn annuity_payment(outstanding: Money, rate: u64, remaining: u32) -> Money {
+    let rounding = Rounding::HalfUp;
+    let owed = i128::from(outstanding.minor());
+    let to_money = |value: i128| Money::from_minor(i64::try_from(value).unwrap_or(i64::MAX));
+    if rate == 0 {
+        return to_money(rounding.divide(owed, i128::from(remaining)));
+    }
+
+    let months = 10_000 * 12;
+    let rate = i128::from(rate);
+    // One month's discount, 1 / (1 + r), raised to the n-th power by squaring.
+    let mut step = rounding.divide(DISCOUNT_SCALE * months, months + rate);
+    let mut discount = DISCOUNT_SCALE;
+    let mut exponent = remaining;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            discount = rounding.divide(discount * step, DISCOUNT_SCALE);
+        }
+        step = rounding.divide(step * step, DISCOUNT_SCALE);
+        exponent >>= 1;
+    }
+
+    let payment = owed
+        .checked_mul(rate)
+        .and_then(|numerator| numerator.checked_mul(DISCOUNT_SCALE))
+        .map_or(i128::MAX, |numerator| {
+            rounding.divide(numerator, months * (DISCOUNT_SCALE - discount))
+        });
+    to_money(payment)
+}
+
+impl Loan {
+    pub fn new(
+        account: &str,
+        principal: Money,
+        rate: u64,
+        term_months: u32,
+        amortization: Amortization,
+        start: NaiveDate,
+    ) -> Self {
+        let mut loan = Loan {
+            id: 0,
+            account: account.to_string(),
+            principal,
+            rate,
+            term_months,
+            amortization,
+            start,
+            outstanding: principal,
+            installments_paid: 0,
+            interest_paid: Money::ZERO,
+            level: Money::ZERO,
+        };
+        loan.level = loan.level_for(principal, term_months);
+        loan
+    }
+
+    // Never rounds down to nothing while something is owed, so every
+    // installment pays off at least one minor unit and a loan smaller than
+    // its term is repaid early rather than all in the last installment.
+    fn level_for(&self, outstanding: Money, remaining: u32) -> Money {
+        let remaining = remaining.max(1);
+        let level = match self.amortization {
+            Amortization::Annuity => annuity_payment(outstanding, self.rate, remaining),
+            Amortization::Linear => Money::from_minor(
+                Rounding::HalfUp.divide(i128::from(outstanding.minor()), i128::from(remaining))
+                    as i64,
+            ),
+        };
+        if outstanding.is_positive() {
+            level.max(Money::from_minor(1))
+        } else {
+            level
+        }
+    }
+
+    pub fn outstanding(&self) -> Money {
+        self.outstanding
+    }
+
+    pub fn principal_repaid(&self) -> Money {
+        self.principal
+            .checked_sub(self.outstanding)
+            .unwrap_or(Money::ZERO)
+    }
+
+    pub fn interest_paid(&self) -> Money {
+        self.interest_paid
+    }
+
+    pub fn installments_paid(&self) -> u32 {
+        self.installments_paid
+    }
+
+    pub fn is_repaid(&self) -> bool {
+        self.outstanding.is_zero()
+    }
+
+    fn due_date(&self, number: u32) -> NaiveDate {
+        self.start
+            .checked_add_months(Months::new(number))
+            .unwrap_or(NaiveDate::MAX)
+    }
+
+    pub fn next_due(&self) -> Option<NaiveDate> {
+        (!self.is_repaid()).then(|| self.due_date(self.installments_paid + 1))
+    }
+
+    // The installment due after `paid` installments while `outstanding` is
+    // still owed. Interest is charged monthly on the outstanding principal.
+    fn installment(&self, paid: u32, outstanding: Money, rounding: Rounding) -> Installment {
+        let owed = i128::from(outstanding.minor());
+        let interest = rounding.divide(owed * i128::from(self.rate), 10_000 * 12);
+        let level = i128::from(self.level.minor());
+
+        // The last installment settles whatever rounding left over.
+        let principal = if paid + 1 >= self.term_months {
+            owed
+        } else {
+            match self.amortization {
+                Amortization::Annuity => level - interest,
+                Amortization::Linear => level,
+            }
+        }
+        .clamp(0, owed);
+
+        let to_money = |value: i128| Money::from_minor(i64::try_from(value).unwrap_or(i64::MAX));
+        Installment {
+            number: paid + 1,
+            due: self.due_date(paid + 1),
+            principal: to_money(principal),
+            interest: to_money(interest),
+            outstanding: to_money(owed - principal),
+        }
+    }
+
+    // Remaining installments as things stand, after any early repayments.
+    // Annuity loans keep their term, so an early repayment lowers the
+    // installments rather than shortening the loan.
+    pub fn schedule(&self, rounding: Rounding) -> Vec<Installment> {
+        let mut installments = Vec::new();
+        let mut outstanding = self.outstanding;
+        let mut paid = self.installments_paid;
+
+        while outstanding.is_positive() {
+            let installment = self.installment(paid, outstanding, rounding);
+            outstanding = installment.outstanding;
+            paid += 1;
+            installments.push(installment);
+        }
+        installments
+    }
+}
+
+impl Bank {
+    pub fn loans(&self) -> &[Loan] {
+        &self.loans
+    }
+
+    pub fn loan(&self, id: u64) -> Result<&Loan, BankError> {
+        self.loans
+            .iter()
+            .find(|loan| loan.id == id)
+            .ok_or(BankError::LoanNotFound(id))
+    }
+
+    pub fn loans_of(&self, reference: &str) -> Result<Vec<&Loan>, BankError> {
+        let id = self.account_id(reference)?;
+        Ok(self
+            .loans
+            .iter()
+            .filter(|loan| loan.account == id.as_str())
+            .collect())
+    }
+
+    fn loan_index(&self, id: u64) -> Result<usize, BankError> {
+        self.loans
+            .iter()
+            .position(|loan| loan.id == id)
+            .ok_or(BankError::LoanNotFound(id))
+    }
+
+    // Pays the principal out into the borrower's account on the loan's start
+    // date and returns the new loan's id.
+    pub fn grant_loan(&mut self, mut loan: Loan) -> Result<u64, BankError> {
+        let snapshot = self.operation_snapshot().with_loans(self);
+        let id = self.account_id(&loan.account)?;
+        if !loan.principal.is_positive() {
+            return Err(BankError::InvalidLoan(format!(
+                "principal {} must be positive",
+                loan.principal
+            )));
+        }
+        if loan.term_months == 0 {
+            return Err(BankError::InvalidLoan(
+                "term must be at least one month".to_string(),
+            ));
+        }
+
+        let user = self.users.get_mut(id.as_str()).expect("Account exists");
+        user.check_active()?;
+        user.balance = user
+            .balance
+            .checked_add(loan.principal)
+            .ok_or_else(|| BankError::TransferOverflow(user.name.clone()))?;
+        self.ledger.record_adjustment_at(
+            midnight(loan.start),
+            EntryKind::Disbursement,
+            id.as_str(),
+            loan.principal,
+            user.balance,
+        );
+
+        loan.id = self.loans.iter().map(|loan| loan.id).max().unwrap_or(0) + 1;
+        loan.account = id.to_string();
+        loan.outstanding = loan.principal;
+        loan.installments_paid = 0;
+        loan.interest_paid = Money::ZERO;
+        loan.level = loan.level_for(loan.principal, loan.term_months);
+
+        let loan_id = loan.id;
+        self.loans.push(loan);
+        self.checkpoint(snapshot);
+        Ok(loan_id)
+    }
+
+    // Debits `principal` and `interest` from the borrower's account, within
+    // its credit line, dated `date`. The two are separate ledger entries, a
+    // repayment followed by an interest charge; the first one's id is
+    // returned.
+    fn debit_repayment(
+        &mut self,
+        account: &str,
+        principal: Money,
+        interest: Money,
+        date: NaiveDate,
+    ) -> Result<u64, BankError> {
+        let user = self
+            .users
+            .get_mut(account)
+            .ok_or_else(|| BankError::UserNotFound(account.to_string()))?;
+        user.check_active()?;
+        let total = principal
+            .checked_add(interest)
+            .ok_or_else(|| BankError::TransferOverflow(user.name.clone()))?;
+        user.checked_debit(user.balance, total)?;
+
+        let mut entry_ids = Vec::new();
+        for (kind, amount) in [
+            (EntryKind::Repayment, principal),
+            (EntryKind::Interest, interest),
+        ] {
+            if amount.is_zero() {
+                continue;
+            }
+            user.balance = user.checked_debit(user.balance, amount)?;
+            entry_ids.push(self.ledger.record_adjustment_at(
+                midnight(date),
+                kind,
+                account,
+                amount.checked_neg().unwrap_or(Money::MIN),
+                user.balance,
+            ));
+        }
+        entry_ids
+            .first()
+            .copied()
+            .ok_or_else(|| BankError::InvalidLoan("installment has nothing to pay".to_string()))
+    }
+
+    // Repays up to `amount` of the outstanding principal ahead of schedule,
+    // without interest or fees. Returns the ledger entry of the repayment.
+    pub fn repay_loan(
+        &mut self,
+        id: u64,
+        amount: Money,
+        date: NaiveDate,
+    ) -> Result<u64, BankError> {
+        let snapshot = self.operation_snapshot().with_loans(self);
+        let index = self.loan_index(id)?;
+        let loan = &self.loans[index];
+        if loan.is_repaid() {
+            return Err(BankError::LoanRepaid(id));
+        }
+        if !amount.is_positive() {
+            return Err(BankError::NegativeAmount(amount));
+        }
+
+        let amount = amount.min(loan.outstanding);
+        let account = loan.account.clone();
+        let entry_id = self.debit_repayment(&account, amount, Money::ZERO, date)?;
+
+        let loan = &mut self.loans[index];
+        loan.outstanding = loan
+            .outstanding
+            .checked_sub(amount)
+            .expect("Repayment is capped at the outstanding principal");
+        loan.level = loan.level_for(
+            loan.outstanding,
+            loan.term_months.saturating_sub(loan.installments_paid),
+        );
+        self.checkpoint(snapshot);
+        Ok(entry_id)
+    }
+
+    // Debits every installment falling due up to and including `until`, in
+    // loan id order. A loan whose installment can't be paid is skipped until
+    // the next run, which tries the same installment again. Nothing else
+    // collects installments, `run_until` included, so callers run this
+    // themselves whenever they move the bank's date forward.
+    pub fn collect_installments(&mut self, until: NaiveDate) -> Vec<InstallmentRecord> {
+        let snapshot = self.operation_snapshot().with_loans(self);
+        let mut records = Vec::new();
+        self.loans.sort_by_key(|loan| loan.id);
+
+        for index in 0..self.loans.len() {
+            while let Some(due) = self.loans[index].next_due().filter(|due| *due <= until) {
+                let loan = &self.loans[index];
+                let installment = loan.installment(
+                    loan.installments_paid,
+                    loan.outstanding,
+                    self.interest_config.rounding,
+                );
+                debug_assert_eq!(installment.due, due);
+
+                let account = loan.account.clone();
+                let outcome = match self.debit_repayment(
+                    &account,
+                    installment.principal,
+                    installment.interest,
+                    due,
+                ) {
+                    Ok(entry_id) => {
+                        let loan = &mut self.loans[index];
+                        loan.outstanding = installment.outstanding;
+                        loan.installments_paid += 1;
+                        loan.interest_paid = loan
+                            .interest_paid
+                            .checked_add(installment.interest)
+                            .unwrap_or(Money::MAX);
+                        InstallmentOutcome::Paid { entry_id }
+                    }
+                    Err(error) => InstallmentOutcome::Missed { error },
+                };
+
+                let missed = matches!(outcome, InstallmentOutcome::Missed { .. });
+                records.push(InstallmentRecord {
+                    loan_id: self.loans[index].id,
+                    installment,
+                    outcome,
+                });
+                if missed {
+                    break;
+                }
+            }
+        }
+
+        if !records.is_empty() {
+            self.checkpoint(snapshot);
+        }
+        records
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("Midnight is a valid time")
+        .and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::User;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(50_000),
+        ))
+        .unwrap();
+        bank
+    }
+
+    fn loan(amortization: Amortization) -> Loan {
+        Loan::new(
+            "Alice",
+            Money::from_minor(120_000),
+            1200,
+            12,
+            amortization,
+            date(2026, 1, 31),
+        )
+    }
+
+    #[test]
+    fn test_schedules() {
+        let annuity = loan(Amortization::Annuity).schedule(Rounding::HalfEven);
+        assert_eq!(annuity.len(), 12);
+        // 1,200.00 at 1% a month over a year: 106.62 a month.
+        assert_eq!(annuity[0].payment(), Money::from_minor(10_662));
+        assert_eq!(annuity[0].interest, Money::from_minor(1_200));
+        assert_eq!(annuity[0].due, date(2026, 2, 28));
+        assert!(
+            annuity[..11]
+                .iter()
+                .all(|installment| installment.payment() == Money::from_minor(10_662))
+        );
+        assert_eq!(annuity[11].outstanding, Money::ZERO);
+        let principal: i64 = annuity.iter().map(|i| i.principal.minor()).sum();
+        assert_eq!(principal, 120_000);
+
+        let linear = loan(Amortization::Linear).schedule(Rounding::HalfEven);
+        assert_eq!(linear.len(), 12);
+        assert!(
+            linear
+                .iter()
+                .all(|installment| installment.principal == Money::from_minor(10_000))
+        );
+        assert_eq!(linear[0].interest, Money::from_minor(1_200));
+        assert_eq!(linear[11].interest, Money::from_minor(100));
+
+        let interest_free = Loan::new(
+            "Alice",
+            Money::from_minor(1_000),
+            0,
+            3,
+            Amortization::Annuity,
+            date(2026, 1, 1),
+        );
+        let payments: Vec<Money> = interest_free
+            .schedule(Rounding::HalfEven)
+            .iter()
+            .map(Installment::payment)
+            .collect();
+        assert_eq!(
+            payments,
+            vec![
+                Money::from_minor(333),
+                Money::from_minor(333),
+                Money::from_minor(334)
+            ]
+        );
+
+        // 200,000.00 over 30 years at 6%: 1,199.10 a month.
+        assert_eq!(
+            annuity_payment(Money::from_minor(20_000_000), 600, 360),
+            Money::from_minor(119_910)
+        );
+        // A single installment is the principal plus a month's interest.
+        assert_eq!(
+            annuity_payment(Money::from_minor(100_000), 1200, 1),
+            Money::from_minor(101_000)
+        );
+        assert_eq!(annuity_payment(Money::MAX, u64::MAX, 12), Money::MAX);
+    }
+
+    #[test]
+    fn test_collect_and_repay() {
+        let mut bank = bank();
+        let id = bank.grant_loan(loan(Amortization::Linear)).unwrap();
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(170_000)
+        );
+
+        let records = bank.collect_installments(date(2026, 4, 30));
+        assert_eq!(records.len(), 3);
+        let loan = bank.loan(id).unwrap();
+        assert_eq!(loan.installments_paid(), 3);
+        assert_eq!(loan.outstanding(), Money::from_minor(90_000));
+        assert_eq!(
+            loan.interest_paid(),
+            Money::from_minor(1_200 + 1_100 + 1_000)
+        );
+        assert_eq!(loan.next_due(), Some(date(2026, 5, 31)));
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(170_000 - 30_000 - 3_300)
+        );
+
+        // An early repayment lowers the following installments.
+        bank.repay_loan(id, Money::from_minor(45_000), date(2026, 5, 1))
+            .unwrap();
+        let schedule = bank.loan(id).unwrap().schedule(Rounding::HalfEven);
+        assert_eq!(schedule.len(), 9);
+        assert_eq!(schedule[0].principal, Money::from_minor(5_000));
+        assert_eq!(schedule[0].interest, Money::from_minor(450));
+
+        // Repaying more than is owed only takes what is outstanding.
+        bank.repay_loan(id, Money::from_minor(1_000_000), date(2026, 5, 2))
+            .unwrap();
+        assert!(bank.loan(id).unwrap().is_repaid());
+        assert_eq!(bank.loan(id).unwrap().next_due(), None);
+        assert_eq!(
+            bank.repay_loan(id, Money::from_minor(1), date(2026, 5, 3)),
+            Err(BankError::LoanRepaid(id))
+        );
+        assert!(bank.collect_installments(date(2027, 1, 1)).is_empty());
+
+        let replayed = bank.ledger().replay().unwrap();
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(replayed[alice.id.as_str()], alice.balance);
+    }
+
+    #[test]
+    fn test_missed_installments() {
+        let mut bank = bank();
+        bank.add_user(User::new("Bob".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        let id = bank.grant_loan(loan(Amortization::Annuity)).unwrap();
+        // Alice spends all but 150.00 of the loan and her savings.
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(155_000))
+            .unwrap();
+
+        let records = bank.collect_installments(date(2026, 4, 30));
+        assert_eq!(records.len(), 2);
+        assert!(matches!(
+            records[1].outcome,
+            InstallmentOutcome::Missed {
+                error: BankError::InsufficientCredit { .. }
+            }
+        ));
+        assert_eq!(bank.loan(id).unwrap().installments_paid(), 1);
+
+        // The missed installment is the first one collected next time.
+        bank.transfer_funds("Bob", "Alice", Money::from_minor(100_000))
+            .unwrap();
+        let records = bank.collect_installments(date(2026, 4, 30));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].installment.number, 2);
+        assert_eq!(records[0].installment.due, date(2026, 3, 31));
+        assert!(bank.reconcile().unwrap().is_empty());
+        let replayed = bank.ledger().replay().unwrap();
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(replayed[alice.id.as_str()], alice.balance);
+
+        assert_eq!(
+            bank.grant_loan(Loan::new(
+                "Alice",
+                Money::ZERO,
+                100,
+                12,
+                Amortization::Linear,
+                date(2026, 1, 1)
+            )),
+            Err(BankError::InvalidLoan(
+                "principal 0.00 must be positive".to_string()
+            ))
+        );
+        assert_eq!(bank.loan(99), Err(BankError::LoanNotFound(99)));
+    }
+
+    #[test]
+    fn test_loans_smaller_than_their_term() {
+        let mut bank = bank();
+        let id = bank
+            .grant_loan(Loan::new(
+                "Alice",
+                Money::from_minor(5),
+                0,
+                12,
+                Amortization::Linear,
+                date(2026, 1, 1),
+            ))
+            .unwrap();
+
+        // One minor unit a month until it's paid off.
+        let records = bank.collect_installments(date(2027, 1, 1));
+        assert_eq!(records.len(), 5);
+        assert!(records.iter().all(
+            |record| record.installment.payment() == Money::from_minor(1)
+                && matches!(record.outcome, InstallmentOutcome::Paid { .. })
+        ));
+        assert!(bank.loan(id).unwrap().is_repaid());
+
+        // Repaying early can leave less than one unit per remaining month.
+        let id = bank.grant_loan(loan(Amortization::Annuity)).unwrap();
+        bank.repay_loan(id, Money::from_minor(119_997), date(2026, 1, 15))
+            .unwrap();
+        let records = bank.collect_installments(date(2027, 1, 1));
+        assert_eq!(records.len(), 3);
+        assert!(bank.loan(id).unwrap().is_repaid());
+        assert!(bank.reconcile().unwrap().is_empty());
+    }
+}
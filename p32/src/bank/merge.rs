@@ -0,0 +1,817 @@
+use super::{
+    AccountId, AccountStatus, Bank, BankError, BankEvent, Currency, EntryKind, ExchangeRates,
+    Money, Rounding, User,
+};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+// What happens to a user of the other bank whose name is already taken.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    // Fold the other account into the existing one.
+    #[default]
+    Combine,
+    // Keep both accounts, adding a prefix to the incoming name.
+    Rename {
+        prefix: String,
+    },
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CreditLinePolicy {
+    #[default]
+    Keep,
+    Max,
+    Min,
+    Sum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InterestPolicy {
+    #[default]
+    Average,
+    // Each bank's rate weighs in proportion to the balances it applies to:
+    // overdrafts for the credit interest, deposits for the debit interest.
+    WeightedByBalance,
+    Keep,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MergePolicy {
+    pub conflicts: ConflictPolicy,
+    pub credit_lines: CreditLinePolicy,
+    pub interest: InterestPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeAction {
+    Add,
+    Rename,
+    Combine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserMerge {
+    pub source: String,
+    pub source_account: AccountId,
+    pub target: String,
+    // Account in this bank that ends up holding the money.
+    pub account: AccountId,
+    pub action: MergeAction,
+    pub currency: Currency,
+    // Amount added to the target account, in the target account's currency.
+    pub credited: Money,
+    pub balance_before: Option<Money>,
+    pub balance_after: Money,
+    pub credit_line_before: Option<Money>,
+    pub credit_line_after: Money,
+    #[serde(skip)]
+    carry_after: i64,
+    #[serde(skip)]
+    customer_after: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RateChange {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: u64,
+}
+
+// Everything `merge_bank_with` would do, worked out without touching either
+// bank.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergePlan {
+    pub source: String,
+    pub users: Vec<UserMerge>,
+    pub new_rates: Vec<RateChange>,
+    pub credit_interest_before: u64,
+    pub credit_interest_after: u64,
+    pub debit_interest_before: u64,
+    pub debit_interest_after: u64,
+}
+
+impl MergePlan {
+    pub fn conflicts(&self) -> impl Iterator<Item = &UserMerge> {
+        self.users
+            .iter()
+            .filter(|user| user.action != MergeAction::Add)
+    }
+}
+
+impl fmt::Display for MergePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Merge plan for {}:", self.source)?;
+        for user in &self.users {
+            match (user.action, user.balance_before, user.credit_line_before) {
+                (MergeAction::Combine, Some(balance), Some(credit_line)) => writeln!(
+                    f,
+                    "  ~ {}: {} -> {} {} (credit line {} -> {})",
+                    user.target,
+                    balance,
+                    user.balance_after,
+                    user.currency,
+                    credit_line,
+                    user.credit_line_after
+                )?,
+                (MergeAction::Rename, _, _) => writeln!(
+                    f,
+                    "  + {} (renamed from {}): {} {} (credit line {})",
+                    user.target,
+                    user.source,
+                    user.balance_after,
+                    user.currency,
+                    user.credit_line_after
+                )?,
+                _ => writeln!(
+                    f,
+                    "  + {}: {} {} (credit line {})",
+                    user.target, user.balance_after, user.currency, user.credit_line_after
+                )?,
+            }
+        }
+        for rate in &self.new_rates {
+            writeln!(f, "  + rate {} -> {}: {}", rate.from, rate.to, rate.rate)?;
+        }
+        writeln!(
+            f,
+            "  credit interest: {} -> {}",
+            self.credit_interest_before, self.credit_interest_after
+        )?;
+        write!(
+            f,
+            "  debit interest: {} -> {}",
+            self.debit_interest_before, self.debit_interest_after
+        )
+    }
+}
+
+impl FromStr for CreditLinePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(CreditLinePolicy::Keep),
+            "max" => Ok(CreditLinePolicy::Max),
+            "min" => Ok(CreditLinePolicy::Min),
+            "sum" => Ok(CreditLinePolicy::Sum),
+            _ => Err(format!("Unknown credit line policy '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for InterestPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "average" => Ok(InterestPolicy::Average),
+            "weighted" => Ok(InterestPolicy::WeightedByBalance),
+            "keep" => Ok(InterestPolicy::Keep),
+            _ => Err(format!("Unknown interest policy '{}'", s)),
+        }
+    }
+}
+
+// Deposits and overdrafts of `users`, both as positive amounts in `currency`.
+fn exposure(
+    users: &HashMap<String, User>,
+    rates: &ExchangeRates,
+    currency: Currency,
+    rounding: Rounding,
+) -> Result<(u128, u128), BankError> {
+    let mut deposits: u128 = 0;
+    let mut overdrafts: u128 = 0;
+
+    for user in users.values() {
+        let balance = rates.convert(user.balance, user.currency, currency, rounding)?;
+        let amount = u128::from(balance.minor().unsigned_abs());
+        if balance.is_positive() {
+            deposits += amount;
+        } else {
+            overdrafts += amount;
+        }
+    }
+
+    Ok((deposits, overdrafts))
+}
+
+fn merged_rate(
+    policy: InterestPolicy,
+    own: u64,
+    other: u64,
+    own_weight: u128,
+    other_weight: u128,
+) -> Result<u64, BankError> {
+    let average = || {
+        own.checked_add(other)
+            .map(|sum| sum / 2)
+            .ok_or(BankError::InterestRateOverflow)
+    };
+
+    match policy {
+        InterestPolicy::Keep => Ok(own),
+        InterestPolicy::Average => average(),
+        InterestPolicy::WeightedByBalance => {
+            let total_weight = own_weight + other_weight;
+            if total_weight == 0 {
+                return average();
+            }
+
+            (own as u128)
+                .checked_mul(own_weight)
+                .zip((other as u128).checked_mul(other_weight))
+                .and_then(|(own, other)| own.checked_add(other))
+                .and_then(|weighted| u64::try_from(weighted / total_weight).ok())
+                .ok_or(BankError::InterestRateOverflow)
+        }
+    }
+}
+
+impl Bank {
+    pub fn plan_merge(&self, other: &Bank, policy: &MergePolicy) -> Result<MergePlan, BankError> {
+        let mut rates = self.exchange_rates.clone();
+        let mut new_rates: Vec<RateChange> = other
+            .exchange_rates
+            .rates()
+            .filter(|&(from, to, _)| self.exchange_rates.rate(from, to).is_none())
+            .map(|(from, to, rate)| RateChange { from, to, rate })
+            .collect();
+        new_rates.sort_by_key(|rate| (rate.from.to_string(), rate.to.to_string()));
+        for rate in &new_rates {
+            rates.set_rate(rate.from, rate.to, rate.rate);
+        }
+
+        let mut incoming: Vec<&User> = other.users.values().collect();
+        incoming.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+
+        let mut taken: HashSet<&str> = self.users.values().map(|user| user.name.as_str()).collect();
+        taken.extend(other.users.values().map(|user| user.name.as_str()));
+
+        // Incoming accounts keep their id unless it's already in use here, and
+        // their customers are numbered after ours.
+        let mut assigned = HashSet::new();
+        let mut last_sequence = self
+            .users
+            .values()
+            .chain(other.users.values())
+            .filter_map(|user| user.id.sequence())
+            .max()
+            .unwrap_or(0);
+        let mut account_for = |id: &AccountId| {
+            if id.is_assigned()
+                && !self.users.contains_key(id.as_str())
+                && assigned.insert(id.clone())
+            {
+                return id.clone();
+            }
+            last_sequence += 1;
+            let id = AccountId::new(last_sequence);
+            assigned.insert(id.clone());
+            id
+        };
+        let customer_offset = self.next_customer() - 1;
+
+        let mut users = Vec::with_capacity(incoming.len());
+        let mut renamed = HashSet::new();
+
+        for user in incoming {
+            let name = &user.name;
+            let carry = other
+                .interest_carry
+                .get(user.id.as_str())
+                .copied()
+                .unwrap_or(0);
+            let customer_after = user
+                .customer
+                .checked_add(customer_offset)
+                .ok_or_else(|| BankError::MergeOverflow(name.clone()))?;
+
+            let existing = self.find_by_name(name);
+            if existing.is_empty() {
+                users.push(UserMerge {
+                    source: name.clone(),
+                    source_account: user.id.clone(),
+                    target: name.clone(),
+                    account: account_for(&user.id),
+                    action: MergeAction::Add,
+                    currency: user.currency,
+                    credited: user.balance,
+                    balance_before: None,
+                    balance_after: user.balance,
+                    credit_line_before: None,
+                    credit_line_after: user.credit_line,
+                    carry_after: carry,
+                    customer_after,
+                });
+                continue;
+            }
+
+            match &policy.conflicts {
+                ConflictPolicy::Fail => return Err(BankError::MergeConflict(name.clone())),
+                ConflictPolicy::Rename { prefix } => {
+                    let target = format!("{}{}", prefix, name);
+                    if taken.contains(target.as_str()) || !renamed.insert(target.clone()) {
+                        return Err(BankError::MergeConflict(target));
+                    }
+
+                    users.push(UserMerge {
+                        source: name.clone(),
+                        source_account: user.id.clone(),
+                        target,
+                        account: account_for(&user.id),
+                        action: MergeAction::Rename,
+                        currency: user.currency,
+                        credited: user.balance,
+                        balance_before: None,
+                        balance_after: user.balance,
+                        credit_line_before: None,
+                        credit_line_after: user.credit_line,
+                        carry_after: carry,
+                        customer_after,
+                    });
+                }
+                ConflictPolicy::Combine => {
+                    // Combining by name needs exactly one account under it on
+                    // either side.
+                    let ([existing], 1) = (existing.as_slice(), other.find_by_name(name).len())
+                    else {
+                        return Err(BankError::MergeConflict(name.clone()));
+                    };
+
+                    // A closed account can't take in money, so the other
+                    // account needs a new name instead.
+                    if existing.status == AccountStatus::Closed {
+                        return Err(BankError::AccountClosed(name.clone()));
+                    }
+
+                    let credited = rates.convert(
+                        user.balance,
+                        user.currency,
+                        existing.currency,
+                        self.fx_rounding,
+                    )?;
+                    let balance_after = existing
+                        .balance
+                        .checked_add(credited)
+                        .ok_or_else(|| BankError::MergeOverflow(name.clone()))?;
+
+                    let other_credit_line = rates.convert(
+                        user.credit_line,
+                        user.currency,
+                        existing.currency,
+                        self.fx_rounding,
+                    )?;
+                    let credit_line_after = match policy.credit_lines {
+                        CreditLinePolicy::Keep => existing.credit_line,
+                        CreditLinePolicy::Max => existing.credit_line.max(other_credit_line),
+                        CreditLinePolicy::Min => existing.credit_line.min(other_credit_line),
+                        CreditLinePolicy::Sum => existing
+                            .credit_line
+                            .checked_add(other_credit_line)
+                            .ok_or_else(|| BankError::CreditLineTooLarge(name.clone()))?,
+                    };
+
+                    // Sub-unit remainders only make sense in the account's own
+                    // currency, so they're dropped when the currencies differ.
+                    let mut carry_after = self
+                        .interest_carry
+                        .get(existing.id.as_str())
+                        .copied()
+                        .unwrap_or(0);
+                    if existing.currency == user.currency {
+                        carry_after = carry_after
+                            .checked_add(carry)
+                            .ok_or_else(|| BankError::MergeOverflow(name.clone()))?;
+                    }
+
+                    users.push(UserMerge {
+                        source: name.clone(),
+                        source_account: user.id.clone(),
+                        target: name.clone(),
+                        account: existing.id.clone(),
+                        action: MergeAction::Combine,
+                        currency: existing.currency,
+                        credited,
+                        balance_before: Some(existing.balance),
+                        balance_after,
+                        credit_line_before: Some(existing.credit_line),
+                        credit_line_after,
+                        carry_after,
+                        customer_after: existing.customer,
+                    });
+                }
+            }
+        }
+
+        // Standing orders and loans follow their accounts, so every account
+        // they name has to be coming over, and what is still owed on the
+        // loans has to fit among our receivables.
+        let target_of = |account: &str| {
+            users
+                .iter()
+                .find(|merge| merge.source_account.as_str() == account)
+                .ok_or_else(|| BankError::UserNotFound(account.to_string()))
+        };
+        for order in &other.standing_orders {
+            target_of(&order.from)?;
+            target_of(&order.to)?;
+        }
+        let mut receivable: HashMap<Currency, Money> = HashMap::new();
+        for loan in &self.loans {
+            let total = receivable
+                .entry(self.currency_of(&loan.account))
+                .or_default();
+            *total = total
+                .checked_add(loan.outstanding)
+                .ok_or(BankError::BalanceOverflow)?;
+        }
+        for loan in &other.loans {
+            let merge = target_of(&loan.account)?;
+            let total = receivable.entry(merge.currency).or_default();
+            *total = total
+                .checked_add(loan.outstanding)
+                .ok_or_else(|| BankError::MergeOverflow(merge.target.clone()))?;
+        }
+
+        let (own_deposits, own_overdrafts) =
+            exposure(&self.users, &rates, self.currency, self.fx_rounding)?;
+        let (other_deposits, other_overdrafts) =
+            exposure(&other.users, &rates, self.currency, self.fx_rounding)?;
+
+        Ok(MergePlan {
+            source: other.name.clone(),
+            users,
+            new_rates,
+            credit_interest_before: self.credit_interest,
+            credit_interest_after: merged_rate(
+                policy.interest,
+                self.credit_interest,
+                other.credit_interest,
+                own_overdrafts,
+                other_overdrafts,
+            )?,
+            debit_interest_before: self.debit_interest,
+            debit_interest_after: merged_rate(
+                policy.interest,
+                self.debit_interest,
+                other.debit_interest,
+                own_deposits,
+                other_deposits,
+            )?,
+        })
+    }
+
+    // Everything that can fail is checked while planning, so either the
+    // whole merge is applied or the bank is left untouched.
+    pub fn merge_bank_with(
+        &mut self,
+        mut other: Bank,
+        policy: &MergePolicy,
+    ) -> Result<MergePlan, BankError> {
+        let plan = self.plan_merge(&other, policy)?;
+
+        // Standing orders and loans follow their accounts, renumbered after
+        // ours. They are moved over before anything changes here, so a
+        // missing account leaves the bank untouched.
+        let targets: HashMap<&str, &UserMerge> = plan
+            .users
+            .iter()
+            .map(|merge| (merge.source_account.as_str(), merge))
+            .collect();
+        let target = |account: &str| {
+            targets
+                .get(account)
+                .copied()
+                .ok_or_else(|| BankError::UserNotFound(account.to_string()))
+        };
+        let mut next_id = self
+            .standing_orders
+            .iter()
+            .map(|order| order.id)
+            .max()
+            .unwrap_or(0);
+        let mut orders = Vec::with_capacity(other.standing_orders.len());
+        for mut order in other.standing_orders.drain(..) {
+            next_id += 1;
+            order.id = next_id;
+            order.from = target(&order.from)?.account.to_string();
+            order.to = target(&order.to)?.account.to_string();
+            orders.push(order);
+        }
+        let mut next_id = self.loans.iter().map(|loan| loan.id).max().unwrap_or(0);
+        let mut loans = Vec::with_capacity(other.loans.len());
+        for mut loan in other.loans.drain(..) {
+            next_id += 1;
+            loan.id = next_id;
+            let merge = target(&loan.account)?;
+            loan.account = merge.account.to_string();
+            loans.push((loan, merge.balance_after));
+        }
+
+        let snapshot = self.snapshot();
+
+        for rate in &plan.new_rates {
+            self.exchange_rates.set_rate(rate.from, rate.to, rate.rate);
+        }
+
+        for merge in &plan.users {
+            let mut user = other
+                .users
+                .remove(merge.source_account.as_str())
+                .expect("Planned user exists in the other bank");
+
+            match merge.action {
+                MergeAction::Add | MergeAction::Rename => {
+                    user.id = merge.account.clone();
+                    user.name = merge.target.clone();
+                    user.customer = merge.customer_after;
+                    self.ledger.record_adjustment(
+                        EntryKind::Merge,
+                        merge.account.as_str(),
+                        merge.balance_after,
+                        merge.balance_after,
+                    );
+                    self.users.insert(merge.account.to_string(), user);
+                }
+                MergeAction::Combine => {
+                    let existing = self
+                        .users
+                        .get_mut(merge.account.as_str())
+                        .expect("Planned user exists in this bank");
+                    existing.balance = merge.balance_after;
+                    existing.credit_line = merge.credit_line_after;
+                    self.ledger.record_adjustment(
+                        EntryKind::Merge,
+                        merge.account.as_str(),
+                        merge.credited,
+                        merge.balance_after,
+                    );
+                }
+            }
+
+            if merge.carry_after == 0 {
+                self.interest_carry.remove(merge.account.as_str());
+            } else {
+                self.interest_carry
+                    .insert(merge.account.to_string(), merge.carry_after);
+            }
+        }
+
+        self.standing_orders.extend(orders);
+
+        // What is still owed on the loans comes in as one merge entry naming
+        // the account on both sides: the account's balance stays as it is,
+        // but the loan shows up among the bank's receivables.
+        for (loan, balance) in loans {
+            if loan.outstanding.is_positive() {
+                self.ledger.record(
+                    EntryKind::Merge,
+                    Some((&loan.account, balance)),
+                    Some((&loan.account, balance)),
+                    loan.outstanding,
+                );
+            }
+            self.loans.push(loan);
+        }
+
+        self.credit_interest = plan.credit_interest_after;
+        self.debit_interest = plan.debit_interest_after;
+
+        self.events
+            .publish(BankEvent::BankMerged { plan: plan.clone() });
+        self.checkpoint(snapshot);
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Amortization, Loan};
+
+    fn banks() -> (Bank, Bank) {
+        let mut bank1 = Bank::new("Bank 1".to_string(), 500, 300);
+        bank1
+            .add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(1000),
+                Money::from_minor(1000),
+            ))
+            .unwrap();
+        bank1
+            .add_user(User::new(
+                "Bob".to_string(),
+                Money::from_minor(2000),
+                Money::from_minor(-500),
+            ))
+            .unwrap();
+
+        let mut bank2 = Bank::new("Bank 2".to_string(), 600, 400);
+        bank2
+            .add_user(User::new(
+                "Alice".to_string(),
+                Money::from_minor(1500),
+                Money::from_minor(500),
+            ))
+            .unwrap();
+        bank2
+            .add_user(User::new(
+                "Charlie".to_string(),
+                Money::from_minor(1500),
+                Money::from_minor(-1500),
+            ))
+            .unwrap();
+
+        (bank1, bank2)
+    }
+
+    #[test]
+    fn test_plan_merge_is_dry_run() {
+        let (bank1, bank2) = banks();
+        let policy = MergePolicy {
+            credit_lines: CreditLinePolicy::Max,
+            ..MergePolicy::default()
+        };
+
+        let plan = bank1.plan_merge(&bank2, &policy).unwrap();
+        assert_eq!(plan.users.len(), 2);
+        assert_eq!(plan.conflicts().count(), 1);
+
+        let alice = &plan.users[0];
+        assert_eq!(alice.action, MergeAction::Combine);
+        assert_eq!(alice.balance_before, Some(Money::from_minor(1000)));
+        assert_eq!(alice.balance_after, Money::from_minor(1500));
+        assert_eq!(alice.credit_line_after, Money::from_minor(1500));
+        assert_eq!(plan.users[1].action, MergeAction::Add);
+        assert_eq!(plan.credit_interest_after, 550);
+
+        // Nothing was touched.
+        assert_eq!(
+            bank1.user("Alice").unwrap().balance,
+            Money::from_minor(1000)
+        );
+        assert_eq!(bank1.users.len(), 2);
+        assert_eq!(bank1.ledger().len(), 2);
+
+        let text = plan.to_string();
+        assert!(text.contains("~ Alice: 10.00 -> 15.00 EUR (credit line 10.00 -> 15.00)"));
+        assert!(text.contains("+ Charlie: -15.00 EUR (credit line 15.00)"));
+
+        let mut merged = bank1;
+        assert_eq!(merged.merge_bank_with(bank2, &policy).unwrap(), plan);
+        assert_eq!(
+            merged.user("Alice").unwrap().credit_line,
+            Money::from_minor(1500)
+        );
+        assert!(merged.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_conflict_policies() {
+        let (mut bank1, bank2) = banks();
+        let policy = MergePolicy {
+            conflicts: ConflictPolicy::Fail,
+            ..MergePolicy::default()
+        };
+        assert_eq!(
+            bank1.merge_bank_with(bank2, &policy),
+            Err(BankError::MergeConflict("Alice".to_string()))
+        );
+        assert_eq!(bank1.users.len(), 2);
+        assert_eq!(bank1.credit_interest, 500);
+
+        let (mut bank1, bank2) = banks();
+        let policy = MergePolicy {
+            conflicts: ConflictPolicy::Rename {
+                prefix: "b2-".to_string(),
+            },
+            credit_lines: CreditLinePolicy::Sum,
+            interest: InterestPolicy::Keep,
+        };
+        bank1.merge_bank_with(bank2, &policy).unwrap();
+
+        assert_eq!(bank1.users.len(), 4);
+        assert_eq!(
+            bank1.user("Alice").unwrap().balance,
+            Money::from_minor(1000)
+        );
+        assert_eq!(
+            bank1.user("b2-Alice").unwrap().balance,
+            Money::from_minor(500)
+        );
+        assert_eq!(bank1.user("b2-Alice").unwrap().name, "b2-Alice");
+        assert_eq!(
+            bank1.user("Alice").unwrap().credit_line,
+            Money::from_minor(1000)
+        );
+        assert_eq!(bank1.credit_interest, 500);
+        assert!(bank1.reconcile().unwrap().is_empty());
+
+        // The prefixed name is itself taken.
+        let (mut bank1, bank2) = banks();
+        bank1
+            .add_user(User::new(
+                "b2-Alice".to_string(),
+                Money::from_minor(0),
+                Money::from_minor(0),
+            ))
+            .unwrap();
+        assert_eq!(
+            bank1.plan_merge(&bank2, &policy),
+            Err(BankError::MergeConflict("b2-Alice".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_merge_credit_lines_and_interest() {
+        let (bank1, bank2) = banks();
+
+        let plan = |credit_lines, interest| {
+            let policy = MergePolicy {
+                credit_lines,
+                interest,
+                ..MergePolicy::default()
+            };
+            bank1.plan_merge(&bank2, &policy).unwrap()
+        };
+
+        let min = plan(CreditLinePolicy::Min, InterestPolicy::Average);
+        assert_eq!(min.users[0].credit_line_after, Money::from_minor(1000));
+        let sum = plan(CreditLinePolicy::Sum, InterestPolicy::Average);
+        assert_eq!(sum.users[0].credit_line_after, Money::from_minor(2500));
+
+        // Overdrafts: 500 at 500bp against 1500 at 600bp.
+        // Deposits: 1000 at 300bp against 500 at 400bp.
+        let weighted = plan(CreditLinePolicy::Keep, InterestPolicy::WeightedByBalance);
+        assert_eq!(weighted.credit_interest_after, 575);
+        assert_eq!(weighted.debit_interest_after, 333);
+
+        // Without any balances to weigh it falls back to the plain average.
+        let empty1 = Bank::new("Empty 1".to_string(), 100, 0);
+        let empty2 = Bank::new("Empty 2".to_string(), 201, 0);
+        let policy = MergePolicy {
+            interest: InterestPolicy::WeightedByBalance,
+            ..MergePolicy::default()
+        };
+        let plan = empty1.plan_merge(&empty2, &policy).unwrap();
+        assert_eq!(plan.credit_interest_after, 150);
+    }
+
+    #[test]
+    fn test_merge_loans() {
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+        let loan = |account: &str, principal| {
+            Loan::new(account, principal, 0, 2, Amortization::Linear, start)
+        };
+        let lender = || {
+            let (_, mut bank2) = banks();
+            bank2
+                .grant_loan(loan("Charlie", Money::from_minor(3000)))
+                .unwrap();
+            bank2
+        };
+
+        let (mut bank1, _) = banks();
+        bank1
+            .grant_loan(loan("Alice", Money::from_minor(1000)))
+            .unwrap();
+        let entries = bank1.ledger().len();
+        bank1
+            .merge_bank_with(lender(), &MergePolicy::default())
+            .unwrap();
+        let charlie = bank1.user("Charlie").unwrap().clone();
+        assert_eq!(charlie.balance, Money::from_minor(1500));
+
+        // Two accounts merged in, then one entry for the loan.
+        let merged = &bank1.ledger().entries()[entries..];
+        assert_eq!(merged.len(), 3);
+        let receivable = &merged[2];
+        assert_eq!(receivable.kind, EntryKind::Merge);
+        assert_eq!(receivable.from.as_deref(), Some(charlie.id.as_str()));
+        assert_eq!(receivable.to.as_deref(), Some(charlie.id.as_str()));
+        assert_eq!(receivable.amount, Money::from_minor(3000));
+
+        assert_eq!(bank1.loans()[1].id, 2);
+        assert_eq!(bank1.loans()[1].account, charlie.id.as_str());
+        assert!(bank1.reconcile().unwrap().is_empty());
+        assert!(bank1.verify_books().is_ok());
+
+        // Receivables that don't fit are refused before anything changes.
+        let (mut bank1, _) = banks();
+        bank1.loans.push(loan("Alice", Money::MAX));
+        assert_eq!(
+            bank1.plan_merge(&lender(), &MergePolicy::default()),
+            Err(BankError::MergeOverflow("Charlie".to_string()))
+        );
+        assert_eq!(
+            bank1.merge_bank_with(lender(), &MergePolicy::default()),
+            Err(BankError::MergeOverflow("Charlie".to_string()))
+        );
+        assert_eq!(bank1.users.len(), 2);
+    }
+}
@@ -0,0 +1,248 @@
+use super::Currency;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Every currency the bank handles has two decimal places, so amounts are kept
+// as a whole number of cents (or fils).
+pub const MINOR_UNITS: u32 = 2;
+const SCALE: i64 = 10_i64.pow(MINOR_UNITS);
+
+// An exact amount of money in minor units. It serialises as the bare integer,
+// so stored banks keep their format.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+    pub const MAX: Money = Money(i64::MAX);
+    pub const MIN: Money = Money(i64::MIN);
+
+    pub const fn from_minor(minor: i64) -> Self {
+        Money(minor)
+    }
+
+    pub fn from_major(major: i64) -> Option<Self> {
+        major.checked_mul(SCALE).map(Money)
+    }
+
+    pub const fn minor(self) -> i64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+
+    pub fn checked_neg(self) -> Option<Money> {
+        self.0.checked_neg().map(Money)
+    }
+
+    // The most negative amount has no positive counterpart and saturates to
+    // `Money::MAX`.
+    pub fn abs(self) -> Money {
+        Money(self.0.saturating_abs())
+    }
+
+    // Formats with the currency's symbol and thousands separators, e.g.
+    // "-€1,234.56".
+    pub fn format(self, currency: Currency) -> String {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let amount = self.0.unsigned_abs();
+        let major = (amount / SCALE as u64).to_string();
+
+        let mut grouped = String::with_capacity(major.len() + major.len() / 3);
+        for (index, digit) in major.chars().enumerate() {
+            if index > 0 && (major.len() - index).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+
+        format!(
+            "{}{}{}.{:0width$}",
+            sign,
+            currency.symbol(),
+            grouped,
+            amount % SCALE as u64,
+            width = MINOR_UNITS as usize
+        )
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let amount = self.0.unsigned_abs();
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            amount / SCALE as u64,
+            amount % SCALE as u64,
+            width = MINOR_UNITS as usize
+        )
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &s[prefix.len()..])
+}
+
+fn strip_suffix_ignore_case<'a>(s: &'a str, suffix: &str) -> Option<&'a str> {
+    let start = s.len().checked_sub(suffix.len())?;
+    let tail = s.get(start..)?;
+    tail.eq_ignore_ascii_case(suffix).then(|| &s[..start])
+}
+
+// Drops a leading or trailing currency symbol or code.
+fn strip_currency(s: &str) -> &str {
+    for currency in [Currency::EUR, Currency::USD, Currency::AED] {
+        let code = currency.to_string();
+        for marker in [currency.symbol().trim_end(), code.as_str()] {
+            if let Some(rest) = strip_prefix_ignore_case(s, marker) {
+                return rest.trim_start();
+            }
+            if let Some(rest) = strip_suffix_ignore_case(s, marker) {
+                return rest.trim_end();
+            }
+        }
+    }
+    s
+}
+
+// Accepts amounts such as "1234.5", "1,234.56", "-€12", "$ 3.50" or
+// "12.00 AED". More decimals than the currency has are rejected rather than
+// rounded.
+impl FromStr for Money {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid amount '{}'", s);
+
+        let mut rest = s.trim();
+        let mut negative = false;
+        if let Some(stripped) = rest.strip_prefix('-') {
+            negative = true;
+            rest = stripped.trim_start();
+        }
+        rest = strip_currency(rest);
+        if !negative && let Some(stripped) = rest.strip_prefix('-') {
+            negative = true;
+            rest = stripped;
+        }
+
+        let (whole, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+        if whole.is_empty() || fraction.len() > MINOR_UNITS as usize {
+            return Err(invalid());
+        }
+
+        let groups: Vec<&str> = whole.split(',').collect();
+        let grouped_correctly = groups.len() == 1
+            || (!groups[0].is_empty()
+                && groups[0].len() <= 3
+                && groups[1..].iter().all(|group| group.len() == 3));
+        let digits = || {
+            groups
+                .iter()
+                .flat_map(|group| group.chars())
+                .chain(fraction.chars())
+        };
+        if !grouped_correctly || !digits().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let mut minor: i64 = 0;
+        let padding = MINOR_UNITS as usize - fraction.len();
+        for digit in digits().chain(std::iter::repeat_n('0', padding)) {
+            minor = minor
+                .checked_mul(10)
+                .and_then(|minor| minor.checked_add(i64::from(digit as u8 - b'0')))
+                .ok_or_else(invalid)?;
+        }
+
+        Ok(Money(if negative { -minor } else { minor }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_money() {
+        assert_eq!("1,234.56".parse(), Ok(Money::from_minor(123_456)));
+        assert_eq!("1234.5".parse(), Ok(Money::from_minor(123_450)));
+        assert_eq!("12".parse(), Ok(Money::from_minor(1_200)));
+        assert_eq!("-0.07".parse(), Ok(Money::from_minor(-7)));
+        assert_eq!("€1,000,000".parse(), Ok(Money::from_minor(100_000_000)));
+        assert_eq!("-$ 3.50".parse(), Ok(Money::from_minor(-350)));
+        assert_eq!("12.00 AED".parse(), Ok(Money::from_minor(1_200)));
+        assert_eq!("usd 5".parse(), Ok(Money::from_minor(500)));
+
+        for invalid in [
+            "", "1.234", "12,34", "1,2345", ",123", "1.2.3", "abc", "--5", "1e3",
+        ] {
+            assert!(invalid.parse::<Money>().is_err(), "{}", invalid);
+        }
+        assert!("92233720368547758.08".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn test_format_money() {
+        assert_eq!(Money::from_minor(123_456).to_string(), "1234.56");
+        assert_eq!(Money::from_minor(-5).to_string(), "-0.05");
+        assert_eq!(
+            Money::from_minor(i64::MIN).to_string(),
+            "-92233720368547758.08"
+        );
+
+        assert_eq!(
+            Money::from_minor(123_456).format(Currency::EUR),
+            "€1,234.56"
+        );
+        assert_eq!(
+            Money::from_minor(-100_000).format(Currency::USD),
+            "-$1,000.00"
+        );
+        assert_eq!(Money::from_minor(99).format(Currency::AED), "AED 0.99");
+
+        let amount = Money::from_minor(-1_234_567_890);
+        assert_eq!(amount.format(Currency::EUR).parse(), Ok(amount));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let amount = Money::from_major(10).unwrap();
+        assert_eq!(amount.minor(), 1_000);
+        assert_eq!(
+            amount.checked_sub(Money::from_minor(1_500)),
+            Some(Money::from_minor(-500))
+        );
+        assert_eq!(Money::MAX.checked_add(Money::from_minor(1)), None);
+        assert_eq!(Money::MIN.checked_neg(), None);
+        assert_eq!(Money::MIN.abs(), Money::MAX);
+        assert_eq!(Money::from_major(i64::MAX), None);
+    }
+}
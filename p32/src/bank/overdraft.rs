@@ -0,0 +1,308 @@
+use super::{Bank, BankError, Money, User};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// What happens when a debit would take an account past its credit line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum OverdraftPolicy {
+    // The credit line is the limit; anything past it is rejected.
+    #[default]
+    Hard,
+    Soft(SoftOverdraft),
+}
+
+// Lets an account go up to `limit` past its credit line. Every transfer that
+// leaves it more than `grace` past the line is charged `fee`, and
+// `accrue_interest` charges `penalty_rate` (in bp, on top of the usual
+// overdraft interest) on whatever is more than `grace` past the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SoftOverdraft {
+    pub limit: Money,
+    pub fee: OverdraftFee,
+    pub grace: Money,
+    pub penalty_rate: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverdraftFee {
+    Fixed(Money),
+    // In bp of the part of the transfer past the grace amount.
+    Percentage(u64),
+}
+
+impl Default for OverdraftFee {
+    fn default() -> Self {
+        OverdraftFee::Fixed(Money::ZERO)
+    }
+}
+
+impl fmt::Display for OverdraftPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverdraftPolicy::Hard => write!(f, "hard"),
+            OverdraftPolicy::Soft(soft) => {
+                write!(f, "soft up to {} past the credit line, fee ", soft.limit)?;
+                match soft.fee {
+                    OverdraftFee::Fixed(fee) => write!(f, "{}", fee)?,
+                    OverdraftFee::Percentage(rate) => write!(f, "{}bp", rate)?,
+                }
+                write!(f, ", grace {}, penalty {}bp", soft.grace, soft.penalty_rate)
+            }
+        }
+    }
+}
+
+impl OverdraftPolicy {
+    // How far past the credit line the balance may go.
+    pub fn limit(&self) -> Money {
+        match self {
+            OverdraftPolicy::Hard => Money::ZERO,
+            OverdraftPolicy::Soft(soft) => soft.limit,
+        }
+    }
+}
+
+impl User {
+    pub fn with_overdraft(mut self, overdraft: OverdraftPolicy) -> Self {
+        self.overdraft = overdraft;
+        self
+    }
+
+    // The lowest balance the account may reach.
+    pub(crate) fn debit_floor(&self) -> Money {
+        self.credit_line
+            .checked_add(self.overdraft.limit())
+            .and_then(Money::checked_neg)
+            .unwrap_or(Money::MIN)
+    }
+
+    // How far `balance` is past the credit line and the grace amount, the
+    // part that fees and penalty interest are charged on.
+    fn chargeable_overdraft(&self, balance: Money) -> Money {
+        let OverdraftPolicy::Soft(soft) = self.overdraft else {
+            return Money::ZERO;
+        };
+        let allowed = self
+            .credit_line
+            .checked_add(soft.grace)
+            .unwrap_or(Money::MAX);
+        balance
+            .checked_neg()
+            .and_then(|owed| owed.checked_sub(allowed))
+            .unwrap_or(Money::MAX)
+            .max(Money::ZERO)
+    }
+
+    // Takes `amount` out of `balance` within the credit line and overdraft
+    // limit, charging the overdraft fee if that goes past the grace amount.
+    // The fee has to fit within the limit as well. Returns the balance after
+    // both and the fee.
+    pub(crate) fn debit_with_fee(
+        &self,
+        balance: Money,
+        amount: Money,
+    ) -> Result<(Money, Money), BankError> {
+        let after = self.checked_debit(balance, amount)?;
+        let fee = self.overdraft_fee(amount, after)?;
+        if fee.is_zero() {
+            return Ok((after, fee));
+        }
+
+        let after_fee =
+            self.checked_debit(after, fee)
+                .map_err(|_| BankError::InsufficientCredit {
+                    user: self.name.clone(),
+                    amount,
+                })?;
+        Ok((after_fee, fee))
+    }
+
+    fn overdraft_fee(&self, amount: Money, balance: Money) -> Result<Money, BankError> {
+        let OverdraftPolicy::Soft(soft) = self.overdraft else {
+            return Ok(Money::ZERO);
+        };
+        let chargeable = self.chargeable_overdraft(balance);
+        if chargeable.is_zero() {
+            return Ok(Money::ZERO);
+        }
+
+        match soft.fee {
+            OverdraftFee::Fixed(fee) => Ok(fee),
+            OverdraftFee::Percentage(rate) => {
+                let fee = u128::from(chargeable.min(amount).minor().unsigned_abs())
+                    * u128::from(rate)
+                    / 10000;
+                i64::try_from(fee)
+                    .map(Money::from_minor)
+                    .map_err(|_| BankError::TransferOverflow(self.name.clone()))
+            }
+        }
+    }
+
+    // Penalty interest for one accrual period, as a negative amount.
+    pub(crate) fn overdraft_penalty(&self) -> Result<Money, BankError> {
+        let OverdraftPolicy::Soft(soft) = self.overdraft else {
+            return Ok(Money::ZERO);
+        };
+
+        let penalty = u128::from(
+            self.chargeable_overdraft(self.balance)
+                .minor()
+                .unsigned_abs(),
+        ) * u128::from(soft.penalty_rate)
+            / 10000;
+        i64::try_from(penalty)
+            .map(|penalty| Money::from_minor(-penalty))
+            .map_err(|_| BankError::InterestOverflow(self.name.clone()))
+    }
+}
+
+impl Bank {
+    pub fn set_overdraft_policy(
+        &mut self,
+        reference: &str,
+        overdraft: OverdraftPolicy,
+    ) -> Result<(), BankError> {
+        if let OverdraftPolicy::Soft(soft) = overdraft {
+            let fee = match soft.fee {
+                OverdraftFee::Fixed(fee) => fee,
+                OverdraftFee::Percentage(_) => Money::ZERO,
+            };
+            if let Some(amount) = [soft.limit, soft.grace, fee]
+                .into_iter()
+                .find(|amount| amount.is_negative())
+            {
+                return Err(BankError::NegativeAmount(amount));
+            }
+        }
+
+        let snapshot = self.operation_snapshot().with_account(self, reference);
+        self.user_mut(reference)?.overdraft = overdraft;
+        self.checkpoint(snapshot);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{EntryKind, Transfer};
+
+    fn soft(fee: OverdraftFee) -> OverdraftPolicy {
+        OverdraftPolicy::Soft(SoftOverdraft {
+            limit: Money::from_minor(5000),
+            fee,
+            grace: Money::from_minor(1000),
+            penalty_rate: 200,
+        })
+    }
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 1000, 0);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(10_000),
+            Money::from_minor(10_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new("Bob".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank
+    }
+
+    #[test]
+    fn test_hard_limit() {
+        let mut bank = bank();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(20_000))
+            .unwrap();
+        assert!(matches!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(1)),
+            Err(BankError::InsufficientCredit { .. })
+        ));
+    }
+
+    #[test]
+    fn test_soft_limit_with_fixed_fee() {
+        let mut bank = bank();
+        bank.set_overdraft_policy("Alice", soft(OverdraftFee::Fixed(Money::from_minor(300))))
+            .unwrap();
+
+        // Within the credit line and then within the grace amount: no fee.
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(20_000))
+            .unwrap();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(1000))
+            .unwrap();
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(-11_000)
+        );
+
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(1000))
+            .unwrap();
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(-12_300)
+        );
+        let entries = bank.ledger().entries();
+        let fee = &entries[entries.len() - 2];
+        assert_eq!(fee.kind, EntryKind::Fee);
+        assert_eq!(fee.amount, Money::from_minor(300));
+        assert_eq!(entries.last().unwrap().kind, EntryKind::Transfer);
+        assert!(bank.reconcile().unwrap().is_empty());
+
+        // The soft limit is 150.00 below zero, fee included.
+        assert!(matches!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(2500)),
+            Err(BankError::InsufficientCredit { .. })
+        ));
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(2400))
+            .unwrap();
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(-15_000)
+        );
+        assert!(bank.verify_books().is_ok());
+    }
+
+    #[test]
+    fn test_percentage_fee_and_penalty() {
+        let mut bank = bank();
+        bank.set_overdraft_policy("Alice", soft(OverdraftFee::Percentage(500)))
+            .unwrap();
+
+        // 30.00 of this is past the grace amount; 5% of that is 1.50.
+        bank.transfer_batch(&[
+            Transfer::new("Alice", "Bob", Money::from_minor(19_000)),
+            Transfer::new("Alice", "Bob", Money::from_minor(5000)),
+        ])
+        .unwrap();
+        let alice = bank.user("Alice").unwrap();
+        assert_eq!(alice.balance, Money::from_minor(-14_150));
+
+        // 10% overdraft interest plus 2% on the 31.50 past the grace amount.
+        bank.accrue_interest().unwrap();
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(-14_150 - 1415 - 63)
+        );
+
+        assert!(matches!(
+            bank.set_overdraft_policy(
+                "Alice",
+                OverdraftPolicy::Soft(SoftOverdraft {
+                    limit: Money::from_minor(-1),
+                    ..SoftOverdraft::default()
+                })
+            ),
+            Err(BankError::NegativeAmount(_))
+        ));
+        bank.set_overdraft_policy("Alice", OverdraftPolicy::Hard)
+            .unwrap();
+        assert!(
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(1))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    Truncate,
+    #[default]
+    HalfEven,
+    HalfUp,
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rounding = match self {
+            Rounding::Truncate => "truncate",
+            Rounding::HalfEven => "half-even",
+            Rounding::HalfUp => "half-up",
+        };
+        write!(f, "{}", rounding)
+    }
+}
+
+impl Rounding {
+    // Divides `numerator` by a positive `denominator`. Truncation goes toward
+    // zero and "half up" rounds ties away from zero, so debits and credits of
+    // the same magnitude round symmetrically.
+    pub fn divide(self, numerator: i128, denominator: i128) -> i128 {
+        assert!(denominator > 0, "Rounding denominator must be positive");
+
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        if remainder == 0 {
+            return quotient;
+        }
+
+        let away_from_zero = if numerator < 0 { -1 } else { 1 };
+        let twice_remainder = remainder.abs() * 2;
+
+        match self {
+            Rounding::Truncate => quotient,
+            Rounding::HalfUp => {
+                if twice_remainder >= denominator {
+                    quotient + away_from_zero
+                } else {
+                    quotient
+                }
+            }
+            Rounding::HalfEven => match twice_remainder.cmp(&denominator) {
+                std::cmp::Ordering::Greater => quotient + away_from_zero,
+                std::cmp::Ordering::Less => quotient,
+                std::cmp::Ordering::Equal if quotient % 2 == 0 => quotient,
+                std::cmp::Ordering::Equal => quotient + away_from_zero,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divide() {
+        let cases = [
+            // (numerator, truncate, half-even, half-up) over a denominator of 10
+            (25, 2, 2, 3),
+            (35, 3, 4, 4),
+            (26, 2, 3, 3),
+            (24, 2, 2, 2),
+            (-25, -2, -2, -3),
+            (-26, -2, -3, -3),
+            (30, 3, 3, 3),
+        ];
+
+        for (numerator, truncate, half_even, half_up) in cases {
+            assert_eq!(Rounding::Truncate.divide(numerator, 10), truncate);
+            assert_eq!(Rounding::HalfEven.divide(numerator, 10), half_even);
+            assert_eq!(Rounding::HalfUp.divide(numerator, 10), half_up);
+        }
+    }
+}
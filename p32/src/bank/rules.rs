@@ -0,0 +1,501 @@
+use super::{Bank, BankError, EntryKind, Ledger, Money, User};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::fmt;
+
+// Everything a rule gets to look at before a transfer goes through. Past
+// activity comes from the ledger, so rules don't need state of their own.
+pub struct TransferCheck<'a> {
+    pub from: &'a User,
+    pub to: &'a User,
+    // In the sender's currency.
+    pub amount: Money,
+    pub at: DateTime<Utc>,
+    pub ledger: &'a Ledger,
+    // Amounts the sender has sent earlier in the same batch, at `at`, which
+    // aren't in the ledger yet.
+    pub pending: &'a [Money],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RejectionReason {
+    DailyLimitExceeded {
+        limit: Money,
+        spent: Money,
+        amount: Money,
+    },
+    AmountAboveMaximum {
+        maximum: Money,
+        amount: Money,
+    },
+    VelocityExceeded {
+        max_transfers: usize,
+        window: Duration,
+    },
+    BlockedCounterparty(String),
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub rule: String,
+    pub reason: RejectionReason,
+}
+
+// A check run before every transfer, on top of the credit line. Rules are
+// consulted in the order they were added and the first rejection wins.
+pub trait TransferRule: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason>;
+}
+
+// Caps what a user can send within one UTC calendar day.
+pub struct DailyLimit {
+    pub limit: Money,
+}
+
+pub struct MaxTransferAmount {
+    pub maximum: Money,
+}
+
+// Allows at most `max_transfers` outgoing transfers per user within any
+// sliding `window`.
+pub struct Velocity {
+    pub max_transfers: usize,
+    pub window: Duration,
+}
+
+// Refuses transfers from or to any of the listed account ids or names.
+#[derive(Default)]
+pub struct Blocklist {
+    names: HashSet<String>,
+}
+
+pub struct CustomRule<F> {
+    name: String,
+    check: F,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::DailyLimitExceeded {
+                limit,
+                spent,
+                amount,
+            } => write!(
+                f,
+                "{} would exceed the daily limit of {} ({} already sent today)",
+                amount, limit, spent
+            ),
+            RejectionReason::AmountAboveMaximum { maximum, amount } => {
+                write!(f, "{} is above the maximum of {}", amount, maximum)
+            }
+            RejectionReason::VelocityExceeded {
+                max_transfers,
+                window,
+            } => write!(
+                f,
+                "more than {} transfers within {} minutes",
+                max_transfers,
+                window.num_minutes()
+            ),
+            RejectionReason::BlockedCounterparty(name) => write!(f, "'{}' is blocked", name),
+            RejectionReason::Custom(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.reason)
+    }
+}
+
+impl TransferCheck<'_> {
+    // Transfers the sender made at or after `since`, up to the check's time,
+    // including the ones pending in the same batch. Undone transfers don't
+    // count.
+    pub fn outgoing_since(&self, since: DateTime<Utc>) -> impl Iterator<Item = Money> + '_ {
+        self.ledger
+            .entries()
+            .iter()
+            .filter(move |entry| {
+                entry.kind == EntryKind::Transfer
+                    && !self.ledger.is_reversed(entry.id)
+                    && entry.from.as_deref() == Some(self.from.id.as_str())
+                    && entry.timestamp >= since
+                    && entry.timestamp <= self.at
+            })
+            .map(|entry| entry.amount)
+            .chain(self.pending.iter().copied())
+    }
+}
+
+impl TransferRule for DailyLimit {
+    fn name(&self) -> &str {
+        "daily-limit"
+    }
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason> {
+        let midnight = transfer
+            .at
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .expect("Midnight is a valid time")
+            .and_utc();
+        let spent = transfer
+            .outgoing_since(midnight)
+            .try_fold(Money::ZERO, Money::checked_add)
+            .unwrap_or(Money::MAX);
+
+        match spent.checked_add(transfer.amount) {
+            Some(total) if total <= self.limit => Ok(()),
+            _ => Err(RejectionReason::DailyLimitExceeded {
+                limit: self.limit,
+                spent,
+                amount: transfer.amount,
+            }),
+        }
+    }
+}
+
+impl TransferRule for MaxTransferAmount {
+    fn name(&self) -> &str {
+        "max-amount"
+    }
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason> {
+        if transfer.amount > self.maximum {
+            return Err(RejectionReason::AmountAboveMaximum {
+                maximum: self.maximum,
+                amount: transfer.amount,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl TransferRule for Velocity {
+    fn name(&self) -> &str {
+        "velocity"
+    }
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason> {
+        let since = transfer.at - self.window;
+        if transfer.outgoing_since(since).count() >= self.max_transfers {
+            return Err(RejectionReason::VelocityExceeded {
+                max_transfers: self.max_transfers,
+                window: self.window,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Blocklist {
+    pub fn new<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Blocklist {
+            names: names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn block(&mut self, name: &str) {
+        self.names.insert(name.to_string());
+    }
+}
+
+impl TransferRule for Blocklist {
+    fn name(&self) -> &str {
+        "blocklist"
+    }
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason> {
+        for user in [transfer.from, transfer.to] {
+            if self.names.contains(&user.name) || self.names.contains(user.id.as_str()) {
+                return Err(RejectionReason::BlockedCounterparty(user.name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F> CustomRule<F>
+where
+    F: Fn(&TransferCheck<'_>) -> Result<(), RejectionReason> + Send + Sync,
+{
+    pub fn new(name: &str, check: F) -> Self {
+        CustomRule {
+            name: name.to_string(),
+            check,
+        }
+    }
+}
+
+impl<F> TransferRule for CustomRule<F>
+where
+    F: Fn(&TransferCheck<'_>) -> Result<(), RejectionReason> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, transfer: &TransferCheck<'_>) -> Result<(), RejectionReason> {
+        (self.check)(transfer)
+    }
+}
+
+pub(crate) fn check_rules(
+    rules: &[Box<dyn TransferRule>],
+    transfer: &TransferCheck<'_>,
+) -> Result<(), BankError> {
+    for rule in rules {
+        rule.check(transfer).map_err(|reason| {
+            BankError::TransferRejected(Rejection {
+                rule: rule.name().to_string(),
+                reason,
+            })
+        })?;
+    }
+    Ok(())
+}
+
+impl Bank {
+    pub fn add_rule(&mut self, rule: impl TransferRule + 'static) {
+        self.rules.push(Box::new(rule));
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name())
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> bool {
+        let count = self.rules.len();
+        self.rules.retain(|rule| rule.name() != name);
+        self.rules.len() != count
+    }
+
+    // Takes account ids; unknown ones are left for the transfer itself to
+    // report. `pending` holds what the sender has sent earlier in the same
+    // batch.
+    pub(crate) fn check_transfer_rules(
+        &self,
+        from: &str,
+        to: &str,
+        amount: Money,
+        at: DateTime<Utc>,
+        pending: &[Money],
+    ) -> Result<(), BankError> {
+        let (Some(from), Some(to)) = (self.users.get(from), self.users.get(to)) else {
+            return Ok(());
+        };
+
+        check_rules(
+            &self.rules,
+            &TransferCheck {
+                from,
+                to,
+                amount,
+                at,
+                ledger: &self.ledger,
+                pending,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Transfer;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        for name in ["Alice", "Bob", "Mallory"] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::ZERO,
+                Money::from_minor(100_000),
+            ))
+            .unwrap();
+        }
+        bank
+    }
+
+    fn rejection(bank: &mut Bank, from: &str, to: &str, amount: i64) -> Option<Rejection> {
+        match bank.transfer_funds(from, to, Money::from_minor(amount)) {
+            Ok(()) => None,
+            Err(BankError::TransferRejected(rejection)) => Some(rejection),
+            Err(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    fn batch_rejection(bank: &mut Bank, transfers: &[(&str, &str, i64)]) -> (usize, Rejection) {
+        let transfers: Vec<Transfer> = transfers
+            .iter()
+            .map(|&(from, to, amount)| Transfer::new(from, to, Money::from_minor(amount)))
+            .collect();
+        match bank.transfer_batch(&transfers) {
+            Err(BankError::BatchTransferFailed { index, error }) => match *error {
+                BankError::TransferRejected(rejection) => (index, rejection),
+                error => panic!("unexpected error: {}", error),
+            },
+            result => panic!("expected a rejection, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let mut bank = bank();
+        bank.add_rule(MaxTransferAmount {
+            maximum: Money::from_minor(5_000),
+        });
+        bank.add_rule(DailyLimit {
+            limit: Money::from_minor(8_000),
+        });
+        bank.add_rule(Blocklist::new(["Mallory"]));
+
+        assert_eq!(
+            rejection(&mut bank, "Alice", "Bob", 6_000),
+            Some(Rejection {
+                rule: "max-amount".to_string(),
+                reason: RejectionReason::AmountAboveMaximum {
+                    maximum: Money::from_minor(5_000),
+                    amount: Money::from_minor(6_000)
+                }
+            })
+        );
+
+        assert_eq!(rejection(&mut bank, "Alice", "Bob", 5_000), None);
+        assert_eq!(
+            rejection(&mut bank, "Alice", "Bob", 4_000).map(|rejection| rejection.reason),
+            Some(RejectionReason::DailyLimitExceeded {
+                limit: Money::from_minor(8_000),
+                spent: Money::from_minor(5_000),
+                amount: Money::from_minor(4_000)
+            })
+        );
+        assert_eq!(rejection(&mut bank, "Alice", "Bob", 3_000), None);
+        // Bob's limit is separate from Alice's.
+        assert_eq!(rejection(&mut bank, "Bob", "Alice", 3_000), None);
+
+        assert_eq!(
+            rejection(&mut bank, "Bob", "Mallory", 1).map(|rejection| rejection.reason),
+            Some(RejectionReason::BlockedCounterparty("Mallory".to_string()))
+        );
+        assert_eq!(
+            bank.user("Mallory").unwrap().balance,
+            Money::from_minor(100_000)
+        );
+
+        assert!(bank.remove_rule("blocklist"));
+        assert!(!bank.remove_rule("blocklist"));
+        assert_eq!(rejection(&mut bank, "Bob", "Mallory", 1), None);
+        assert_eq!(
+            bank.rules().collect::<Vec<_>>(),
+            vec!["max-amount", "daily-limit"]
+        );
+    }
+
+    #[test]
+    fn test_velocity_and_custom_rules() {
+        let mut bank = bank();
+        bank.add_rule(Velocity {
+            max_transfers: 2,
+            window: Duration::minutes(10),
+        });
+        bank.add_rule(CustomRule::new("same-currency", |transfer| {
+            if transfer.from.currency != transfer.to.currency {
+                return Err(RejectionReason::Custom(
+                    "cross-currency transfers need approval".to_string(),
+                ));
+            }
+            Ok(())
+        }));
+
+        assert_eq!(rejection(&mut bank, "Alice", "Bob", 100), None);
+        assert_eq!(rejection(&mut bank, "Alice", "Bob", 100), None);
+        let rejected = rejection(&mut bank, "Alice", "Bob", 100).unwrap();
+        assert_eq!(rejected.rule, "velocity");
+        assert_eq!(
+            BankError::TransferRejected(rejected).to_string(),
+            "Transfer rejected by velocity: more than 2 transfers within 10 minutes"
+        );
+
+        bank.add_user(
+            User::new("Dave".to_string(), Money::ZERO, Money::ZERO)
+                .with_currency(crate::bank::Currency::USD),
+        )
+        .unwrap();
+        assert_eq!(
+            rejection(&mut bank, "Bob", "Dave", 100),
+            Some(Rejection {
+                rule: "same-currency".to_string(),
+                reason: RejectionReason::Custom(
+                    "cross-currency transfers need approval".to_string()
+                )
+            })
+        );
+    }
+
+    #[test]
+    fn test_batch_daily_limit() {
+        let mut bank = bank();
+        bank.add_rule(DailyLimit {
+            limit: Money::from_minor(8_000),
+        });
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(2_000))
+            .unwrap();
+
+        let (index, rejection) = batch_rejection(
+            &mut bank,
+            &[
+                ("Alice", "Bob", 3_000),
+                ("Bob", "Alice", 5_000),
+                ("Alice", "Mallory", 4_000),
+            ],
+        );
+        assert_eq!(index, 2);
+        assert_eq!(
+            rejection.reason,
+            RejectionReason::DailyLimitExceeded {
+                limit: Money::from_minor(8_000),
+                spent: Money::from_minor(5_000),
+                amount: Money::from_minor(4_000)
+            }
+        );
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(98_000)
+        );
+    }
+
+    #[test]
+    fn test_batch_velocity() {
+        let mut bank = bank();
+        bank.add_rule(Velocity {
+            max_transfers: 2,
+            window: Duration::minutes(10),
+        });
+        let (index, rejection) = batch_rejection(
+            &mut bank,
+            &[
+                ("Alice", "Bob", 100),
+                ("Bob", "Alice", 100),
+                ("Alice", "Mallory", 100),
+                ("Alice", "Bob", 100),
+            ],
+        );
+        assert_eq!(index, 3);
+        assert_eq!(rejection.rule, "velocity");
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(100_000)
+        );
+    }
+}
@@ -0,0 +1,509 @@
+use super::{
+    Bank, BankError, Currency, EntryKind, ExchangeRates, InterestConfig, LedgerEntry, Loan, Money,
+    Rounding, StandingOrder, User,
+};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+
+// How many operations `Bank::undo` can go back by default.
+pub const DEFAULT_UNDO_LIMIT: usize = 50;
+
+// The state of a bank at one point. Balances aren't copied: every change to
+// them is in the ledger, so restoring reverses the entries recorded since.
+// A full snapshot copies everything else. One taken for an operation only
+// copies what that operation changes besides balances, so taking it doesn't
+// cost more than the operation itself.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    // Accounts as they were, with their interest carry.
+    accounts: HashMap<String, (User, Option<i64>)>,
+    // Whether `accounts` holds every account; restoring then drops any
+    // others.
+    full: bool,
+    name: String,
+    credit_interest: u64,
+    debit_interest: u64,
+    currency: Currency,
+    exchange_rates: ExchangeRates,
+    fx_rounding: Rounding,
+    interest_config: InterestConfig,
+    standing_orders: Option<Vec<StandingOrder>>,
+    loans: Option<Vec<Loan>>,
+    ledger_len: usize,
+    // How many times the bank had been restored to an earlier point of the
+    // ledger when this was taken.
+    rewinds: usize,
+}
+
+// Snapshots taken before each operation, newest last. Like rules and event
+// handlers, the history isn't saved with the bank or carried over by a merge.
+// A `ConcurrentBank` keeps it for when it is converted back.
+#[derive(Debug)]
+pub(crate) struct History {
+    undo: VecDeque<Snapshot>,
+    limit: usize,
+    // The ledger length each restore reversed entries back to, in order.
+    rewinds: Vec<usize>,
+}
+
+impl Snapshot {
+    // Id of the last ledger entry included in the snapshot, 0 if none.
+    pub fn entry_id(&self) -> u64 {
+        self.ledger_len as u64
+    }
+
+    pub(crate) fn with_account(mut self, bank: &Bank, reference: &str) -> Self {
+        if let Ok(id) = bank.account_id(reference) {
+            let carry = bank.interest_carry.get(id.as_str()).copied();
+            self.accounts
+                .insert(id.to_string(), (bank.users[id.as_str()].clone(), carry));
+        }
+        self
+    }
+
+    pub(crate) fn with_standing_orders(mut self, bank: &Bank) -> Self {
+        self.standing_orders = Some(bank.standing_orders.clone());
+        self
+    }
+
+    pub(crate) fn with_loans(mut self, bank: &Bank) -> Self {
+        self.loans = Some(bank.loans.clone());
+        self
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo: VecDeque::new(),
+            limit: DEFAULT_UNDO_LIMIT,
+            rewinds: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    // A snapshot can be restored as long as the bank hasn't been restored
+    // past it since it was taken.
+    fn can_restore(&self, snapshot: &Snapshot, ledger_len: usize) -> bool {
+        snapshot.ledger_len <= ledger_len
+            && self
+                .rewinds
+                .get(snapshot.rewinds..)
+                .is_some_and(|rewinds| rewinds.iter().all(|len| *len >= snapshot.ledger_len))
+    }
+}
+
+impl Bank {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = self.operation_snapshot();
+        snapshot.accounts = self
+            .users
+            .iter()
+            .map(|(id, user)| {
+                let carry = self.interest_carry.get(id).copied();
+                (id.clone(), (user.clone(), carry))
+            })
+            .collect();
+        snapshot.full = true;
+        snapshot.with_standing_orders(self).with_loans(self)
+    }
+
+    // What every operation needs to be undone: the bank's settings and how
+    // far the ledger went. Accounts, standing orders and loans that the
+    // operation changes other than through the ledger are added with the
+    // `with_*` methods.
+    pub(crate) fn operation_snapshot(&self) -> Snapshot {
+        Snapshot {
+            accounts: HashMap::new(),
+            full: false,
+            name: self.name.clone(),
+            credit_interest: self.credit_interest,
+            debit_interest: self.debit_interest,
+            currency: self.currency,
+            exchange_rates: self.exchange_rates.clone(),
+            fx_rounding: self.fx_rounding,
+            interest_config: self.interest_config.clone(),
+            standing_orders: None,
+            loans: None,
+            ledger_len: self.ledger.len(),
+            rewinds: self.history.rewinds.len(),
+        }
+    }
+
+    // Puts the bank back the way it was when `snapshot` was taken. Every
+    // ledger entry recorded since is reversed by a new one, and accounts
+    // opened since are removed. Fails with `SnapshotUnavailable` if the bank
+    // was already restored past the snapshot by an earlier restore or undo.
+    // Operations that can no longer be undone afterwards are forgotten.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), BankError> {
+        if !self.history.can_restore(snapshot, self.ledger.len()) {
+            return Err(BankError::SnapshotUnavailable(snapshot.entry_id()));
+        }
+
+        let ledger_len = self.ledger.len();
+        let users = &self.users;
+        let balances = self.ledger.reverse_since(snapshot.ledger_len, |id| {
+            users.get(id).map_or(Money::ZERO, |user| user.balance)
+        })?;
+        for (id, balance) in balances {
+            if let Some(user) = self.users.get_mut(&id) {
+                user.balance = balance;
+            }
+        }
+        if self.ledger.len() > ledger_len {
+            self.history.rewinds.push(snapshot.ledger_len);
+        }
+
+        let snapshot = snapshot.clone();
+        // Accounts whose opening has been undone didn't exist yet.
+        for entry in &self.ledger.entries()[snapshot.ledger_len..] {
+            if entry.kind == EntryKind::Opening
+                && let Some(id) = entry.to.as_ref().or(entry.from.as_ref())
+            {
+                self.users.remove(id);
+                self.interest_carry.remove(id);
+            }
+        }
+        if snapshot.full {
+            self.users
+                .retain(|id, _| snapshot.accounts.contains_key(id));
+            self.interest_carry
+                .retain(|id, _| snapshot.accounts.contains_key(id));
+        }
+        for (id, (user, carry)) in snapshot.accounts {
+            match carry {
+                Some(carry) => self.interest_carry.insert(id.clone(), carry),
+                None => self.interest_carry.remove(&id),
+            };
+            self.users.insert(id, user);
+        }
+
+        self.name = snapshot.name;
+        self.credit_interest = snapshot.credit_interest;
+        self.debit_interest = snapshot.debit_interest;
+        self.currency = snapshot.currency;
+        self.exchange_rates = snapshot.exchange_rates;
+        self.fx_rounding = snapshot.fx_rounding;
+        self.interest_config = snapshot.interest_config;
+        if let Some(standing_orders) = snapshot.standing_orders {
+            self.standing_orders = standing_orders;
+        }
+        if let Some(loans) = snapshot.loans {
+            self.loans = loans;
+        }
+
+        let ledger_len = self.ledger.len();
+        let history = &mut self.history;
+        let undo = std::mem::take(&mut history.undo);
+        history.undo = undo
+            .into_iter()
+            .filter(|snapshot| history.can_restore(snapshot, ledger_len))
+            .collect();
+        Ok(())
+    }
+
+    // Reverts the last `count` operations, such as transfers, interest runs,
+    // merges or loan payments, newest first. Changes made directly through
+    // `users` or `user_mut` aren't operations; they are only reverted when
+    // an operation that changed the same account is. Nothing is undone
+    // unless all `count` can be.
+    pub fn undo(&mut self, count: usize) -> Result<(), BankError> {
+        let available = self.history.undo.len();
+        if count > available {
+            return Err(BankError::NothingToUndo {
+                requested: count,
+                available,
+            });
+        }
+
+        for _ in 0..count {
+            let snapshot = self
+                .history
+                .undo
+                .pop_back()
+                .expect("Counted snapshot exists");
+            self.restore(&snapshot)?;
+        }
+        Ok(())
+    }
+
+    // How many operations `undo` can currently go back.
+    pub fn undo_depth(&self) -> usize {
+        self.history.undo.len()
+    }
+
+    // Keeps at most `limit` operations for `undo`, forgetting the oldest.
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.history.limit = limit;
+        self.trim_history();
+    }
+
+    // Called by each operation once it has succeeded, with the snapshot it
+    // took before changing anything.
+    pub(crate) fn checkpoint(&mut self, snapshot: Snapshot) {
+        self.history.undo.push_back(snapshot);
+        self.trim_history();
+    }
+
+    fn trim_history(&mut self) {
+        while self.history.undo.len() > self.history.limit {
+            self.history.undo.pop_front();
+        }
+    }
+
+    // Balance of an account right after ledger entry `entry_id`, or `None`
+    // if the account hadn't been opened by then.
+    pub fn balance_at(&self, reference: &str, entry_id: u64) -> Result<Option<Money>, BankError> {
+        self.balance_where(reference, |entry| entry.id <= entry_id)
+    }
+
+    // Balance of an account at `timestamp`, counting every entry dated at or
+    // before it. Entries for past dates, like standing order payments and
+    // loan installments, count from their date rather than from when they
+    // were recorded.
+    pub fn balance_at_time(
+        &self,
+        reference: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<Money>, BankError> {
+        self.balance_where(reference, |entry| entry.timestamp <= timestamp)
+    }
+
+    fn balance_where(
+        &self,
+        reference: &str,
+        include: impl Fn(&LedgerEntry) -> bool,
+    ) -> Result<Option<Money>, BankError> {
+        let id = self.account_id(reference)?;
+        let mut balance = None;
+
+        for entry in self
+            .ledger
+            .entries_for(id.as_str())
+            .filter(|entry| include(entry))
+        {
+            let mut total = balance.unwrap_or(Money::ZERO);
+            if entry.from.as_deref() == Some(id.as_str()) {
+                total = total
+                    .checked_sub(entry.amount)
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+            }
+            if entry.to.as_deref() == Some(id.as_str()) {
+                total = total
+                    .checked_add(entry.credited_amount())
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+            }
+            balance = Some(total);
+        }
+
+        Ok(balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Amortization, Transfer};
+    use chrono::{Duration, NaiveDate};
+
+    fn bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 1000);
+        for (name, balance) in [("Alice", 1000), ("Bob", 0)] {
+            bank.add_user(User::new(
+                name.to_string(),
+                Money::from_minor(500),
+                Money::from_minor(balance),
+            ))
+            .unwrap();
+        }
+        bank
+    }
+
+    fn balances(bank: &Bank) -> Vec<(String, Money)> {
+        let mut balances: Vec<(String, Money)> = bank
+            .users
+            .values()
+            .map(|user| (user.name.clone(), user.balance))
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    #[test]
+    fn test_restore_snapshot() {
+        let mut bank = bank();
+        let snapshot = bank.snapshot();
+        let before = balances(&bank);
+
+        bank.transfer_batch(&[
+            Transfer::new("Alice", "Bob", Money::from_minor(300)),
+            Transfer::new("Bob", "Alice", Money::from_minor(100)),
+        ])
+        .unwrap();
+        bank.open_account(User::new("Carol".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.grant_loan(Loan::new(
+            "Bob",
+            Money::from_minor(1200),
+            0,
+            12,
+            Amortization::Linear,
+            NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(bank.ledger().len(), 6);
+
+        bank.restore(&snapshot).unwrap();
+        assert_eq!(balances(&bank), before);
+        assert!(bank.user("Carol").is_err());
+        assert!(bank.loans().is_empty());
+        assert!(bank.reconcile().unwrap().is_empty());
+        assert!(bank.verify_books().is_ok());
+        // Opening both accounts can still be undone; what came after can't.
+        assert_eq!(bank.undo_depth(), 3);
+
+        // Nothing is removed from the ledger: each entry since the snapshot
+        // is reversed by a new one, newest first.
+        assert_eq!(bank.ledger().len(), 10);
+        let reversed: Vec<Option<u64>> = bank.ledger().entries()[6..]
+            .iter()
+            .map(|entry| {
+                assert_eq!(entry.kind, EntryKind::Reversal);
+                entry.reverses
+            })
+            .collect();
+        assert_eq!(reversed, [Some(6), Some(5), Some(4), Some(3)]);
+        let transfer = &bank.ledger().entries()[8];
+        assert_eq!(
+            transfer.from.as_deref(),
+            Some(bank.account_id("Alice").unwrap().as_str())
+        );
+        assert_eq!(transfer.amount, Money::from_minor(100));
+
+        // Neither ledger ids nor Carol's account id are handed out again.
+        let id = bank
+            .open_account(User::new("Dave".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        assert_eq!(id.sequence(), Some(4));
+        assert_eq!(bank.ledger().entries()[10].id, 11);
+    }
+
+    #[test]
+    fn test_undo_operations() {
+        let mut bank = bank();
+        bank.set_undo_limit(3);
+        let start = balances(&bank);
+
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(200))
+            .unwrap();
+        let after_transfer = balances(&bank);
+        bank.accrue_interest().unwrap();
+        bank.freeze_account("Bob").unwrap();
+        // Failed operations aren't recorded.
+        bank.transfer_funds("Bob", "Alice", Money::from_minor(1))
+            .unwrap_err();
+        assert_eq!(bank.undo_depth(), 3);
+
+        assert_eq!(
+            bank.undo(4),
+            Err(BankError::NothingToUndo {
+                requested: 4,
+                available: 3
+            })
+        );
+        bank.undo(2).unwrap();
+        assert_eq!(balances(&bank), after_transfer);
+        assert!(bank.user("Bob").unwrap().is_active());
+        // Both interest entries are reversed.
+        assert_eq!(bank.ledger().len(), 7);
+        assert!(bank.ledger().is_reversed(4) && bank.ledger().is_reversed(5));
+
+        bank.undo(1).unwrap();
+        assert_eq!(balances(&bank), start);
+        assert!(bank.undo(1).is_err());
+
+        // Anything older than the limit is forgotten.
+        for _ in 0..5 {
+            bank.transfer_funds("Alice", "Bob", Money::from_minor(10))
+                .unwrap();
+        }
+        assert_eq!(bank.undo_depth(), 3);
+        bank.undo(3).unwrap();
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(20));
+    }
+
+    #[test]
+    fn test_stale_snapshots() {
+        let mut bank = bank();
+        let start = bank.snapshot();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(200))
+            .unwrap();
+        let later = bank.snapshot();
+
+        bank.restore(&start).unwrap();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(500))
+            .unwrap();
+        // The transfer `later` was taken after has been reversed since.
+        assert!(bank.ledger().is_reversed(later.entry_id()));
+        assert_eq!(bank.restore(&later), Err(BankError::SnapshotUnavailable(3)));
+
+        bank.restore(&start).unwrap();
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::ZERO);
+    }
+
+    #[test]
+    fn test_balance_at() {
+        let mut bank = bank();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+            .unwrap();
+        bank.accrue_interest().unwrap();
+
+        assert_eq!(bank.balance_at("Bob", 1).unwrap(), None);
+        assert_eq!(bank.balance_at("Bob", 2).unwrap(), Some(Money::ZERO));
+        assert_eq!(
+            bank.balance_at("Bob", 3).unwrap(),
+            Some(Money::from_minor(300))
+        );
+        assert_eq!(
+            bank.balance_at("Alice", 3).unwrap(),
+            Some(Money::from_minor(700))
+        );
+        assert_eq!(
+            bank.balance_at("Alice", 99).unwrap(),
+            Some(bank.user("Alice").unwrap().balance)
+        );
+        assert!(bank.balance_at("Carol", 1).is_err());
+
+        // A payment dated in the past counts from its own date.
+        bank.add_standing_order(StandingOrder::new(
+            "Bob",
+            "Alice",
+            Money::from_minor(30),
+            crate::bank::Schedule::Once,
+            NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+        ))
+        .unwrap();
+        bank.run_until(NaiveDate::from_ymd_opt(2020, 6, 1).unwrap());
+        let date = |day| {
+            NaiveDate::from_ymd_opt(2020, 6, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        assert_eq!(
+            bank.balance_at_time("Bob", date(1) - Duration::seconds(1))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            bank.balance_at_time("Bob", date(2)).unwrap(),
+            Some(Money::from_minor(-30))
+        );
+        assert_eq!(
+            bank.balance_at_time("Bob", Utc::now()).unwrap(),
+            Some(bank.user("Bob").unwrap().balance)
+        );
+    }
+}
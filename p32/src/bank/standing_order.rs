@@ -0,0 +1,600 @@
+use super::{Bank, BankError, BankEvent, Money};
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Schedule {
+    // A single payment on the order's start date.
+    Once,
+    Daily,
+    Weekly,
+    // On the given day of every month, or the month's last day when it is
+    // shorter (so the 31st pays on 30 April and 28 February).
+    MonthlyOn(u32),
+    EndOfMonth,
+}
+
+// Failed payments are tried again `interval_days` later, at most
+// `max_retries` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub interval_days: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRetry {
+    pub due: NaiveDate,
+    pub attempt: u32,
+    pub retry_on: NaiveDate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StandingOrder {
+    pub id: u64,
+    // Account ids or names; names are resolved to ids when the order is
+    // added.
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+    pub schedule: Schedule,
+    pub start: NaiveDate,
+    pub end: Option<NaiveDate>,
+    pub retry: RetryPolicy,
+    pub(crate) next_due: Option<NaiveDate>,
+    pub(crate) retries: Vec<PendingRetry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    Paid {
+        entry_id: u64,
+    },
+    Failed {
+        error: BankError,
+        retry_on: Option<NaiveDate>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentRecord {
+    pub order_id: u64,
+    pub due: NaiveDate,
+    pub executed_on: NaiveDate,
+    pub attempt: u32,
+    pub outcome: PaymentOutcome,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            interval_days: 1,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Once => write!(f, "once"),
+            Schedule::Daily => write!(f, "daily"),
+            Schedule::Weekly => write!(f, "weekly"),
+            Schedule::MonthlyOn(day) => write!(f, "monthly:{}", day),
+            Schedule::EndOfMonth => write!(f, "end-of-month"),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "once" => Ok(Schedule::Once),
+            "daily" => Ok(Schedule::Daily),
+            "weekly" => Ok(Schedule::Weekly),
+            "end-of-month" => Ok(Schedule::EndOfMonth),
+            other => other
+                .strip_prefix("monthly:")
+                .and_then(|day| day.parse().ok())
+                .map(Schedule::MonthlyOn)
+                .ok_or_else(|| format!("Unknown schedule '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for PaymentRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Order #{} due {} (attempt {} on {}): ",
+            self.order_id, self.due, self.attempt, self.executed_on
+        )?;
+        match &self.outcome {
+            PaymentOutcome::Paid { entry_id } => write!(f, "paid, ledger entry #{}", entry_id),
+            PaymentOutcome::Failed {
+                error,
+                retry_on: Some(retry_on),
+            } => write!(f, "failed: {}; retrying on {}", error, retry_on),
+            PaymentOutcome::Failed {
+                error,
+                retry_on: None,
+            } => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1)
+        .and_then(|first| first.checked_add_months(Months::new(1)))
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(NaiveDate::MAX)
+}
+
+fn day_in_month(date: NaiveDate, day: u32) -> NaiveDate {
+    let last = last_day_of_month(date);
+    last.with_day(day.min(last.day())).unwrap_or(last)
+}
+
+fn add_days(date: NaiveDate, days: u64) -> NaiveDate {
+    date.checked_add_days(Days::new(days))
+        .unwrap_or(NaiveDate::MAX)
+}
+
+impl Schedule {
+    // First payment date on or after `from`, for an order starting on `start`.
+    fn next_on_or_after(self, start: NaiveDate, from: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Schedule::Once => (from <= start).then_some(start),
+            Schedule::Daily => Some(from.max(start)),
+            Schedule::Weekly => {
+                let from = from.max(start);
+                let weeks = ((from - start).num_days() as u64).div_ceil(7);
+                Some(add_days(start, weeks * 7))
+            }
+            Schedule::MonthlyOn(day) => {
+                let from = from.max(start);
+                let candidate = day_in_month(from, day);
+                if candidate >= from {
+                    Some(candidate)
+                } else {
+                    from.with_day(1)
+                        .and_then(|first| first.checked_add_months(Months::new(1)))
+                        .map(|next| day_in_month(next, day))
+                }
+            }
+            Schedule::EndOfMonth => Some(last_day_of_month(from.max(start))),
+        }
+    }
+}
+
+impl StandingOrder {
+    pub fn new(from: &str, to: &str, amount: Money, schedule: Schedule, start: NaiveDate) -> Self {
+        StandingOrder {
+            id: 0,
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            schedule,
+            start,
+            end: None,
+            retry: RetryPolicy::default(),
+            next_due: None,
+            retries: Vec::new(),
+        }
+    }
+
+    // Last date (inclusive) on which a regular payment may fall.
+    pub fn until(mut self, end: NaiveDate) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn next_due(&self) -> Option<NaiveDate> {
+        self.next_due
+    }
+
+    pub fn pending_retries(&self) -> &[PendingRetry] {
+        &self.retries
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_due.is_none() && self.retries.is_empty()
+    }
+
+    fn occurrence_from(&self, from: NaiveDate) -> Option<NaiveDate> {
+        self.schedule
+            .next_on_or_after(self.start, from)
+            .filter(|date| self.end.is_none_or(|end| *date <= end))
+    }
+
+    fn next_event(&self) -> Option<NaiveDate> {
+        self.retries
+            .iter()
+            .map(|retry| retry.retry_on)
+            .chain(self.next_due)
+            .min()
+    }
+}
+
+impl Bank {
+    pub fn standing_orders(&self) -> &[StandingOrder] {
+        &self.standing_orders
+    }
+
+    pub fn add_standing_order(&mut self, mut order: StandingOrder) -> Result<u64, BankError> {
+        let snapshot = self.operation_snapshot().with_standing_orders(self);
+        order.from = self.account_id(&order.from)?.to_string();
+        order.to = self.account_id(&order.to)?.to_string();
+        if order.amount.is_negative() {
+            return Err(BankError::NegativeAmount(order.amount));
+        }
+        if let Schedule::MonthlyOn(day) = order.schedule
+            && !(1..=31).contains(&day)
+        {
+            return Err(BankError::InvalidSchedule(order.schedule.to_string()));
+        }
+
+        order.id = self
+            .standing_orders
+            .iter()
+            .map(|order| order.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        order.next_due = order.occurrence_from(order.start);
+        order.retries.clear();
+
+        let id = order.id;
+        self.standing_orders.push(order);
+        self.checkpoint(snapshot);
+        Ok(id)
+    }
+
+    pub fn cancel_standing_order(&mut self, id: u64) -> Result<StandingOrder, BankError> {
+        let index = self
+            .standing_orders
+            .iter()
+            .position(|order| order.id == id)
+            .ok_or(BankError::StandingOrderNotFound(id))?;
+        let snapshot = self.operation_snapshot().with_standing_orders(self);
+        let order = self.standing_orders.remove(index);
+        self.checkpoint(snapshot);
+        Ok(order)
+    }
+
+    // Moves money through the same staging as `transfer_funds`, and
+    // publishes the same events, but dates the ledger entry on the day the
+    // payment was due.
+    fn scheduled_transfer(
+        &mut self,
+        from: &str,
+        to: &str,
+        amount: Money,
+        date: NaiveDate,
+    ) -> Result<u64, BankError> {
+        let timestamp = date
+            .and_hms_opt(0, 0, 0)
+            .expect("Midnight is a valid time")
+            .and_utc();
+
+        let mut staged = HashMap::new();
+        let staged_transfer = self
+            .check_transfer_rules(from, to, amount, timestamp, &[])
+            .and_then(|()| self.stage_transfer(&mut staged, from, to, amount))
+            .inspect_err(|error| {
+                self.events
+                    .publish(BankEvent::rejected(from, to, amount, error))
+            })?;
+        let before = (self.users[from].balance, self.users[to].balance);
+
+        self.commit_staged(staged);
+        let entry_id = self
+            .ledger
+            .record_transfer_at(timestamp, from, to, amount, staged_transfer);
+        self.events.publish(BankEvent::transfer(
+            entry_id,
+            (&self.users[from], &self.users[to]),
+            amount,
+            before,
+            &staged_transfer,
+        ));
+        Ok(entry_id)
+    }
+
+    fn attempt_payment(
+        &mut self,
+        index: usize,
+        due: NaiveDate,
+        date: NaiveDate,
+        attempt: u32,
+    ) -> PaymentRecord {
+        let order = &self.standing_orders[index];
+        let (id, from, to, amount, retry) = (
+            order.id,
+            order.from.clone(),
+            order.to.clone(),
+            order.amount,
+            order.retry,
+        );
+
+        let outcome = match self.scheduled_transfer(&from, &to, amount, date) {
+            Ok(entry_id) => PaymentOutcome::Paid { entry_id },
+            Err(error) => {
+                let retry_on = (attempt <= retry.max_retries)
+                    .then(|| add_days(date, u64::from(retry.interval_days.max(1))));
+                if let Some(retry_on) = retry_on {
+                    self.standing_orders[index].retries.push(PendingRetry {
+                        due,
+                        attempt: attempt + 1,
+                        retry_on,
+                    });
+                }
+                PaymentOutcome::Failed { error, retry_on }
+            }
+        };
+
+        PaymentRecord {
+            order_id: id,
+            due,
+            executed_on: date,
+            attempt,
+            outcome,
+        }
+    }
+
+    // Executes every payment and retry falling due up to and including
+    // `until`, day by day and in order id within a day. Retries of earlier
+    // failures go before that day's regular payment. Loan installments
+    // aren't collected here; see `collect_installments`.
+    pub fn run_until(&mut self, until: NaiveDate) -> Vec<PaymentRecord> {
+        let snapshot = self.operation_snapshot().with_standing_orders(self);
+        let mut records = Vec::new();
+
+        while let Some(date) = self
+            .standing_orders
+            .iter()
+            .filter_map(StandingOrder::next_event)
+            .min()
+            .filter(|date| *date <= until)
+        {
+            self.standing_orders.sort_by_key(|order| order.id);
+
+            for index in 0..self.standing_orders.len() {
+                let order = &mut self.standing_orders[index];
+                let (due_retries, waiting): (Vec<PendingRetry>, Vec<PendingRetry>) = order
+                    .retries
+                    .drain(..)
+                    .partition(|retry| retry.retry_on == date);
+                order.retries = waiting;
+
+                for retry in due_retries {
+                    records.push(self.attempt_payment(index, retry.due, date, retry.attempt));
+                }
+
+                let order = &mut self.standing_orders[index];
+                if order.next_due == Some(date) {
+                    order.next_due = date.succ_opt().and_then(|next| order.occurrence_from(next));
+                    records.push(self.attempt_payment(index, date, date, 1));
+                }
+            }
+        }
+
+        if !records.is_empty() {
+            self.checkpoint(snapshot);
+        }
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::User;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn occurrences(schedule: Schedule, start: NaiveDate, count: usize) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        let mut from = start;
+        while dates.len() < count {
+            let Some(next) = schedule.next_on_or_after(start, from) else {
+                break;
+            };
+            dates.push(next);
+            from = next.succ_opt().unwrap();
+        }
+        dates
+    }
+
+    #[test]
+    fn test_schedules() {
+        let start = date(2026, 1, 31);
+
+        assert_eq!(occurrences(Schedule::Once, start, 3), vec![start]);
+        assert_eq!(
+            occurrences(Schedule::Weekly, start, 2),
+            vec![start, date(2026, 2, 7)]
+        );
+        assert_eq!(
+            occurrences(Schedule::MonthlyOn(31), start, 3),
+            vec![start, date(2026, 2, 28), date(2026, 3, 31)]
+        );
+        assert_eq!(
+            occurrences(Schedule::MonthlyOn(15), start, 2),
+            vec![date(2026, 2, 15), date(2026, 3, 15)]
+        );
+        assert_eq!(
+            occurrences(Schedule::EndOfMonth, date(2024, 2, 3), 2),
+            vec![date(2024, 2, 29), date(2024, 3, 31)]
+        );
+
+        assert_eq!("monthly:15".parse(), Ok(Schedule::MonthlyOn(15)));
+        assert_eq!("End-Of-Month".parse(), Ok(Schedule::EndOfMonth));
+        assert!("monthly".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::ZERO,
+            Money::from_minor(250_000),
+        ))
+        .unwrap();
+        bank.add_user(User::new("Landlord".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.add_user(User::new("Streaming".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+
+        let rent = StandingOrder::new(
+            "Alice",
+            "Landlord",
+            Money::from_minor(100_000),
+            Schedule::MonthlyOn(1),
+            date(2026, 1, 1),
+        );
+        let subscription = StandingOrder::new(
+            "Alice",
+            "Streaming",
+            Money::from_minor(999),
+            Schedule::EndOfMonth,
+            date(2026, 1, 1),
+        )
+        .until(date(2026, 2, 28));
+        assert_eq!(bank.add_standing_order(rent), Ok(1));
+        assert_eq!(bank.add_standing_order(subscription), Ok(2));
+
+        let records = bank.run_until(date(2026, 2, 28));
+        assert_eq!(records.len(), 4);
+        assert!(
+            records
+                .iter()
+                .all(|record| matches!(record.outcome, PaymentOutcome::Paid { .. }))
+        );
+        assert_eq!(
+            bank.user("Landlord").unwrap().balance,
+            Money::from_minor(200_000)
+        );
+        assert_eq!(
+            bank.user("Alice").unwrap().balance,
+            Money::from_minor(48_002)
+        );
+        assert!(bank.standing_orders()[1].is_finished());
+        assert_eq!(bank.standing_orders()[0].next_due(), Some(date(2026, 3, 1)));
+
+        let entry = bank.ledger().entries().last().unwrap();
+        assert_eq!(entry.timestamp.date_naive(), date(2026, 2, 28));
+        assert!(bank.reconcile().unwrap().is_empty());
+
+        // Running again up to the same date does nothing.
+        assert!(bank.run_until(date(2026, 2, 28)).is_empty());
+
+        let records = bank.run_until(date(2026, 3, 31));
+        assert_eq!(
+            records[0].outcome,
+            PaymentOutcome::Failed {
+                error: BankError::InsufficientCredit {
+                    user: "Alice".to_string(),
+                    amount: Money::from_minor(100_000)
+                },
+                retry_on: None
+            }
+        );
+        assert_eq!(
+            bank.user("Landlord").unwrap().balance,
+            Money::from_minor(200_000)
+        );
+    }
+
+    #[test]
+    fn test_retries() {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 0);
+        bank.add_user(User::new("Alice".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.add_user(User::new("Bob".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.add_user(User::new(
+            "Employer".to_string(),
+            Money::ZERO,
+            Money::from_minor(600),
+        ))
+        .unwrap();
+
+        let order = StandingOrder::new(
+            "Alice",
+            "Bob",
+            Money::from_minor(500),
+            Schedule::Once,
+            date(2026, 3, 1),
+        )
+        .with_retry(RetryPolicy {
+            max_retries: 2,
+            interval_days: 3,
+        });
+        bank.add_standing_order(order).unwrap();
+
+        let records = bank.run_until(date(2026, 3, 5));
+        assert_eq!(records.len(), 2);
+        assert!(matches!(
+            records[1].outcome,
+            PaymentOutcome::Failed {
+                retry_on: Some(retry_on),
+                ..
+            } if retry_on == date(2026, 3, 7)
+        ));
+        assert_eq!(records[1].attempt, 2);
+
+        // Salary arrives before the last retry.
+        bank.transfer_funds("Employer", "Alice", Money::from_minor(600))
+            .unwrap();
+        let records = bank.run_until(date(2026, 3, 31));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].due, date(2026, 3, 1));
+        assert_eq!(records[0].executed_on, date(2026, 3, 7));
+        assert_eq!(records[0].attempt, 3);
+        assert!(matches!(records[0].outcome, PaymentOutcome::Paid { .. }));
+        assert_eq!(bank.user("Bob").unwrap().balance, Money::from_minor(500));
+        assert!(bank.standing_orders()[0].is_finished());
+        assert!(bank.reconcile().unwrap().is_empty());
+
+        assert_eq!(
+            bank.add_standing_order(StandingOrder::new(
+                "Alice",
+                "Carol",
+                Money::from_minor(1),
+                Schedule::Daily,
+                date(2026, 1, 1)
+            )),
+            Err(BankError::UserNotFound("Carol".to_string()))
+        );
+        assert_eq!(
+            bank.add_standing_order(StandingOrder::new(
+                "Alice",
+                "Bob",
+                Money::from_minor(1),
+                Schedule::MonthlyOn(32),
+                date(2026, 1, 1)
+            )),
+            Err(BankError::InvalidSchedule("monthly:32".to_string()))
+        );
+        assert!(bank.cancel_standing_order(1).is_ok());
+        assert_eq!(
+            bank.cancel_standing_order(1),
+            Err(BankError::StandingOrderNotFound(1))
+        );
+    }
+}
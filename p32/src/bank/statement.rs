@@ -0,0 +1,498 @@
+use super::{AccountId, Bank, BankError, Currency, EntryKind, LedgerEntry, Money};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Movement {
+    pub entry_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: EntryKind,
+    // Name of the account on the other side, if it's in this bank.
+    pub counterparty: Option<String>,
+    pub amount: Money,
+    pub balance: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Statement {
+    pub user: String,
+    pub account: AccountId,
+    pub currency: Currency,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub opening_balance: Money,
+    pub movements: Vec<Movement>,
+    pub interest_earned: Money,
+    pub interest_charged: Money,
+    pub closing_balance: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CreditUsage {
+    pub user: String,
+    pub account: AccountId,
+    pub currency: Currency,
+    pub credit_line: Money,
+    pub used: Money,
+    // Share of the credit line in use, in basis points.
+    pub utilisation: u64,
+    pub over_limit: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BankReport {
+    pub name: String,
+    pub currency: Currency,
+    pub users: usize,
+    pub total_liabilities: Money,
+    pub total_assets: Money,
+    pub credit_usage: Vec<CreditUsage>,
+    pub over_limit: Vec<String>,
+}
+
+// Signed effect of a ledger entry on `user`'s balance.
+fn movement_amount(entry: &LedgerEntry, user: &str) -> Result<Money, BankError> {
+    let mut amount = Money::ZERO;
+
+    if entry.from.as_deref() == Some(user) {
+        amount = amount
+            .checked_sub(entry.amount)
+            .ok_or(BankError::LedgerOverflow(entry.id))?;
+    }
+
+    if entry.to.as_deref() == Some(user) {
+        amount = amount
+            .checked_add(entry.credited_amount())
+            .ok_or(BankError::LedgerOverflow(entry.id))?;
+    }
+
+    Ok(amount)
+}
+
+fn counterparty<'a>(entry: &'a LedgerEntry, account: &str) -> Option<&'a str> {
+    if entry.from.as_deref() == Some(account) {
+        entry.to.as_deref()
+    } else {
+        entry.from.as_deref()
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("Midnight is a valid time")
+        .and_utc()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Bank {
+    // Statement of `user`'s account from `start` (inclusive) to `end`
+    // (exclusive), rebuilt from the ledger.
+    pub fn statement(
+        &self,
+        user: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Statement, BankError> {
+        let account = self.user(user)?;
+        let id = account.id.as_str();
+
+        if end < start {
+            return Err(BankError::InvalidDateRange { start, end });
+        }
+
+        let (period_start, period_end) = (midnight(start), midnight(end));
+
+        let mut entries: Vec<&LedgerEntry> = self.ledger.entries_for(id).collect();
+        entries.sort_by_key(|entry| (entry.timestamp, entry.id));
+
+        let mut opening_balance = Money::ZERO;
+        let mut movements = Vec::new();
+        let mut interest_earned = Money::ZERO;
+        let mut interest_charged = Money::ZERO;
+
+        for entry in entries {
+            if entry.timestamp >= period_end {
+                break;
+            }
+
+            let amount = movement_amount(entry, id)?;
+
+            if entry.timestamp < period_start {
+                opening_balance = opening_balance
+                    .checked_add(amount)
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+                continue;
+            }
+
+            if entry.kind == EntryKind::Interest {
+                let total = if amount.is_negative() {
+                    &mut interest_charged
+                } else {
+                    &mut interest_earned
+                };
+                *total = total
+                    .checked_add(amount.abs())
+                    .ok_or(BankError::LedgerOverflow(entry.id))?;
+            }
+
+            let balance = movements
+                .last()
+                .map_or(opening_balance, |movement: &Movement| movement.balance)
+                .checked_add(amount)
+                .ok_or(BankError::LedgerOverflow(entry.id))?;
+
+            movements.push(Movement {
+                entry_id: entry.id,
+                timestamp: entry.timestamp,
+                kind: entry.kind,
+                counterparty: counterparty(entry, id).map(|other| {
+                    self.users
+                        .get(other)
+                        .map_or_else(|| other.to_string(), |user| user.name.clone())
+                }),
+                amount,
+                balance,
+            });
+        }
+
+        let closing_balance = movements
+            .last()
+            .map_or(opening_balance, |movement| movement.balance);
+
+        Ok(Statement {
+            user: account.name.clone(),
+            account: account.id.clone(),
+            currency: account.currency,
+            start,
+            end,
+            opening_balance,
+            movements,
+            interest_earned,
+            interest_charged,
+            closing_balance,
+        })
+    }
+
+    pub fn report(&self) -> Result<BankReport, BankError> {
+        let (total_liabilities, total_assets) = self.calc_balance_in(self.currency)?;
+
+        let mut users: Vec<_> = self.users.values().collect();
+        users.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+
+        let credit_usage: Vec<CreditUsage> = users
+            .iter()
+            .filter(|user| user.credit_line.is_positive() || user.balance.is_negative())
+            .map(|user| {
+                let used = if user.balance.is_negative() {
+                    user.balance.abs()
+                } else {
+                    Money::ZERO
+                };
+                let utilisation = if !user.credit_line.is_positive() {
+                    if used.is_positive() { u64::MAX } else { 0 }
+                } else {
+                    u64::try_from(
+                        i128::from(used.minor()) * 10_000 / i128::from(user.credit_line.minor()),
+                    )
+                    .unwrap_or(u64::MAX)
+                };
+
+                CreditUsage {
+                    user: user.name.clone(),
+                    account: user.id.clone(),
+                    currency: user.currency,
+                    credit_line: user.credit_line,
+                    used,
+                    utilisation,
+                    over_limit: used > user.credit_line,
+                }
+            })
+            .collect();
+
+        let over_limit = credit_usage
+            .iter()
+            .filter(|usage| usage.over_limit)
+            .map(|usage| usage.user.clone())
+            .collect();
+
+        Ok(BankReport {
+            name: self.name.clone(),
+            currency: self.currency,
+            users: users.len(),
+            total_liabilities,
+            total_assets,
+            credit_usage,
+            over_limit,
+        })
+    }
+}
+
+impl Statement {
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("entry,timestamp,kind,counterparty,amount,balance\n");
+        for movement in &self.movements {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                movement.entry_id,
+                movement.timestamp.to_rfc3339(),
+                movement.kind,
+                csv_field(movement.counterparty.as_deref().unwrap_or("")),
+                movement.amount,
+                movement.balance
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statement for {} ({})", self.user, self.currency)?;
+        writeln!(f, "Account: {}", self.account)?;
+        writeln!(f, "Period: {} to {}", self.start, self.end)?;
+        writeln!(
+            f,
+            "Opening balance: {}",
+            self.opening_balance.format(self.currency)
+        )?;
+        for movement in &self.movements {
+            writeln!(
+                f,
+                "  {} #{} {} {} {:>12} {:>12}",
+                movement.timestamp.format("%Y-%m-%d"),
+                movement.entry_id,
+                movement.kind,
+                movement.counterparty.as_deref().unwrap_or("-"),
+                movement.amount,
+                movement.balance
+            )?;
+        }
+        writeln!(
+            f,
+            "Interest earned: {}",
+            self.interest_earned.format(self.currency)
+        )?;
+        writeln!(
+            f,
+            "Interest charged: {}",
+            self.interest_charged.format(self.currency)
+        )?;
+        write!(
+            f,
+            "Closing balance: {}",
+            self.closing_balance.format(self.currency)
+        )
+    }
+}
+
+impl BankReport {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for BankReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Report for {} ({} users)", self.name, self.users)?;
+        writeln!(
+            f,
+            "Total liabilities: {}",
+            self.total_liabilities.format(self.currency)
+        )?;
+        writeln!(
+            f,
+            "Total assets: {}",
+            self.total_assets.format(self.currency)
+        )?;
+        writeln!(f, "Credit line usage:")?;
+        for usage in &self.credit_usage {
+            let utilisation = if usage.utilisation == u64::MAX {
+                "n/a".to_string()
+            } else {
+                format!(
+                    "{}.{:02}%",
+                    usage.utilisation / 100,
+                    usage.utilisation % 100
+                )
+            };
+            writeln!(
+                f,
+                "  {}: {} of {} ({}){}",
+                usage.user,
+                usage.used.format(usage.currency),
+                usage.credit_line.format(usage.currency),
+                utilisation,
+                if usage.over_limit { " OVER LIMIT" } else { "" }
+            )?;
+        }
+        if self.over_limit.is_empty() {
+            write!(f, "Users over their limit: none")
+        } else {
+            write!(f, "Users over their limit: {}", self.over_limit.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Compounding, User};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn sample_bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 0, 365);
+        bank.interest_config.compounding = Compounding::Daily;
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(1000),
+        ))
+        .unwrap();
+        bank.add_user(User::new(
+            "Bob, Jr.".to_string(),
+            Money::from_minor(500),
+            Money::from_minor(0),
+        ))
+        .unwrap();
+        bank
+    }
+
+    fn days_from_today(days: u64) -> NaiveDate {
+        Utc::now().date_naive() + chrono::Days::new(days)
+    }
+
+    #[test]
+    fn test_statement() {
+        let mut bank = sample_bank();
+        bank.add_user(User::new(
+            "Carol".to_string(),
+            Money::from_minor(0),
+            Money::from_minor(14_000),
+        ))
+        .unwrap();
+        bank.transfer_funds("Carol", "Bob, Jr.", Money::from_minor(4000))
+            .unwrap();
+
+        // 3.65% over ACT/365 earns Carol exactly one unit a day, posted at the
+        // end of each day from tomorrow on.
+        bank.accrue_interest_between(days_from_today(1), days_from_today(11))
+            .unwrap();
+        assert_eq!(
+            bank.user("Carol").unwrap().balance,
+            Money::from_minor(10_010)
+        );
+
+        let statement = bank
+            .statement("Carol", days_from_today(3), days_from_today(8))
+            .unwrap();
+        assert_eq!(statement.opening_balance, Money::from_minor(10_001));
+        assert_eq!(statement.movements.len(), 5);
+        assert!(
+            statement
+                .movements
+                .iter()
+                .all(|movement| movement.kind == EntryKind::Interest
+                    && movement.amount == Money::from_minor(1))
+        );
+        assert_eq!(statement.interest_earned, Money::from_minor(5));
+        assert_eq!(statement.interest_charged, Money::from_minor(0));
+        assert_eq!(statement.closing_balance, Money::from_minor(10_006));
+
+        let statement = bank
+            .statement("Carol", days_from_today(0), days_from_today(12))
+            .unwrap();
+        assert_eq!(statement.opening_balance, Money::from_minor(0));
+        assert_eq!(statement.movements[0].kind, EntryKind::Opening);
+        assert_eq!(statement.movements[1].amount, Money::from_minor(-4000));
+        assert_eq!(
+            statement.movements[1].counterparty.as_deref(),
+            Some("Bob, Jr.")
+        );
+        assert_eq!(statement.movements.len(), 12);
+        assert_eq!(
+            statement.closing_balance,
+            bank.user("Carol").unwrap().balance
+        );
+
+        let bob = bank
+            .statement("Bob, Jr.", days_from_today(0), days_from_today(12))
+            .unwrap();
+        assert_eq!(bob.closing_balance, bank.user("Bob, Jr.").unwrap().balance);
+
+        assert_eq!(
+            bank.statement("Dave", days_from_today(0), days_from_today(1)),
+            Err(BankError::UserNotFound("Dave".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_statement_exports() {
+        let mut bank = sample_bank();
+        bank.transfer_funds("Alice", "Bob, Jr.", Money::from_minor(400))
+            .unwrap();
+        let statement = bank
+            .statement("Alice", date(2025, 1, 1), days_from_today(1))
+            .unwrap();
+
+        let text = statement.to_text();
+        assert!(text.starts_with("Statement for Alice (EUR)"));
+        assert!(text.ends_with("Closing balance: €6.00"));
+
+        let csv = statement.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "entry,timestamp,kind,counterparty,amount,balance");
+        assert!(lines[2].starts_with("3,"));
+        assert!(lines[2].ends_with(",transfer,\"Bob, Jr.\",-4.00,6.00"));
+
+        let json: serde_json::Value = serde_json::from_str(&statement.to_json().unwrap()).unwrap();
+        assert_eq!(json["closing_balance"], 600);
+        assert_eq!(json["movements"][1]["kind"], "transfer");
+    }
+
+    #[test]
+    fn test_report() {
+        let mut bank = sample_bank();
+        bank.add_user(User::new(
+            "Carol".to_string(),
+            Money::from_minor(100),
+            Money::from_minor(-150),
+        ))
+        .unwrap();
+        bank.transfer_funds("Bob, Jr.", "Alice", Money::from_minor(250))
+            .unwrap();
+
+        let report = bank.report().unwrap();
+        assert_eq!(report.users, 3);
+        assert_eq!(report.total_liabilities, Money::from_minor(1250));
+        assert_eq!(report.total_assets, Money::from_minor(400));
+        assert_eq!(report.credit_usage.len(), 2);
+        assert_eq!(report.credit_usage[0].user, "Bob, Jr.");
+        assert_eq!(report.credit_usage[0].utilisation, 5000);
+        assert_eq!(report.over_limit, vec!["Carol".to_string()]);
+
+        let text = report.to_string();
+        assert!(text.contains("Bob, Jr.: €2.50 of €5.00 (50.00%)"));
+        assert!(text.contains("Carol: €1.50 of €1.00 (150.00%) OVER LIMIT"));
+    }
+}
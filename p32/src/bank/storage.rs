@@ -0,0 +1,1527 @@
+use super::{
+    AccountId, AccountStatus, Action, Amortization, Bank, Compounding, Currency, DayCount, Denial,
+    EntryKind, ExchangeRates, InterestConfig, KycStatus, Ledger, LedgerEntry, Loan, Money,
+    OverdraftFee, OverdraftPolicy, PendingRetry, RetryPolicy, Role, Rounding, Schedule,
+    SoftOverdraft, StandingOrder, Tier, User,
+};
+use chrono::{DateTime, Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const SCHEMA_VERSION: u16 = 10;
+// Version 1 files predate currencies and load as single-currency EUR banks;
+// version 2 files predate interest configuration and load with the default;
+// version 3 files predate standing orders and load without any; version 4
+// files predate account status and load every account as active with a
+// pending KYC check; version 5 files key accounts by holder name and are
+// given account ids, one customer per account, in name order; version 6
+// files predate loans and load without any; version 7 files predate
+// overdraft policies and load every account with a hard limit; version 8
+// files predate the log of denied actions and load with it empty; version 9
+// files predate reversal entries, so none of their entries reverses another.
+pub const MIN_SCHEMA_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"P32B";
+// magic + version + payload length
+const HEADER_LEN: usize = 4 + 2 + 8;
+const CHECKSUM_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageFormat {
+    Json,
+    Binary,
+}
+
+impl FromStr for StorageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(StorageFormat::Json),
+            "binary" | "bin" => Ok(StorageFormat::Binary),
+            _ => Err(format!("Unknown storage format '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u16),
+    Truncated,
+    Corrupt(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(error) => write!(f, "I/O error: {}", error),
+            StorageError::Json(error) => write!(f, "Invalid JSON: {}", error),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported schema version {} (expected {} to {})",
+                version, MIN_SCHEMA_VERSION, SCHEMA_VERSION
+            ),
+            StorageError::Truncated => write!(f, "File is truncated"),
+            StorageError::Corrupt(reason) => write!(f, "File is corrupt: {}", reason),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StorageError::Io(error) => Some(error),
+            StorageError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(error: io::Error) -> Self {
+        StorageError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(error: serde_json::Error) -> Self {
+        StorageError::Json(error)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BankRecord {
+    version: u16,
+    name: String,
+    credit_interest: u64,
+    debit_interest: u64,
+    #[serde(default)]
+    currency: Currency,
+    #[serde(default)]
+    exchange_rates: Vec<(Currency, Currency, u64)>,
+    #[serde(default)]
+    fx_rounding: Rounding,
+    #[serde(default)]
+    interest_config: InterestConfig,
+    #[serde(default)]
+    interest_carry: Vec<(String, i64)>,
+    users: Vec<User>,
+    ledger: Vec<LedgerEntry>,
+    #[serde(default)]
+    standing_orders: Vec<StandingOrder>,
+    #[serde(default)]
+    loans: Vec<Loan>,
+    #[serde(default)]
+    denials: Vec<Denial>,
+}
+
+impl Bank {
+    pub fn save(&self, path: impl AsRef<Path>, format: StorageFormat) -> Result<(), StorageError> {
+        let bytes = match format {
+            StorageFormat::Json => self.to_json()?.into_bytes(),
+            StorageFormat::Binary => self.to_bytes(),
+        };
+
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    // The format is detected from the file contents, so callers don't need to
+    // remember how a bank was saved.
+    pub fn load(path: impl AsRef<Path>) -> Result<Bank, StorageError> {
+        Bank::load_with_format(path).map(|(bank, _)| bank)
+    }
+
+    pub fn load_with_format(path: impl AsRef<Path>) -> Result<(Bank, StorageFormat), StorageError> {
+        let bytes = fs::read(path)?;
+
+        if bytes.starts_with(MAGIC) {
+            Ok((Bank::from_bytes(&bytes)?, StorageFormat::Binary))
+        } else {
+            let json = String::from_utf8(bytes).map_err(|_| {
+                StorageError::Corrupt("file is neither binary nor UTF-8".to_string())
+            })?;
+            Ok((Bank::from_json(&json)?, StorageFormat::Json))
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, StorageError> {
+        Ok(serde_json::to_string_pretty(&self.to_record())?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Bank, StorageError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| StorageError::Corrupt("missing schema version".to_string()))?;
+        if !(u64::from(MIN_SCHEMA_VERSION)..=u64::from(SCHEMA_VERSION)).contains(&version) {
+            return Err(StorageError::UnsupportedVersion(
+                u16::try_from(version).unwrap_or(u16::MAX),
+            ));
+        }
+
+        Bank::from_record(serde_json::from_value(value)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let record = self.to_record();
+        let mut payload = Vec::new();
+
+        write_str(&mut payload, &record.name);
+        write_u64(&mut payload, record.credit_interest);
+        write_u64(&mut payload, record.debit_interest);
+        payload.push(currency_tag(record.currency));
+        payload.push(rounding_tag(record.fx_rounding));
+
+        write_u64(&mut payload, record.exchange_rates.len() as u64);
+        for &(from, to, rate) in &record.exchange_rates {
+            payload.push(currency_tag(from));
+            payload.push(currency_tag(to));
+            write_u64(&mut payload, rate);
+        }
+
+        let config = &record.interest_config;
+        payload.push(compounding_tag(config.compounding));
+        payload.push(day_count_tag(config.day_count));
+        payload.push(rounding_tag(config.rounding));
+        write_tiers(&mut payload, &config.deposit_tiers);
+        write_tiers(&mut payload, &config.overdraft_tiers);
+
+        write_u64(&mut payload, record.interest_carry.len() as u64);
+        for (name, carry) in &record.interest_carry {
+            write_str(&mut payload, name);
+            write_i64(&mut payload, *carry);
+        }
+
+        write_u64(&mut payload, record.users.len() as u64);
+        for user in &record.users {
+            write_str(&mut payload, user.id.as_str());
+            write_u64(&mut payload, user.customer);
+            write_str(&mut payload, &user.name);
+            write_money(&mut payload, user.credit_line);
+            write_money(&mut payload, user.balance);
+            payload.push(currency_tag(user.currency));
+            payload.push(status_tag(user.status));
+            payload.push(kyc_tag(user.kyc));
+            write_overdraft(&mut payload, user.overdraft);
+        }
+
+        write_u64(&mut payload, record.ledger.len() as u64);
+        for entry in &record.ledger {
+            write_u64(&mut payload, entry.id);
+            write_i64(&mut payload, entry.timestamp.timestamp());
+            payload.extend_from_slice(&entry.timestamp.timestamp_subsec_nanos().to_le_bytes());
+            payload.push(kind_tag(entry.kind));
+            write_opt_str(&mut payload, entry.from.as_deref());
+            write_opt_str(&mut payload, entry.to.as_deref());
+            write_money(&mut payload, entry.amount);
+            write_opt_money(&mut payload, entry.from_balance);
+            write_opt_money(&mut payload, entry.to_balance);
+            write_opt_money(&mut payload, entry.converted_amount);
+            write_opt_u64(&mut payload, entry.reverses);
+        }
+
+        write_u64(&mut payload, record.standing_orders.len() as u64);
+        for order in &record.standing_orders {
+            write_u64(&mut payload, order.id);
+            write_str(&mut payload, &order.from);
+            write_str(&mut payload, &order.to);
+            write_money(&mut payload, order.amount);
+            write_schedule(&mut payload, order.schedule);
+            write_date(&mut payload, order.start);
+            write_opt_date(&mut payload, order.end);
+            payload.extend_from_slice(&order.retry.max_retries.to_le_bytes());
+            payload.extend_from_slice(&order.retry.interval_days.to_le_bytes());
+            write_opt_date(&mut payload, order.next_due);
+            write_u64(&mut payload, order.retries.len() as u64);
+            for retry in &order.retries {
+                write_date(&mut payload, retry.due);
+                payload.extend_from_slice(&retry.attempt.to_le_bytes());
+                write_date(&mut payload, retry.retry_on);
+            }
+        }
+
+        write_u64(&mut payload, record.loans.len() as u64);
+        for loan in &record.loans {
+            write_u64(&mut payload, loan.id);
+            write_str(&mut payload, &loan.account);
+            write_money(&mut payload, loan.principal);
+            write_u64(&mut payload, loan.rate);
+            payload.extend_from_slice(&loan.term_months.to_le_bytes());
+            payload.push(amortization_tag(loan.amortization));
+            write_date(&mut payload, loan.start);
+            write_money(&mut payload, loan.outstanding);
+            payload.extend_from_slice(&loan.installments_paid.to_le_bytes());
+            write_money(&mut payload, loan.interest_paid);
+            write_money(&mut payload, loan.level);
+        }
+
+        write_u64(&mut payload, record.denials.len() as u64);
+        for denial in &record.denials {
+            write_str(&mut payload, &denial.principal);
+            write_role(&mut payload, denial.role);
+            write_action(&mut payload, &denial.action);
+            write_i64(&mut payload, denial.timestamp.timestamp());
+            payload.extend_from_slice(&denial.timestamp.timestamp_subsec_nanos().to_le_bytes());
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + CHECKSUM_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        write_u64(&mut bytes, payload.len() as u64);
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Bank, StorageError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(StorageError::Truncated);
+        }
+
+        let mut header = Reader::new(bytes);
+        if header.take(4)? != MAGIC {
+            return Err(StorageError::Corrupt("bad magic number".to_string()));
+        }
+
+        let version = header.read_u16()?;
+        if !(MIN_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version) {
+            return Err(StorageError::UnsupportedVersion(version));
+        }
+
+        let payload_len = usize::try_from(header.read_u64()?)
+            .map_err(|_| StorageError::Corrupt("payload length too large".to_string()))?;
+        let payload = header.take(payload_len)?;
+        let checksum = header.read_u32()?;
+        if !header.is_empty() {
+            return Err(StorageError::Corrupt(
+                "trailing bytes after checksum".to_string(),
+            ));
+        }
+        if crc32(payload) != checksum {
+            return Err(StorageError::Corrupt("checksum mismatch".to_string()));
+        }
+
+        let mut reader = Reader::new(payload);
+        let name = reader.read_str()?;
+        let credit_interest = reader.read_u64()?;
+        let debit_interest = reader.read_u64()?;
+
+        let mut currency = Currency::default();
+        let mut fx_rounding = Rounding::default();
+        let mut exchange_rates = Vec::new();
+        if version >= 2 {
+            currency = currency_from_tag(reader.read_u8()?)?;
+            fx_rounding = rounding_from_tag(reader.read_u8()?)?;
+
+            let rate_count = reader.read_u64()?;
+            for _ in 0..rate_count {
+                exchange_rates.push((
+                    currency_from_tag(reader.read_u8()?)?,
+                    currency_from_tag(reader.read_u8()?)?,
+                    reader.read_u64()?,
+                ));
+            }
+        }
+
+        let mut interest_config = InterestConfig::default();
+        let mut interest_carry = Vec::new();
+        if version >= 3 {
+            interest_config.compounding = compounding_from_tag(reader.read_u8()?)?;
+            interest_config.day_count = day_count_from_tag(reader.read_u8()?)?;
+            interest_config.rounding = rounding_from_tag(reader.read_u8()?)?;
+            interest_config.deposit_tiers = reader.read_tiers()?;
+            interest_config.overdraft_tiers = reader.read_tiers()?;
+
+            let carry_count = reader.read_u64()?;
+            for _ in 0..carry_count {
+                interest_carry.push((reader.read_str()?, reader.read_i64()?));
+            }
+        }
+
+        let user_count = reader.read_u64()?;
+        let mut users = Vec::new();
+        for _ in 0..user_count {
+            let mut id = AccountId::default();
+            let mut customer = 0;
+            if version >= 6 {
+                id = reader.read_str()?.parse().map_err(StorageError::Corrupt)?;
+                customer = reader.read_u64()?;
+            }
+            let name = reader.read_str()?;
+            let credit_line = reader.read_money()?;
+            let balance = reader.read_money()?;
+            let mut user = User::new(name, credit_line, balance).for_customer(customer);
+            user.id = id;
+            if version >= 2 {
+                user.currency = currency_from_tag(reader.read_u8()?)?;
+            }
+            if version >= 5 {
+                user.status = status_from_tag(reader.read_u8()?)?;
+                user.kyc = kyc_from_tag(reader.read_u8()?)?;
+            }
+            if version >= 8 {
+                user.overdraft = reader.read_overdraft()?;
+            }
+            users.push(user);
+        }
+
+        let entry_count = reader.read_u64()?;
+        let mut ledger = Vec::new();
+        for _ in 0..entry_count {
+            let id = reader.read_u64()?;
+            let seconds = reader.read_i64()?;
+            let nanos = reader.read_u32()?;
+            let timestamp = DateTime::from_timestamp(seconds, nanos).ok_or_else(|| {
+                StorageError::Corrupt(format!("invalid timestamp in ledger entry #{}", id))
+            })?;
+
+            ledger.push(LedgerEntry {
+                id,
+                timestamp,
+                kind: kind_from_tag(reader.read_u8()?)?,
+                from: reader.read_opt_str()?,
+                to: reader.read_opt_str()?,
+                amount: reader.read_money()?,
+                from_balance: reader.read_opt_money()?,
+                to_balance: reader.read_opt_money()?,
+                converted_amount: if version >= 2 {
+                    reader.read_opt_money()?
+                } else {
+                    None
+                },
+                reverses: if version >= 10 {
+                    reader.read_opt_u64()?
+                } else {
+                    None
+                },
+            });
+        }
+
+        let mut standing_orders = Vec::new();
+        if version >= 4 {
+            let order_count = reader.read_u64()?;
+            for _ in 0..order_count {
+                let id = reader.read_u64()?;
+                let from = reader.read_str()?;
+                let to = reader.read_str()?;
+                let amount = reader.read_money()?;
+                let schedule = reader.read_schedule()?;
+                let mut order =
+                    StandingOrder::new(&from, &to, amount, schedule, reader.read_date()?);
+                order.id = id;
+                order.end = reader.read_opt_date()?;
+                order.retry = RetryPolicy {
+                    max_retries: reader.read_u32()?,
+                    interval_days: reader.read_u32()?,
+                };
+                order.next_due = reader.read_opt_date()?;
+
+                let retry_count = reader.read_u64()?;
+                for _ in 0..retry_count {
+                    order.retries.push(PendingRetry {
+                        due: reader.read_date()?,
+                        attempt: reader.read_u32()?,
+                        retry_on: reader.read_date()?,
+                    });
+                }
+                standing_orders.push(order);
+            }
+        }
+
+        let mut loans = Vec::new();
+        if version >= 7 {
+            let loan_count = reader.read_u64()?;
+            for _ in 0..loan_count {
+                let id = reader.read_u64()?;
+                let account = reader.read_str()?;
+                let principal = reader.read_money()?;
+                let rate = reader.read_u64()?;
+                let term_months = reader.read_u32()?;
+                let amortization = amortization_from_tag(reader.read_u8()?)?;
+                let mut loan = Loan::new(
+                    &account,
+                    principal,
+                    rate,
+                    term_months,
+                    amortization,
+                    reader.read_date()?,
+                );
+                loan.id = id;
+                loan.outstanding = reader.read_money()?;
+                loan.installments_paid = reader.read_u32()?;
+                loan.interest_paid = reader.read_money()?;
+                loan.level = reader.read_money()?;
+                loans.push(loan);
+            }
+        }
+
+        let mut denials = Vec::new();
+        if version >= 9 {
+            let denial_count = reader.read_u64()?;
+            for _ in 0..denial_count {
+                let principal = reader.read_str()?;
+                let role = reader.read_role()?;
+                let action = reader.read_action()?;
+                let seconds = reader.read_i64()?;
+                let nanos = reader.read_u32()?;
+                let timestamp = DateTime::from_timestamp(seconds, nanos).ok_or_else(|| {
+                    StorageError::Corrupt(format!("invalid timestamp in denial of '{}'", principal))
+                })?;
+                denials.push(Denial {
+                    principal,
+                    role,
+                    action,
+                    timestamp,
+                });
+            }
+        }
+
+        if !reader.is_empty() {
+            return Err(StorageError::Corrupt(
+                "unexpected data after ledger".to_string(),
+            ));
+        }
+
+        Bank::from_record(BankRecord {
+            version,
+            name,
+            credit_interest,
+            debit_interest,
+            currency,
+            exchange_rates,
+            fx_rounding,
+            interest_config,
+            interest_carry,
+            users,
+            ledger,
+            standing_orders,
+            loans,
+            denials,
+        })
+    }
+
+    fn to_record(&self) -> BankRecord {
+        let mut users: Vec<User> = self.users.values().cloned().collect();
+        users.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+
+        let mut exchange_rates: Vec<_> = self.exchange_rates.rates().collect();
+        exchange_rates.sort_by_key(|&(from, to, _)| (currency_tag(from), currency_tag(to)));
+
+        let mut interest_carry: Vec<(String, i64)> = self
+            .interest_carry
+            .iter()
+            .map(|(id, carry)| (id.clone(), *carry))
+            .collect();
+        interest_carry.sort();
+
+        let mut standing_orders = self.standing_orders.clone();
+        standing_orders.sort_by_key(|order| order.id);
+
+        let mut loans = self.loans.clone();
+        loans.sort_by_key(|loan| loan.id);
+
+        BankRecord {
+            version: SCHEMA_VERSION,
+            name: self.name.clone(),
+            credit_interest: self.credit_interest,
+            debit_interest: self.debit_interest,
+            currency: self.currency,
+            exchange_rates,
+            fx_rounding: self.fx_rounding,
+            interest_config: self.interest_config.clone(),
+            interest_carry,
+            users,
+            ledger: self.ledger.entries().to_vec(),
+            standing_orders,
+            loans,
+            denials: self.denials.clone(),
+        }
+    }
+
+    fn from_record(mut record: BankRecord) -> Result<Bank, StorageError> {
+        if record.version < 6 {
+            assign_account_ids(&mut record)?;
+        }
+
+        let mut users = HashMap::new();
+        for user in record.users {
+            if user.id.as_str().parse::<AccountId>().as_ref() != Ok(&user.id) {
+                return Err(StorageError::Corrupt(format!(
+                    "invalid account id '{}' for user '{}'",
+                    user.id, user.name
+                )));
+            }
+            if users.contains_key(user.id.as_str()) {
+                return Err(StorageError::Corrupt(format!(
+                    "duplicate account '{}'",
+                    user.id
+                )));
+            }
+            if user.customer == 0 {
+                return Err(StorageError::Corrupt(format!(
+                    "account '{}' has no customer",
+                    user.id
+                )));
+            }
+            if user.credit_line.is_negative() {
+                return Err(StorageError::Corrupt(format!(
+                    "negative credit line for user '{}'",
+                    user.name
+                )));
+            }
+            users.insert(user.id.to_string(), user);
+        }
+
+        for (index, entry) in record.ledger.iter().enumerate() {
+            if entry.id != index as u64 + 1 {
+                return Err(StorageError::Corrupt(format!(
+                    "ledger entry #{} out of sequence",
+                    entry.id
+                )));
+            }
+            if entry.amount.is_negative() || entry.credited_amount().is_negative() {
+                return Err(StorageError::Corrupt(format!(
+                    "negative amount in ledger entry #{}",
+                    entry.id
+                )));
+            }
+            // A reversal undoes one earlier entry that isn't a reversal itself.
+            let reverses = entry.reverses.and_then(|id| {
+                record.ledger[..index].get(usize::try_from(id).ok()?.checked_sub(1)?)
+            });
+            if (entry.kind == EntryKind::Reversal)
+                != reverses.is_some_and(|reversed| reversed.kind != EntryKind::Reversal)
+            {
+                return Err(StorageError::Corrupt(format!(
+                    "ledger entry #{} doesn't reverse an earlier entry",
+                    entry.id
+                )));
+            }
+        }
+
+        let mut order_ids = Vec::new();
+        for order in &record.standing_orders {
+            if order_ids.contains(&order.id) {
+                return Err(StorageError::Corrupt(format!(
+                    "duplicate standing order #{}",
+                    order.id
+                )));
+            }
+            order_ids.push(order.id);
+
+            for id in [&order.from, &order.to] {
+                if !users.contains_key(id) {
+                    return Err(StorageError::Corrupt(format!(
+                        "standing order #{} refers to unknown account '{}'",
+                        order.id, id
+                    )));
+                }
+            }
+            if order.amount.is_negative() {
+                return Err(StorageError::Corrupt(format!(
+                    "negative amount in standing order #{}",
+                    order.id
+                )));
+            }
+        }
+
+        let mut loan_ids = Vec::new();
+        for loan in &record.loans {
+            if loan_ids.contains(&loan.id) {
+                return Err(StorageError::Corrupt(format!(
+                    "duplicate loan #{}",
+                    loan.id
+                )));
+            }
+            loan_ids.push(loan.id);
+
+            if !users.contains_key(&loan.account) {
+                return Err(StorageError::Corrupt(format!(
+                    "loan #{} refers to unknown account '{}'",
+                    loan.id, loan.account
+                )));
+            }
+            if loan.outstanding.is_negative()
+                || loan.outstanding > loan.principal
+                || loan.interest_paid.is_negative()
+            {
+                return Err(StorageError::Corrupt(format!(
+                    "inconsistent amounts in loan #{}",
+                    loan.id
+                )));
+            }
+        }
+
+        let mut rates = ExchangeRates::new();
+        for (from, to, rate) in record.exchange_rates {
+            rates.set_rate(from, to, rate);
+        }
+
+        let mut bank = Bank::new(record.name, record.credit_interest, record.debit_interest);
+        bank.currency = record.currency;
+        bank.exchange_rates = rates;
+        bank.fx_rounding = record.fx_rounding;
+        bank.interest_config = record.interest_config;
+        bank.interest_carry = record.interest_carry.into_iter().collect();
+        bank.users = users;
+        bank.ledger = Ledger::from_entries(record.ledger);
+        bank.standing_orders = record.standing_orders;
+        bank.loans = record.loans;
+        bank.denials = record.denials;
+        Ok(bank)
+    }
+}
+
+// Older files refer to accounts by holder name everywhere; names were unique
+// then, so each one becomes its own account and customer.
+fn assign_account_ids(record: &mut BankRecord) -> Result<(), StorageError> {
+    record.users.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut ids = HashMap::new();
+    for (index, user) in record.users.iter_mut().enumerate() {
+        if ids.contains_key(&user.name) {
+            return Err(StorageError::Corrupt(format!(
+                "duplicate user '{}'",
+                user.name
+            )));
+        }
+        user.id = AccountId::new(index as u64 + 1);
+        user.customer = index as u64 + 1;
+        ids.insert(user.name.clone(), user.id.to_string());
+    }
+
+    let rename = |name: &mut String| {
+        if let Some(id) = ids.get(name.as_str()) {
+            *name = id.clone();
+        }
+    };
+    for entry in &mut record.ledger {
+        entry
+            .from
+            .iter_mut()
+            .chain(entry.to.iter_mut())
+            .for_each(rename);
+    }
+    for (name, _) in &mut record.interest_carry {
+        rename(name);
+    }
+    for order in &mut record.standing_orders {
+        rename(&mut order.from);
+        rename(&mut order.to);
+    }
+    Ok(())
+}
+
+fn kind_tag(kind: EntryKind) -> u8 {
+    match kind {
+        EntryKind::Opening => 0,
+        EntryKind::Transfer => 1,
+        EntryKind::Interest => 2,
+        EntryKind::Merge => 3,
+        EntryKind::Settlement => 4,
+        EntryKind::Disbursement => 5,
+        EntryKind::Repayment => 6,
+        EntryKind::Fee => 7,
+        EntryKind::Reversal => 8,
+    }
+}
+
+fn kind_from_tag(tag: u8) -> Result<EntryKind, StorageError> {
+    match tag {
+        0 => Ok(EntryKind::Opening),
+        1 => Ok(EntryKind::Transfer),
+        2 => Ok(EntryKind::Interest),
+        3 => Ok(EntryKind::Merge),
+        4 => Ok(EntryKind::Settlement),
+        5 => Ok(EntryKind::Disbursement),
+        6 => Ok(EntryKind::Repayment),
+        7 => Ok(EntryKind::Fee),
+        8 => Ok(EntryKind::Reversal),
+        _ => Err(StorageError::Corrupt(format!("unknown entry kind {}", tag))),
+    }
+}
+
+fn amortization_tag(amortization: Amortization) -> u8 {
+    match amortization {
+        Amortization::Annuity => 0,
+        Amortization::Linear => 1,
+    }
+}
+
+fn amortization_from_tag(tag: u8) -> Result<Amortization, StorageError> {
+    match tag {
+        0 => Ok(Amortization::Annuity),
+        1 => Ok(Amortization::Linear),
+        _ => Err(StorageError::Corrupt(format!(
+            "unknown amortization {}",
+            tag
+        ))),
+    }
+}
+
+fn status_tag(status: AccountStatus) -> u8 {
+    match status {
+        AccountStatus::Active => 0,
+        AccountStatus::Frozen => 1,
+        AccountStatus::Closed => 2,
+    }
+}
+
+fn status_from_tag(tag: u8) -> Result<AccountStatus, StorageError> {
+    match tag {
+        0 => Ok(AccountStatus::Active),
+        1 => Ok(AccountStatus::Frozen),
+        2 => Ok(AccountStatus::Closed),
+        _ => Err(StorageError::Corrupt(format!(
+            "unknown account status {}",
+            tag
+        ))),
+    }
+}
+
+fn kyc_tag(kyc: KycStatus) -> u8 {
+    match kyc {
+        KycStatus::Pending => 0,
+        KycStatus::Verified => 1,
+        KycStatus::Rejected => 2,
+    }
+}
+
+fn kyc_from_tag(tag: u8) -> Result<KycStatus, StorageError> {
+    match tag {
+        0 => Ok(KycStatus::Pending),
+        1 => Ok(KycStatus::Verified),
+        2 => Ok(KycStatus::Rejected),
+        _ => Err(StorageError::Corrupt(format!("unknown KYC status {}", tag))),
+    }
+}
+
+fn write_overdraft(out: &mut Vec<u8>, overdraft: OverdraftPolicy) {
+    let OverdraftPolicy::Soft(soft) = overdraft else {
+        out.push(0);
+        return;
+    };
+    out.push(1);
+    write_money(out, soft.limit);
+    match soft.fee {
+        OverdraftFee::Fixed(fee) => {
+            out.push(0);
+            write_money(out, fee);
+        }
+        OverdraftFee::Percentage(rate) => {
+            out.push(1);
+            write_u64(out, rate);
+        }
+    }
+    write_money(out, soft.grace);
+    write_u64(out, soft.penalty_rate);
+}
+
+fn write_role(out: &mut Vec<u8>, role: Role) {
+    match role {
+        Role::Customer(customer) => {
+            out.push(0);
+            write_u64(out, customer);
+        }
+        Role::Teller => out.push(1),
+        Role::Admin => out.push(2),
+        Role::Auditor => out.push(3),
+    }
+}
+
+fn write_action(out: &mut Vec<u8>, action: &Action) {
+    let (tag, account) = match action {
+        Action::ViewAccount(id) => (0, Some(id)),
+        Action::ViewBank => (1, None),
+        Action::Transfer { from } => (2, Some(from)),
+        Action::OpenAccount => (3, None),
+        Action::ManageAccount(id) => (4, Some(id)),
+        Action::SetLimits(id) => (5, Some(id)),
+        Action::SetInterestRates => (6, None),
+        Action::AccrueInterest => (7, None),
+    };
+    out.push(tag);
+    if let Some(id) = account {
+        write_str(out, id.as_str());
+    }
+}
+
+fn currency_tag(currency: Currency) -> u8 {
+    match currency {
+        Currency::EUR => 0,
+        Currency::USD => 1,
+        Currency::AED => 2,
+    }
+}
+
+fn currency_from_tag(tag: u8) -> Result<Currency, StorageError> {
+    match tag {
+        0 => Ok(Currency::EUR),
+        1 => Ok(Currency::USD),
+        2 => Ok(Currency::AED),
+        _ => Err(StorageError::Corrupt(format!("unknown currency {}", tag))),
+    }
+}
+
+fn rounding_tag(rounding: Rounding) -> u8 {
+    match rounding {
+        Rounding::Truncate => 0,
+        Rounding::HalfEven => 1,
+        Rounding::HalfUp => 2,
+    }
+}
+
+fn rounding_from_tag(tag: u8) -> Result<Rounding, StorageError> {
+    match tag {
+        0 => Ok(Rounding::Truncate),
+        1 => Ok(Rounding::HalfEven),
+        2 => Ok(Rounding::HalfUp),
+        _ => Err(StorageError::Corrupt(format!(
+            "unknown rounding mode {}",
+            tag
+        ))),
+    }
+}
+
+fn compounding_tag(compounding: Compounding) -> u8 {
+    match compounding {
+        Compounding::Daily => 0,
+        Compounding::Monthly => 1,
+        Compounding::Annually => 2,
+    }
+}
+
+fn compounding_from_tag(tag: u8) -> Result<Compounding, StorageError> {
+    match tag {
+        0 => Ok(Compounding::Daily),
+        1 => Ok(Compounding::Monthly),
+        2 => Ok(Compounding::Annually),
+        _ => Err(StorageError::Corrupt(format!(
+            "unknown compounding period {}",
+            tag
+        ))),
+    }
+}
+
+fn day_count_tag(day_count: DayCount) -> u8 {
+    match day_count {
+        DayCount::Act360 => 0,
+        DayCount::Act365 => 1,
+    }
+}
+
+fn day_count_from_tag(tag: u8) -> Result<DayCount, StorageError> {
+    match tag {
+        0 => Ok(DayCount::Act360),
+        1 => Ok(DayCount::Act365),
+        _ => Err(StorageError::Corrupt(format!(
+            "unknown day count convention {}",
+            tag
+        ))),
+    }
+}
+
+fn write_tiers(out: &mut Vec<u8>, tiers: &[Tier]) {
+    write_u64(out, tiers.len() as u64);
+    for tier in tiers {
+        write_money(out, tier.threshold);
+        write_u64(out, tier.rate);
+    }
+}
+
+fn write_schedule(out: &mut Vec<u8>, schedule: Schedule) {
+    match schedule {
+        Schedule::Once => out.push(0),
+        Schedule::Daily => out.push(1),
+        Schedule::Weekly => out.push(2),
+        Schedule::MonthlyOn(day) => {
+            out.push(3);
+            out.extend_from_slice(&day.to_le_bytes());
+        }
+        Schedule::EndOfMonth => out.push(4),
+    }
+}
+
+// Dates are stored as days since 1 January of year 1.
+fn write_date(out: &mut Vec<u8>, date: NaiveDate) {
+    out.extend_from_slice(&date.num_days_from_ce().to_le_bytes());
+}
+
+fn write_opt_date(out: &mut Vec<u8>, value: Option<NaiveDate>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_date(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_i64(out: &mut Vec<u8>, value: i64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_u64(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_opt_str(out: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_str(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+// Amounts are stored signed. Those that used to be stored unsigned, like
+// credit lines and ledger amounts, were always between zero and `i64::MAX`,
+// so their encoding is the same either way.
+fn write_money(out: &mut Vec<u8>, value: Money) {
+    write_i64(out, value.minor());
+}
+
+fn write_opt_u64(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_u64(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_opt_money(out: &mut Vec<u8>, value: Option<Money>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_money(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StorageError> {
+        let end = self.pos.checked_add(len).ok_or(StorageError::Truncated)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(StorageError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], StorageError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, StorageError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, StorageError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, StorageError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, StorageError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, StorageError> {
+        Ok(i64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_str(&mut self) -> Result<String, StorageError> {
+        let len = usize::try_from(self.read_u64()?).map_err(|_| StorageError::Truncated)?;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| StorageError::Corrupt("invalid UTF-8 in string".to_string()))
+    }
+
+    fn read_flag(&mut self) -> Result<bool, StorageError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            flag => Err(StorageError::Corrupt(format!(
+                "invalid option flag {}",
+                flag
+            ))),
+        }
+    }
+
+    fn read_opt_str(&mut self) -> Result<Option<String>, StorageError> {
+        if self.read_flag()? {
+            Ok(Some(self.read_str()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_tiers(&mut self) -> Result<Vec<Tier>, StorageError> {
+        let count = self.read_u64()?;
+        let mut tiers = Vec::new();
+        for _ in 0..count {
+            tiers.push(Tier {
+                threshold: self.read_money()?,
+                rate: self.read_u64()?,
+            });
+        }
+        Ok(tiers)
+    }
+
+    fn read_schedule(&mut self) -> Result<Schedule, StorageError> {
+        match self.read_u8()? {
+            0 => Ok(Schedule::Once),
+            1 => Ok(Schedule::Daily),
+            2 => Ok(Schedule::Weekly),
+            3 => Ok(Schedule::MonthlyOn(self.read_u32()?)),
+            4 => Ok(Schedule::EndOfMonth),
+            tag => Err(StorageError::Corrupt(format!("unknown schedule {}", tag))),
+        }
+    }
+
+    fn read_date(&mut self) -> Result<NaiveDate, StorageError> {
+        let days = i32::from_le_bytes(self.read_array()?);
+        NaiveDate::from_num_days_from_ce_opt(days)
+            .ok_or_else(|| StorageError::Corrupt(format!("invalid date {}", days)))
+    }
+
+    fn read_opt_date(&mut self) -> Result<Option<NaiveDate>, StorageError> {
+        if self.read_flag()? {
+            Ok(Some(self.read_date()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_money(&mut self) -> Result<Money, StorageError> {
+        Ok(Money::from_minor(self.read_i64()?))
+    }
+
+    fn read_overdraft(&mut self) -> Result<OverdraftPolicy, StorageError> {
+        match self.read_u8()? {
+            0 => Ok(OverdraftPolicy::Hard),
+            1 => {
+                let limit = self.read_money()?;
+                let fee = match self.read_u8()? {
+                    0 => OverdraftFee::Fixed(self.read_money()?),
+                    1 => OverdraftFee::Percentage(self.read_u64()?),
+                    tag => {
+                        return Err(StorageError::Corrupt(format!(
+                            "unknown overdraft fee {}",
+                            tag
+                        )));
+                    }
+                };
+                Ok(OverdraftPolicy::Soft(SoftOverdraft {
+                    limit,
+                    fee,
+                    grace: self.read_money()?,
+                    penalty_rate: self.read_u64()?,
+                }))
+            }
+            tag => Err(StorageError::Corrupt(format!(
+                "unknown overdraft policy {}",
+                tag
+            ))),
+        }
+    }
+
+    fn read_role(&mut self) -> Result<Role, StorageError> {
+        match self.read_u8()? {
+            0 => Ok(Role::Customer(self.read_u64()?)),
+            1 => Ok(Role::Teller),
+            2 => Ok(Role::Admin),
+            3 => Ok(Role::Auditor),
+            tag => Err(StorageError::Corrupt(format!("unknown role {}", tag))),
+        }
+    }
+
+    fn read_action(&mut self) -> Result<Action, StorageError> {
+        let tag = self.read_u8()?;
+        let mut account = || -> Result<AccountId, StorageError> {
+            self.read_str()?.parse().map_err(StorageError::Corrupt)
+        };
+        match tag {
+            0 => Ok(Action::ViewAccount(account()?)),
+            1 => Ok(Action::ViewBank),
+            2 => Ok(Action::Transfer { from: account()? }),
+            3 => Ok(Action::OpenAccount),
+            4 => Ok(Action::ManageAccount(account()?)),
+            5 => Ok(Action::SetLimits(account()?)),
+            6 => Ok(Action::SetInterestRates),
+            7 => Ok(Action::AccrueInterest),
+            _ => Err(StorageError::Corrupt(format!("unknown action {}", tag))),
+        }
+    }
+
+    fn read_opt_u64(&mut self) -> Result<Option<u64>, StorageError> {
+        if self.read_flag()? {
+            Ok(Some(self.read_u64()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_opt_money(&mut self) -> Result<Option<Money>, StorageError> {
+        if self.read_flag()? {
+            Ok(Some(self.read_money()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// CRC-32 (IEEE), bitwise; bank files are small enough not to need a table.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Principal;
+
+    fn sample_bank() -> Bank {
+        let mut bank = Bank::new("Test Bank".to_string(), 500, 300);
+        bank.exchange_rates
+            .set_rate(Currency::EUR, Currency::USD, 1_085_000);
+        bank.add_user(User::new(
+            "Alice".to_string(),
+            Money::from_minor(1000),
+            Money::from_minor(1000),
+        ))
+        .unwrap();
+        bank.add_user(
+            User::new(
+                "Bob".to_string(),
+                Money::from_minor(2000),
+                Money::from_minor(-500),
+            )
+            .with_currency(Currency::USD),
+        )
+        .unwrap();
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(300))
+            .unwrap();
+        bank.accrue_interest().unwrap();
+
+        bank.interest_config.compounding = Compounding::Daily;
+        bank.interest_config.day_count = DayCount::Act360;
+        bank.interest_config.deposit_tiers = vec![
+            Tier {
+                threshold: Money::from_minor(0),
+                rate: 100,
+            },
+            Tier {
+                threshold: Money::from_minor(500),
+                rate: 200,
+            },
+        ];
+        let start = chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        bank.accrue_interest_between(start, start + chrono::Days::new(3))
+            .unwrap();
+        assert_ne!(bank.interest_carry("Alice"), 0);
+
+        let order = StandingOrder::new(
+            "Alice",
+            "Bob",
+            Money::from_minor(10_000),
+            Schedule::MonthlyOn(15),
+            start,
+        )
+        .until(start + chrono::Days::new(90))
+        .with_retry(RetryPolicy {
+            max_retries: 1,
+            interval_days: 2,
+        });
+        bank.add_standing_order(order).unwrap();
+        bank.run_until(start + chrono::Days::new(14));
+        assert_eq!(bank.standing_orders()[0].pending_retries().len(), 1);
+
+        bank.add_user(User::new("Carol".to_string(), Money::ZERO, Money::ZERO))
+            .unwrap();
+        bank.set_kyc_status("Carol", KycStatus::Rejected).unwrap();
+        bank.set_overdraft_policy(
+            "Alice",
+            OverdraftPolicy::Soft(SoftOverdraft {
+                limit: Money::from_minor(5000),
+                fee: OverdraftFee::Percentage(150),
+                grace: Money::from_minor(1000),
+                penalty_rate: 300,
+            }),
+        )
+        .unwrap();
+        bank.set_overdraft_policy(
+            "Bob",
+            OverdraftPolicy::Soft(SoftOverdraft {
+                fee: OverdraftFee::Fixed(Money::from_minor(250)),
+                ..SoftOverdraft::default()
+            }),
+        )
+        .unwrap();
+
+        let loan = Loan::new(
+            "Bob",
+            Money::from_minor(60_000),
+            450,
+            24,
+            Amortization::Linear,
+            start,
+        );
+        bank.grant_loan(loan).unwrap();
+        bank.collect_installments(start + chrono::Days::new(40));
+        assert_eq!(bank.loans()[0].installments_paid(), 1);
+
+        let mut session = bank.session(Principal::new("eve", Role::Customer(1)));
+        session
+            .transfer_funds("Bob", "Alice", Money::from_minor(1))
+            .unwrap_err();
+        session.accrue_interest().unwrap_err();
+        assert_eq!(bank.denials().len(), 2);
+
+        bank.transfer_funds("Alice", "Bob", Money::from_minor(200))
+            .unwrap();
+        bank.undo(1).unwrap();
+        assert_eq!(
+            bank.ledger().entries().last().unwrap().kind,
+            EntryKind::Reversal
+        );
+        bank
+    }
+
+    fn assert_same_bank(a: &Bank, b: &Bank) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.credit_interest, b.credit_interest);
+        assert_eq!(a.debit_interest, b.debit_interest);
+        assert_eq!(a.currency, b.currency);
+        assert_eq!(a.exchange_rates, b.exchange_rates);
+        assert_eq!(a.fx_rounding, b.fx_rounding);
+        assert_eq!(a.interest_config, b.interest_config);
+        assert_eq!(a.interest_carry, b.interest_carry);
+        assert_eq!(a.users, b.users);
+        assert_eq!(a.ledger().entries(), b.ledger().entries());
+        assert_eq!(a.standing_orders(), b.standing_orders());
+        assert_eq!(a.loans(), b.loans());
+        assert_eq!(a.denials(), b.denials());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let bank = sample_bank();
+        let loaded = Bank::from_json(&bank.to_json().unwrap()).unwrap();
+        assert_same_bank(&bank, &loaded);
+        assert!(loaded.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let bank = sample_bank();
+        let loaded = Bank::from_bytes(&bank.to_bytes()).unwrap();
+        assert_same_bank(&bank, &loaded);
+    }
+
+    #[test]
+    fn test_negative_amounts_roundtrip() {
+        let mut bank = sample_bank();
+        bank.interest_config.overdraft_tiers = vec![
+            Tier {
+                threshold: Money::from_minor(-5000),
+                rate: 900,
+            },
+            Tier {
+                threshold: Money::MIN,
+                rate: 1500,
+            },
+        ];
+
+        let loaded = Bank::from_bytes(&bank.to_bytes()).unwrap();
+        assert_same_bank(&bank, &loaded);
+        let loaded = Bank::from_json(&bank.to_json().unwrap()).unwrap();
+        assert_same_bank(&bank, &loaded);
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let bank = sample_bank();
+        let dir = std::env::temp_dir();
+
+        for (file, format) in [
+            ("p32_storage_test.json", StorageFormat::Json),
+            ("p32_storage_test.bin", StorageFormat::Binary),
+        ] {
+            let path = dir.join(format!("{}-{}", std::process::id(), file));
+            bank.save(&path, format).unwrap();
+            let loaded = Bank::load(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_same_bank(&bank, &loaded);
+        }
+
+        assert!(matches!(
+            Bank::load(dir.join("p32-missing-bank-file")),
+            Err(StorageError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_binary_errors() {
+        let bytes = sample_bank().to_bytes();
+
+        assert!(matches!(
+            Bank::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(StorageError::Truncated)
+        ));
+        assert!(matches!(
+            Bank::from_bytes(&bytes[..10]),
+            Err(StorageError::Truncated)
+        ));
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 2] ^= 0xFF;
+        assert!(matches!(
+            Bank::from_bytes(&corrupted),
+            Err(StorageError::Corrupt(_))
+        ));
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Bank::from_bytes(&future),
+            Err(StorageError::UnsupportedVersion(v)) if v == SCHEMA_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_load_version_1() {
+        let json = r#"{
+            "version": 1,
+            "name": "Old Bank",
+            "credit_interest": 500,
+            "debit_interest": 300,
+            "users": [{ "name": "Alice", "credit_line": 100, "balance": 50 }],
+            "ledger": []
+        }"#;
+
+        let bank = Bank::from_json(json).unwrap();
+        assert_eq!(bank.currency, Currency::EUR);
+        assert_eq!(bank.user("Alice").unwrap().currency, Currency::EUR);
+        assert_eq!(bank.user("Alice").unwrap().balance, Money::from_minor(50));
+        assert!(bank.denials().is_empty());
+    }
+
+    #[test]
+    fn test_load_version_5_assigns_account_ids() {
+        let json = r#"{
+            "version": 5,
+            "name": "Old Bank",
+            "credit_interest": 0,
+            "debit_interest": 0,
+            "interest_carry": [["Bob", 7]],
+            "users": [
+                { "name": "Bob", "credit_line": 0, "balance": 300 },
+                { "name": "Alice", "credit_line": 0, "balance": 200 }
+            ],
+            "ledger": [
+                { "id": 1, "timestamp": "2024-01-01T00:00:00Z", "kind": "transfer",
+                  "from": "Alice", "to": "Bob", "amount": 300,
+                  "from_balance": -300, "to_balance": 300 }
+            ]
+        }"#;
+
+        let bank = Bank::from_json(json).unwrap();
+        let (alice, bob) = (bank.user("Alice").unwrap(), bank.user("Bob").unwrap());
+        assert_eq!((alice.id.clone(), alice.customer), (AccountId::new(1), 1));
+        assert_eq!((bob.id.clone(), bob.customer), (AccountId::new(2), 2));
+
+        let entry = &bank.ledger().entries()[0];
+        assert_eq!(entry.from.as_deref(), Some(alice.id.as_str()));
+        assert_eq!(entry.to.as_deref(), Some(bob.id.as_str()));
+        assert_eq!(bank.interest_carry("Bob"), 7);
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let json = sample_bank().to_json().unwrap();
+
+        assert!(matches!(
+            Bank::from_json(&json[..json.len() / 2]),
+            Err(StorageError::Json(_))
+        ));
+
+        let future = json.replace("\"version\": 10", "\"version\": 99");
+        assert!(matches!(
+            Bank::from_json(&future),
+            Err(StorageError::UnsupportedVersion(99))
+        ));
+
+        let duplicated = json.replace(AccountId::new(2).as_str(), AccountId::new(1).as_str());
+        assert!(matches!(
+            Bank::from_json(&duplicated),
+            Err(StorageError::Corrupt(_))
+        ));
+
+        let reverses = sample_bank().ledger().entries().last().unwrap().reverses;
+        let dangling = json.replace(
+            &format!("\"reverses\": {}", reverses.unwrap()),
+            "\"reverses\": 999",
+        );
+        assert!(matches!(
+            Bank::from_json(&dangling),
+            Err(StorageError::Corrupt(_))
+        ));
+
+        let mistyped = json.replace(AccountId::new(1).as_str(), "PB00000000000000001");
+        assert!(matches!(
+            Bank::from_json(&mistyped),
+            Err(StorageError::Corrupt(_))
+        ));
+    }
+}
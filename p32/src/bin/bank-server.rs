@@ -0,0 +1,423 @@
+use p32::bank::{Bank, BankError, Currency, EntryKind, Money, StorageFormat, User};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const USAGE: &str = "Usage: bank-server <bank-file> [--addr <host:port>]
+
+Serves the bank as JSON over HTTP, saving it after every change:
+  GET  /users             every account
+  GET  /users/<account>   one account, by id or name
+  POST /users             {\"name\", \"credit_line\", \"balance\", \"currency\", \"customer\"}
+  POST /transfers         {\"from\", \"to\", \"amount\"}, optionally with an Idempotency-Key header
+                          (keys are kept in <bank-file>.idempotency.json)
+  POST /interest          accrue interest on every account
+  GET  /report            balance report
+
+Amounts are in minor units.";
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8032";
+
+struct Reply {
+    status: u16,
+    body: Value,
+}
+
+struct Api {
+    bank: Bank,
+    file: String,
+    format: StorageFormat,
+    // Completed transfers by idempotency key, saved to `keys_file` so that
+    // retries are still recognised after a restart.
+    transfers: HashMap<String, StoredTransfer>,
+    keys_file: String,
+}
+
+// A completed transfer: the request it was made for and the reply sent.
+#[derive(Serialize, Deserialize)]
+struct StoredTransfer {
+    request: Value,
+    status: u16,
+    body: Value,
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    name: String,
+    #[serde(default)]
+    credit_line: Money,
+    #[serde(default)]
+    balance: Money,
+    #[serde(default)]
+    currency: Currency,
+    // Opens another account for this customer instead of a new customer.
+    customer: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct NewTransfer {
+    from: String,
+    to: String,
+    amount: Money,
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let (Some(file), addr) = (args.next(), args.next()) else {
+        eprintln!("Missing argument: bank file\n\n{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let addr = match (addr.as_deref(), args.next()) {
+        (None, _) => DEFAULT_ADDR.to_string(),
+        (Some("--addr"), Some(addr)) => addr,
+        _ => {
+            eprintln!("Invalid arguments\n\n{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let (bank, format) = match Bank::load_with_format(&file) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            eprintln!("Error: Cannot load '{}': {}", file, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let keys_file = format!("{}.idempotency.json", file);
+    let transfers = match load_transfers(&keys_file, &bank) {
+        Ok(transfers) => transfers,
+        Err(error) => {
+            eprintln!("Error: Cannot load '{}': {}", keys_file, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let server = match Server::http(&addr) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("Error: Cannot listen on {}: {}", addr, error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // With port 0 this is how callers find out which port was picked.
+    println!("Listening on http://{}", server.server_addr());
+    std::io::stdout().flush().ok();
+
+    let mut api = Api {
+        bank,
+        file,
+        format,
+        transfers,
+        keys_file,
+    };
+    for mut request in server.incoming_requests() {
+        let (reply, replayed) = api.handle(&mut request);
+        let mut response = Response::from_string(reply.body.to_string())
+            .with_status_code(reply.status)
+            .with_header(header("Content-Type", "application/json"));
+        if replayed {
+            response.add_header(header("Idempotent-Replayed", "true"));
+        }
+        if let Err(error) = request.respond(response) {
+            eprintln!("Error: Cannot respond: {}", error);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+// Keys are saved before the bank, so a key whose transfer isn't in the bank
+// file belongs to a server that stopped in between. It is dropped so the
+// transfer can be retried.
+fn load_transfers(path: &str, bank: &Bank) -> Result<HashMap<String, StoredTransfer>, String> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(error) => return Err(error.to_string()),
+    };
+    let mut transfers: HashMap<String, StoredTransfer> =
+        serde_json::from_str(&json).map_err(|error| error.to_string())?;
+
+    let ledger = bank.ledger();
+    transfers.retain(|_, stored| {
+        stored.body["entry"].as_u64().is_some_and(|id| {
+            ledger
+                .entry(id)
+                .is_some_and(|entry| entry.kind == EntryKind::Transfer && !ledger.is_reversed(id))
+        })
+    });
+    Ok(transfers)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("Header is valid")
+}
+
+impl Reply {
+    fn ok(body: Value) -> Self {
+        Reply { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Reply {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    fn bank_error(error: BankError) -> Self {
+        let status = match error {
+            BankError::UserNotFound(_) | BankError::CustomerNotFound(_) => 404,
+            BankError::DuplicateUser(_) | BankError::AmbiguousUser(_) => 409,
+            _ => 422,
+        };
+        Reply::error(status, error)
+    }
+}
+
+impl Api {
+    // Returns the reply and whether it is a stored one being replayed.
+    fn handle(&mut self, request: &mut Request) -> (Reply, bool) {
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(percent_decode)
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        let mut body = String::new();
+        if let Err(error) = request.as_reader().read_to_string(&mut body) {
+            return (Reply::error(400, error), false);
+        }
+        let key = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Idempotency-Key"))
+            .map(|header| header.value.to_string());
+
+        match (request.method(), segments.as_slice()) {
+            (Method::Get, ["users"]) => (self.users(), false),
+            (Method::Get, ["users", account]) => (self.user(account), false),
+            (Method::Post, ["users"]) => (self.create_user(&body), false),
+            (Method::Post, ["transfers"]) => self.transfer(&body, key),
+            (Method::Post, ["interest"]) => (self.accrue_interest(), false),
+            (Method::Get, ["report"]) => (self.report(), false),
+            (method, _) => (
+                Reply::error(404, format!("No route for {} {}", method, path)),
+                false,
+            ),
+        }
+    }
+
+    // Saves the bank after a change. If that fails the change is undone, so
+    // the server never answers from state that isn't on disk.
+    fn save(&mut self) -> Result<(), Reply> {
+        self.bank.save(&self.file, self.format).map_err(|error| {
+            self.bank.undo(1).ok();
+            Reply::error(500, format!("Cannot save '{}': {}", self.file, error))
+        })
+    }
+
+    fn save_transfers(&self) -> Result<(), Reply> {
+        let json =
+            serde_json::to_string(&self.transfers).map_err(|error| Reply::error(500, error))?;
+        fs::write(&self.keys_file, json).map_err(|error| {
+            Reply::error(500, format!("Cannot save '{}': {}", self.keys_file, error))
+        })
+    }
+
+    fn user_json(&self, reference: &str) -> Result<Value, Reply> {
+        let user = self.bank.user(reference).map_err(Reply::bank_error)?;
+        Ok(json!(user))
+    }
+
+    fn users(&self) -> Reply {
+        let mut users: Vec<&User> = self.bank.users.values().collect();
+        users.sort_by(|a, b| a.id.cmp(&b.id));
+        Reply::ok(json!({ "users": users }))
+    }
+
+    fn user(&self, reference: &str) -> Reply {
+        match self.user_json(reference) {
+            Ok(user) => Reply::ok(user),
+            Err(reply) => reply,
+        }
+    }
+
+    fn create_user(&mut self, body: &str) -> Reply {
+        let request: NewUser = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(error) => return Reply::error(400, error),
+        };
+
+        let user = User::new(request.name, request.credit_line, request.balance)
+            .with_currency(request.currency);
+        let opened = match request.customer {
+            Some(customer) => self.bank.open_account(user.for_customer(customer)),
+            None => self.bank.add_user(user),
+        };
+        let id = match opened {
+            Ok(id) => id,
+            Err(error) => return Reply::bank_error(error),
+        };
+        if let Err(reply) = self.save() {
+            return reply;
+        }
+
+        match self.user_json(id.as_str()) {
+            Ok(user) => Reply {
+                status: 201,
+                body: user,
+            },
+            Err(reply) => reply,
+        }
+    }
+
+    // A retry with a key seen before gets the first reply back without
+    // moving any money, as long as it is the same transfer. Only completed
+    // transfers are remembered; a failed one can simply be tried again.
+    fn transfer(&mut self, body: &str, key: Option<String>) -> (Reply, bool) {
+        let request: Value = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(error) => return (Reply::error(400, error), false),
+        };
+        if let Some(stored) = key.as_ref().and_then(|key| self.transfers.get(key)) {
+            if stored.request != request {
+                return (
+                    Reply::error(422, "Idempotency key was already used for another transfer"),
+                    false,
+                );
+            }
+            return (
+                Reply {
+                    status: stored.status,
+                    body: stored.body.clone(),
+                },
+                true,
+            );
+        }
+
+        let transfer: NewTransfer = match serde_json::from_value(request.clone()) {
+            Ok(transfer) => transfer,
+            Err(error) => return (Reply::error(400, error), false),
+        };
+        let reply = match self.execute_transfer(&transfer, key.map(|key| (key, request))) {
+            Ok(reply) | Err(reply) => reply,
+        };
+        (reply, false)
+    }
+
+    // Remembers the transfer under `key`, if there is one, in the same step
+    // that saves it.
+    fn execute_transfer(
+        &mut self,
+        transfer: &NewTransfer,
+        key: Option<(String, Value)>,
+    ) -> Result<Reply, Reply> {
+        let from = self
+            .bank
+            .account_id(&transfer.from)
+            .map_err(Reply::bank_error)?;
+        let to = self
+            .bank
+            .account_id(&transfer.to)
+            .map_err(Reply::bank_error)?;
+
+        self.bank
+            .transfer_funds(from.as_str(), to.as_str(), transfer.amount)
+            .map_err(Reply::bank_error)?;
+
+        let entry = self
+            .bank
+            .ledger()
+            .entries()
+            .last()
+            .expect("Transfer was recorded");
+        let body = json!({
+            "entry": entry.id,
+            "from": self.user_json(from.as_str())?,
+            "to": self.user_json(to.as_str())?,
+            "amount": transfer.amount,
+            "credited": entry.credited_amount(),
+        });
+
+        if let Some((key, request)) = key {
+            let stored = StoredTransfer {
+                request,
+                status: 200,
+                body: body.clone(),
+            };
+            self.transfers.insert(key.clone(), stored);
+            let saved = match self.save_transfers() {
+                Ok(()) => self.save(),
+                Err(reply) => {
+                    self.bank.undo(1).ok();
+                    Err(reply)
+                }
+            };
+            if let Err(reply) = saved {
+                self.transfers.remove(&key);
+                self.save_transfers().ok();
+                return Err(reply);
+            }
+        } else {
+            self.save()?;
+        }
+        Ok(Reply::ok(body))
+    }
+
+    fn accrue_interest(&mut self) -> Reply {
+        let first_entry = self.bank.ledger().len();
+        if let Err(error) = self.bank.accrue_interest() {
+            return Reply::bank_error(error);
+        }
+        if let Err(reply) = self.save() {
+            return reply;
+        }
+
+        let accruals = &self.bank.ledger().entries()[first_entry..];
+        Reply::ok(json!({ "accruals": accruals }))
+    }
+
+    fn report(&self) -> Reply {
+        match self.bank.report() {
+            Ok(report) => Reply::ok(json!(report)),
+            Err(error) => Reply::bank_error(error),
+        }
+    }
+}
+
+// Decodes `%XX` escapes in a path segment, leaving anything malformed as is.
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| segment.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,935 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use p32::bank::{
+    Amortization, Bank, ConflictPolicy, Currency, InstallmentOutcome, InstallmentRecord, KycStatus,
+    Loan, MergePolicy, Money, OverdraftFee, OverdraftPolicy, PaymentOutcome, PaymentRecord,
+    RetryPolicy, Schedule, SoftOverdraft, StandingOrder, StorageFormat, User,
+};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: bank <command> <bank-file> [arguments] [--json]
+
+Commands:
+  create <file> --name <name> [--credit-interest <bp>] [--debit-interest <bp>]
+         [--currency <EUR|USD|AED>] [--format <json|binary>]
+  add-user <file> <name> [--credit-line <amount>] [--balance <amount>] [--currency <code>]
+  open-account <file> <name> [--customer <number>] [--credit-line <amount>]
+               [--balance <amount>] [--currency <code>]
+  rename <file> <account> <name>
+  transfer <file> <from> <to> <amount>
+  accrue-interest <file>
+  set-rate <file> <from-currency> <to-currency> <rate>
+  merge <file> <other-file> [--conflicts <combine|rename|fail>] [--prefix <prefix>]
+        [--credit-lines <keep|max|min|sum>] [--interest <average|weighted|keep>] [--dry-run]
+  report <file>
+  statement <file> <user> [--from <YYYY-MM-DD>] [--to <YYYY-MM-DD>] [--format <text|csv|json>]
+  add-order <file> <from> <to> <amount> --schedule <once|daily|weekly|monthly:<day>|end-of-month>
+            --start <YYYY-MM-DD> [--end <YYYY-MM-DD>] [--retries <n>] [--retry-days <days>]
+  cancel-order <file> <id>
+  run-orders <file> --until <YYYY-MM-DD>
+  freeze <file> <user>
+  unfreeze <file> <user>
+  kyc <file> <user> <pending|verified|rejected>
+  close <file> <user> [--settle-to <user>]
+  set-overdraft <file> <user> <hard|soft> [--limit <amount>]
+                [--fee <amount> | --fee-percent <bp>] [--grace <amount>] [--penalty-rate <bp>]
+  grant-loan <file> <account> <amount> --rate <bp> --term <months>
+             [--amortization <annuity|linear>] [--start <YYYY-MM-DD>]
+  repay-loan <file> <id> <amount> [--date <YYYY-MM-DD>]
+  collect-loans <file> --until <YYYY-MM-DD>
+  loans <file> [<account>]
+  loan <file> <id>
+  trial-balance <file>
+  balance-at <file> <account> (--entry <id> | --at <YYYY-MM-DD|RFC 3339 time>)
+  import <file> <input> --format <users-csv|transfers-csv|mt940> [--dry-run]";
+
+struct Args {
+    command: String,
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    json: bool,
+    dry_run: bool,
+}
+
+struct Output {
+    text: String,
+    json: Value,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(output) => {
+            if args.json {
+                println!("{}", output.json);
+            } else {
+                println!("{}", output.text);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            if args.json {
+                println!("{}", json!({ "error": message }));
+            } else {
+                eprintln!("Error: {}", message);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut json = false;
+    let mut dry_run = false;
+
+    while let Some(arg) = args.next() {
+        if arg == "--json" {
+            json = true;
+        } else if arg == "--dry-run" {
+            dry_run = true;
+        } else if let Some(key) = arg.strip_prefix("--") {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for option '--{}'", key))?;
+            options.insert(key.to_string(), value);
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    if positional.is_empty() {
+        return Err("Missing command".to_string());
+    }
+    let command = positional.remove(0);
+    // Only these can show what they would do without saving it.
+    if dry_run && !matches!(command.as_str(), "merge" | "import") {
+        return Err(format!("'{}' doesn't support --dry-run", command));
+    }
+
+    Ok(Args {
+        command,
+        positional,
+        options,
+        json,
+        dry_run,
+    })
+}
+
+fn run(args: &Args) -> Result<Output, String> {
+    let file = positional(args, 0, "bank file")?;
+
+    match args.command.as_str() {
+        "create" => create(args, file),
+        "add-user" | "open-account" => add_user(args, file),
+        "rename" => rename(args, file),
+        "transfer" => transfer(args, file),
+        "accrue-interest" => accrue_interest(file),
+        "set-rate" => set_rate(args, file),
+        "merge" => merge(args, file),
+        "report" => {
+            let (bank, _) = load(file)?;
+            report(&bank)
+        }
+        "statement" => statement(args, file),
+        "add-order" => add_order(args, file),
+        "cancel-order" => cancel_order(args, file),
+        "run-orders" => run_orders(args, file),
+        "freeze" | "unfreeze" | "kyc" => account_status(args, file),
+        "close" => close(args, file),
+        "set-overdraft" => set_overdraft(args, file),
+        "grant-loan" => grant_loan(args, file),
+        "repay-loan" => repay_loan(args, file),
+        "collect-loans" => collect_loans(args, file),
+        "loans" | "loan" => loans(args, file),
+        "trial-balance" => trial_balance(file),
+        "balance-at" => balance_at(args, file),
+        "import" => import(args, file),
+        command => Err(format!("Unknown command '{}'", command)),
+    }
+}
+
+fn positional<'a>(args: &'a Args, index: usize, what: &str) -> Result<&'a str, String> {
+    args.positional
+        .get(index)
+        .map(String::as_str)
+        .ok_or_else(|| format!("Missing argument: {}", what))
+}
+
+fn parse<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {}: '{}'", what, value))
+}
+
+fn option<T: std::str::FromStr>(args: &Args, key: &str, default: T) -> Result<T, String> {
+    match args.options.get(key) {
+        Some(value) => parse(value, key),
+        None => Ok(default),
+    }
+}
+
+fn load(file: &str) -> Result<(Bank, StorageFormat), String> {
+    Bank::load_with_format(file).map_err(|error| format!("Cannot load '{}': {}", file, error))
+}
+
+fn save(bank: &Bank, file: &str, format: StorageFormat) -> Result<(), String> {
+    bank.save(file, format)
+        .map_err(|error| format!("Cannot save '{}': {}", file, error))
+}
+
+fn create(args: &Args, file: &str) -> Result<Output, String> {
+    let name = args
+        .options
+        .get("name")
+        .ok_or_else(|| "Missing option '--name'".to_string())?;
+    let format = option(args, "format", StorageFormat::Json)?;
+
+    let mut bank = Bank::new(
+        name.clone(),
+        option(args, "credit-interest", 0)?,
+        option(args, "debit-interest", 0)?,
+    );
+    bank.currency = option(args, "currency", Currency::default())?;
+
+    save(&bank, file, format)?;
+
+    Ok(Output {
+        text: format!("Created {}", bank),
+        json: json!({ "created": bank.name }),
+    })
+}
+
+fn add_user(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let name = positional(args, 1, "user name")?;
+
+    let user = User::new(
+        name.to_string(),
+        option(args, "credit-line", Money::ZERO)?,
+        option(args, "balance", Money::ZERO)?,
+    )
+    .with_currency(option(args, "currency", bank.currency)?);
+
+    // add-user insists on a new holder name; open-account allows several
+    // accounts per name and per customer.
+    let id = if args.command == "add-user" {
+        bank.add_user(user)
+    } else {
+        bank.open_account(user.for_customer(option(args, "customer", 0)?))
+    }
+    .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let user = user_of(&bank, id.as_str())?;
+    Ok(Output {
+        text: format!("Added {} as {} (customer {})", user, id, user.customer),
+        json: user_json(user),
+    })
+}
+
+fn rename(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let account = account_id(&bank, positional(args, 1, "account")?)?;
+    let name = positional(args, 2, "new name")?;
+
+    bank.rename_account(&account, name)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let user = user_of(&bank, &account)?;
+    Ok(Output {
+        text: format!("Renamed {} to {}", account, name),
+        json: user_json(user),
+    })
+}
+
+// Resolved up front so later lookups still work after the command changes
+// the account (a rename, or closing one of several same-named accounts).
+fn account_id(bank: &Bank, reference: &str) -> Result<String, String> {
+    bank.account_id(reference)
+        .map(|id| id.to_string())
+        .map_err(|error| error.to_string())
+}
+
+fn user_of<'a>(bank: &'a Bank, reference: &str) -> Result<&'a User, String> {
+    bank.user(reference).map_err(|error| error.to_string())
+}
+
+fn transfer(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let from = positional(args, 1, "sender")?;
+    let to = positional(args, 2, "receiver")?;
+    let amount: Money = parse(positional(args, 3, "amount")?, "amount")?;
+    let (from_id, to_id) = (account_id(&bank, from)?, account_id(&bank, to)?);
+
+    bank.transfer_funds(&from_id, &to_id, amount)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let entry = bank
+        .ledger()
+        .entries()
+        .last()
+        .expect("Transfer was recorded");
+    let (from_user, to_user) = (user_of(&bank, &from_id)?, user_of(&bank, &to_id)?);
+
+    Ok(Output {
+        text: format!(
+            "Transferred {} from {} to {}\n{}\n{}",
+            amount.format(from_user.currency),
+            from,
+            to,
+            from_user,
+            to_user
+        ),
+        json: json!({
+            "entry": entry.id,
+            "from": user_json(from_user),
+            "to": user_json(to_user),
+            "amount": amount,
+            "credited": entry.credited_amount(),
+        }),
+    })
+}
+
+fn accrue_interest(file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let first_entry = bank.ledger().len();
+
+    bank.accrue_interest().map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let accruals = &bank.ledger().entries()[first_entry..];
+    let text = accruals
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Output {
+        text: format!("Recorded {} interest entries\n{}", accruals.len(), text)
+            .trim_end()
+            .to_string(),
+        json: json!({ "accruals": accruals }),
+    })
+}
+
+fn set_rate(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let from: Currency = parse(positional(args, 1, "source currency")?, "currency")?;
+    let to: Currency = parse(positional(args, 2, "target currency")?, "currency")?;
+    let rate: u64 = parse(positional(args, 3, "rate")?, "rate")?;
+
+    bank.exchange_rates.set_rate(from, to, rate);
+    save(&bank, file, format)?;
+
+    Ok(Output {
+        text: format!("Set {} -> {} rate to {}", from, to, rate),
+        json: json!({ "from": from, "to": to, "rate": rate }),
+    })
+}
+
+fn merge(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let other_file = positional(args, 1, "other bank file")?;
+    let (other, _) = load(other_file)?;
+
+    let conflicts = match args.options.get("conflicts").map(String::as_str) {
+        None | Some("combine") => ConflictPolicy::Combine,
+        Some("fail") => ConflictPolicy::Fail,
+        Some("rename") => ConflictPolicy::Rename {
+            prefix: args
+                .options
+                .get("prefix")
+                .cloned()
+                .ok_or_else(|| "Missing option '--prefix'".to_string())?,
+        },
+        Some(policy) => return Err(format!("Unknown conflict policy '{}'", policy)),
+    };
+    let policy = MergePolicy {
+        conflicts,
+        credit_lines: option(args, "credit-lines", Default::default())?,
+        interest: option(args, "interest", Default::default())?,
+    };
+
+    let plan = if args.dry_run {
+        bank.plan_merge(&other, &policy)
+    } else {
+        bank.merge_bank_with(other, &policy)
+    }
+    .map_err(|error| error.to_string())?;
+
+    let mut text = plan.to_string();
+    if !args.dry_run {
+        save(&bank, file, format)?;
+        text.push_str(&format!("\nMerged {} into {}", plan.source, bank));
+    }
+
+    Ok(Output {
+        text,
+        json: json!({
+            "merged": !args.dry_run,
+            "plan": plan,
+            "credit_interest": bank.credit_interest,
+            "debit_interest": bank.debit_interest,
+            "users": bank.users.len(),
+        }),
+    })
+}
+
+fn report(bank: &Bank) -> Result<Output, String> {
+    let (liabilities, assets) = bank
+        .calc_balance_in(bank.currency)
+        .map_err(|error| error.to_string())?;
+
+    let mut users: Vec<&User> = bank.users.values().collect();
+    users.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+
+    let mut text = format!("{}\n", bank);
+    for user in &users {
+        text.push_str(&format!("  {}  {}\n", user.id, user));
+    }
+    text.push_str(&format!(
+        "Liabilities: {}\nAssets: {}",
+        liabilities.format(bank.currency),
+        assets.format(bank.currency)
+    ));
+
+    Ok(Output {
+        text,
+        json: json!({
+            "name": bank.name,
+            "currency": bank.currency,
+            "credit_interest": bank.credit_interest,
+            "debit_interest": bank.debit_interest,
+            "users": users.iter().map(|user| user_json(user)).collect::<Vec<_>>(),
+            "liabilities": liabilities,
+            "assets": assets,
+        }),
+    })
+}
+
+fn user_json(user: &User) -> Value {
+    json!({
+        "id": user.id,
+        "name": user.name,
+        "customer": user.customer,
+        "credit_line": user.credit_line,
+        "balance": user.balance,
+        "currency": user.currency,
+        "status": user.status,
+        "kyc": user.kyc,
+        "overdraft": user.overdraft,
+    })
+}
+
+fn statement(args: &Args, file: &str) -> Result<Output, String> {
+    let (bank, _) = load(file)?;
+    let user = positional(args, 1, "user name")?;
+
+    let from = option(args, "from", NaiveDate::from_ymd_opt(1970, 1, 1).unwrap())?;
+    let to = option(
+        args,
+        "to",
+        Utc::now().date_naive().succ_opt().unwrap_or(NaiveDate::MAX),
+    )?;
+
+    let statement = bank
+        .statement(user, from, to)
+        .map_err(|error| error.to_string())?;
+    let json = serde_json::to_value(&statement).map_err(|error| error.to_string())?;
+
+    let text = match args.options.get("format").map(String::as_str) {
+        None | Some("text") => statement.to_text(),
+        Some("csv") => statement.to_csv().trim_end().to_string(),
+        Some("json") => json.to_string(),
+        Some(format) => return Err(format!("Unknown statement format '{}'", format)),
+    };
+
+    Ok(Output { text, json })
+}
+
+fn required<T: std::str::FromStr>(args: &Args, key: &str) -> Result<T, String> {
+    let value = args
+        .options
+        .get(key)
+        .ok_or_else(|| format!("Missing option '--{}'", key))?;
+    parse(value, key)
+}
+
+fn order_json(order: &StandingOrder) -> Value {
+    json!({
+        "id": order.id,
+        "from": order.from,
+        "to": order.to,
+        "amount": order.amount,
+        "schedule": order.schedule.to_string(),
+        "start": order.start,
+        "end": order.end,
+        "next_due": order.next_due(),
+    })
+}
+
+fn add_order(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let from = positional(args, 1, "sender")?;
+    let to = positional(args, 2, "receiver")?;
+    let amount: Money = parse(positional(args, 3, "amount")?, "amount")?;
+    let schedule: Schedule = required(args, "schedule")?;
+
+    let mut order = StandingOrder::new(from, to, amount, schedule, required(args, "start")?)
+        .with_retry(RetryPolicy {
+            max_retries: option(args, "retries", 0)?,
+            interval_days: option(args, "retry-days", 1)?,
+        });
+    if args.options.contains_key("end") {
+        order = order.until(required(args, "end")?);
+    }
+
+    let id = bank
+        .add_standing_order(order)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let order = bank
+        .standing_orders()
+        .iter()
+        .find(|order| order.id == id)
+        .expect("Standing order was added");
+    let next_due = order
+        .next_due()
+        .map_or_else(|| "never".to_string(), |date| date.to_string());
+
+    Ok(Output {
+        text: format!(
+            "Added standing order #{}: {} from {} to {} ({}), next due {}",
+            id,
+            amount.format(user_of(&bank, &order.from)?.currency),
+            from,
+            to,
+            schedule,
+            next_due
+        ),
+        json: order_json(order),
+    })
+}
+
+fn cancel_order(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let id: u64 = parse(positional(args, 1, "order id")?, "order id")?;
+
+    let order = bank
+        .cancel_standing_order(id)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    Ok(Output {
+        text: format!("Cancelled standing order #{}", id),
+        json: order_json(&order),
+    })
+}
+
+fn payment_json(record: &PaymentRecord) -> Value {
+    let mut json = json!({
+        "order": record.order_id,
+        "due": record.due,
+        "executed_on": record.executed_on,
+        "attempt": record.attempt,
+    });
+    match &record.outcome {
+        PaymentOutcome::Paid { entry_id } => {
+            json["status"] = json!("paid");
+            json["entry"] = json!(entry_id);
+        }
+        PaymentOutcome::Failed { error, retry_on } => {
+            json["status"] = json!("failed");
+            json["error"] = json!(error.to_string());
+            json["retry_on"] = json!(retry_on);
+        }
+    }
+    json
+}
+
+fn run_orders(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let until: NaiveDate = required(args, "until")?;
+
+    let records = bank.run_until(until);
+    save(&bank, file, format)?;
+
+    let paid = records
+        .iter()
+        .filter(|record| matches!(record.outcome, PaymentOutcome::Paid { .. }))
+        .count();
+    let mut text = format!(
+        "Ran standing orders until {}: {} paid, {} failed",
+        until,
+        paid,
+        records.len() - paid
+    );
+    for record in &records {
+        text.push_str(&format!("\n  {}", record));
+    }
+
+    Ok(Output {
+        text,
+        json: json!({
+            "until": until,
+            "payments": records.iter().map(payment_json).collect::<Vec<_>>(),
+        }),
+    })
+}
+
+fn account_status(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let name = positional(args, 1, "user name")?;
+    let id = account_id(&bank, name)?;
+
+    match args.command.as_str() {
+        "freeze" => bank.freeze_account(&id),
+        "unfreeze" => bank.unfreeze_account(&id),
+        _ => {
+            let kyc: KycStatus = parse(positional(args, 2, "KYC status")?, "KYC status")?;
+            bank.set_kyc_status(&id, kyc)
+        }
+    }
+    .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let user = user_of(&bank, &id)?;
+    Ok(Output {
+        text: format!("{}: account {}, KYC {}", name, user.status, user.kyc),
+        json: user_json(user),
+    })
+}
+
+fn close(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let name = positional(args, 1, "user name")?;
+    let settle_to = args.options.get("settle-to").map(String::as_str);
+    let id = account_id(&bank, name)?;
+
+    let entry_id = bank
+        .close_account(&id, settle_to)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let mut text = format!("Closed account {}", name);
+    if let Some(entry) = entry_id.map(|id| &bank.ledger().entries()[id as usize - 1]) {
+        text.push_str(&format!("\n{}", entry));
+    }
+
+    Ok(Output {
+        text,
+        json: json!({
+            "closed": user_json(user_of(&bank, &id)?),
+            "settlement": entry_id,
+        }),
+    })
+}
+
+fn set_overdraft(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let name = positional(args, 1, "user name")?;
+    let id = account_id(&bank, name)?;
+
+    let policy = match positional(args, 2, "overdraft policy")? {
+        "hard" => OverdraftPolicy::Hard,
+        "soft" => {
+            let fee = match (args.options.get("fee"), args.options.get("fee-percent")) {
+                (Some(_), Some(_)) => {
+                    return Err("Use either '--fee' or '--fee-percent', not both".to_string());
+                }
+                (_, Some(rate)) => OverdraftFee::Percentage(parse(rate, "fee-percent")?),
+                (fee, None) => {
+                    OverdraftFee::Fixed(fee.map_or(Ok(Money::ZERO), |fee| parse(fee, "fee"))?)
+                }
+            };
+            OverdraftPolicy::Soft(SoftOverdraft {
+                limit: option(args, "limit", Money::ZERO)?,
+                fee,
+                grace: option(args, "grace", Money::ZERO)?,
+                penalty_rate: option(args, "penalty-rate", 0)?,
+            })
+        }
+        policy => return Err(format!("Invalid overdraft policy: '{}'", policy)),
+    };
+
+    bank.set_overdraft_policy(&id, policy)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let user = user_of(&bank, &id)?;
+    Ok(Output {
+        text: format!("{}: overdraft {}", name, user.overdraft),
+        json: user_json(user),
+    })
+}
+
+fn loan_json(bank: &Bank, loan: &Loan, schedule: bool) -> Value {
+    let mut json = json!({
+        "id": loan.id,
+        "account": loan.account,
+        "principal": loan.principal,
+        "rate": loan.rate,
+        "term_months": loan.term_months,
+        "amortization": loan.amortization,
+        "start": loan.start,
+        "outstanding": loan.outstanding(),
+        "principal_repaid": loan.principal_repaid(),
+        "interest_paid": loan.interest_paid(),
+        "installments_paid": loan.installments_paid(),
+        "next_due": loan.next_due(),
+    });
+    if schedule {
+        json["schedule"] = json!(loan.schedule(bank.interest_config.rounding));
+    }
+    json
+}
+
+fn grant_loan(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let account = positional(args, 1, "account")?;
+    let amount: Money = parse(positional(args, 2, "amount")?, "amount")?;
+
+    let loan = Loan::new(
+        account,
+        amount,
+        required(args, "rate")?,
+        required(args, "term")?,
+        option(args, "amortization", Amortization::default())?,
+        option(args, "start", Utc::now().date_naive())?,
+    );
+    let id = bank.grant_loan(loan).map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let loan = bank.loan(id).map_err(|error| error.to_string())?;
+    let currency = user_of(&bank, &loan.account)?.currency;
+    let first = loan.schedule(bank.interest_config.rounding)[0];
+    Ok(Output {
+        text: format!(
+            "Granted loan #{} of {} to {} over {} months ({}), first installment {} due {}",
+            id,
+            amount.format(currency),
+            account,
+            loan.term_months,
+            loan.amortization,
+            first.payment().format(currency),
+            first.due
+        ),
+        json: loan_json(&bank, loan, true),
+    })
+}
+
+fn repay_loan(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let id: u64 = parse(positional(args, 1, "loan id")?, "loan id")?;
+    let amount: Money = parse(positional(args, 2, "amount")?, "amount")?;
+    let date = option(args, "date", Utc::now().date_naive())?;
+
+    let entry_id = bank
+        .repay_loan(id, amount, date)
+        .map_err(|error| error.to_string())?;
+    save(&bank, file, format)?;
+
+    let entry = &bank.ledger().entries()[entry_id as usize - 1];
+    let loan = bank.loan(id).map_err(|error| error.to_string())?;
+    Ok(Output {
+        text: format!(
+            "Repaid {} of loan #{}, {} outstanding\n{}",
+            entry.amount,
+            id,
+            loan.outstanding(),
+            entry
+        ),
+        json: json!({ "entry": entry_id, "loan": loan_json(&bank, loan, false) }),
+    })
+}
+
+fn installment_json(record: &InstallmentRecord) -> Value {
+    let mut json = json!({
+        "loan": record.loan_id,
+        "installment": record.installment,
+    });
+    match &record.outcome {
+        InstallmentOutcome::Paid { entry_id } => {
+            json["status"] = json!("paid");
+            json["entry"] = json!(entry_id);
+        }
+        InstallmentOutcome::Missed { error } => {
+            json["status"] = json!("missed");
+            json["error"] = json!(error.to_string());
+        }
+    }
+    json
+}
+
+fn collect_loans(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let until: NaiveDate = required(args, "until")?;
+
+    let records = bank.collect_installments(until);
+    save(&bank, file, format)?;
+
+    let paid = records
+        .iter()
+        .filter(|record| matches!(record.outcome, InstallmentOutcome::Paid { .. }))
+        .count();
+    let mut text = format!(
+        "Collected installments until {}: {} paid, {} missed",
+        until,
+        paid,
+        records.len() - paid
+    );
+    for record in &records {
+        text.push_str(&format!("\n  {}", record));
+    }
+
+    Ok(Output {
+        text,
+        json: json!({
+            "until": until,
+            "installments": records.iter().map(installment_json).collect::<Vec<_>>(),
+        }),
+    })
+}
+
+fn loans(args: &Args, file: &str) -> Result<Output, String> {
+    let (bank, _) = load(file)?;
+    // `loan` shows a single loan along with its remaining installments.
+    let schedule = args.command == "loan";
+    let loans: Vec<&Loan> = if schedule {
+        let id: u64 = parse(positional(args, 1, "loan id")?, "loan id")?;
+        vec![bank.loan(id).map_err(|error| error.to_string())?]
+    } else if let Some(account) = args.positional.get(1) {
+        bank.loans_of(account).map_err(|error| error.to_string())?
+    } else {
+        bank.loans().iter().collect()
+    };
+
+    let mut text = String::new();
+    for loan in &loans {
+        let currency = user_of(&bank, &loan.account)?.currency;
+        text.push_str(&format!(
+            "Loan #{} to {}: {} at {}bp over {} months ({}), {} outstanding, {} interest paid, {} of {} installments paid\n",
+            loan.id,
+            loan.account,
+            loan.principal.format(currency),
+            loan.rate,
+            loan.term_months,
+            loan.amortization,
+            loan.outstanding().format(currency),
+            loan.interest_paid().format(currency),
+            loan.installments_paid(),
+            loan.term_months
+        ));
+        if schedule {
+            for installment in loan.schedule(bank.interest_config.rounding) {
+                text.push_str(&format!("  {}\n", installment));
+            }
+        }
+    }
+    if loans.is_empty() {
+        text.push_str("No loans\n");
+    }
+
+    Ok(Output {
+        text: text.trim_end().to_string(),
+        json: json!({
+            "loans": loans
+                .iter()
+                .map(|loan| loan_json(&bank, loan, schedule))
+                .collect::<Vec<_>>(),
+        }),
+    })
+}
+
+fn trial_balance(file: &str) -> Result<Output, String> {
+    let (bank, _) = load(file)?;
+    let trial_balance = bank.verify_books().map_err(|error| error.to_string())?;
+
+    Ok(Output {
+        text: trial_balance.to_string().trim_end().to_string(),
+        json: serde_json::to_value(&trial_balance).map_err(|error| error.to_string())?,
+    })
+}
+
+// A bare date means the end of that day.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.to_utc())
+        .or_else(|_| {
+            let end_of_day = NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999)
+                .expect("End of day is a valid time");
+            value
+                .parse::<NaiveDate>()
+                .map(|date| date.and_time(end_of_day).and_utc())
+        })
+        .map_err(|_| format!("Invalid time: '{}'", value))
+}
+
+fn balance_at(args: &Args, file: &str) -> Result<Output, String> {
+    let (bank, _) = load(file)?;
+    let account = positional(args, 1, "account")?;
+    let user = user_of(&bank, account)?;
+
+    let (balance, point) = match (args.options.get("entry"), args.options.get("at")) {
+        (Some(entry), None) => {
+            let entry: u64 = parse(entry, "entry")?;
+            (bank.balance_at(account, entry), format!("entry #{}", entry))
+        }
+        (None, Some(at)) => {
+            let at = parse_time(at)?;
+            (bank.balance_at_time(account, at), at.to_rfc3339())
+        }
+        _ => return Err("Expected exactly one of '--entry' and '--at'".to_string()),
+    };
+    let balance = balance.map_err(|error| error.to_string())?;
+
+    Ok(Output {
+        text: match balance {
+            Some(balance) => format!(
+                "Balance of {} at {}: {}",
+                account,
+                point,
+                balance.format(user.currency)
+            ),
+            None => format!("{} wasn't open yet at {}", account, point),
+        },
+        json: json!({ "account": user.id, "at": point, "balance": balance }),
+    })
+}
+
+// Rows that fail are listed in the output; the rest are imported.
+fn import(args: &Args, file: &str) -> Result<Output, String> {
+    let (mut bank, format) = load(file)?;
+    let input = positional(args, 1, "input file")?;
+    let content = std::fs::read_to_string(input)
+        .map_err(|error| format!("Cannot read '{}': {}", input, error))?;
+
+    let report = match required::<String>(args, "format")?.as_str() {
+        "users-csv" => bank.import_users_csv(&content),
+        "transfers-csv" => bank.import_transfers_csv(&content),
+        "mt940" => bank.import_mt940(&content),
+        format => return Err(format!("Unknown import format '{}'", format)),
+    };
+    if !args.dry_run {
+        save(&bank, file, format)?;
+    }
+
+    Ok(Output {
+        text: report.to_string(),
+        json: serde_json::to_value(&report).map_err(|error| error.to_string())?,
+    })
+}
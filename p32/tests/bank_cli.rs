@@ -0,0 +1,532 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn bank(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bank"))
+        .args(args)
+        .output()
+        .expect("Failed to run bank binary")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_cli_workflow() {
+    let path = temp_file("p32_cli_workflow.bin");
+    let file = path.to_str().unwrap();
+
+    let output = bank(&[
+        "create",
+        file,
+        "--name",
+        "CLI Bank",
+        "--credit-interest",
+        "500",
+        "--debit-interest",
+        "300",
+        "--format",
+        "binary",
+    ]);
+    assert!(output.status.success());
+
+    assert!(
+        bank(&["add-user", file, "Alice", "--balance", "1,000"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", file, "Bob", "--credit-line", "500"])
+            .status
+            .success()
+    );
+
+    // Adding a duplicate user fails without touching the file
+    let output = bank(&["add-user", file, "Bob"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    let output = bank(&["transfer", file, "Bob", "Alice", "400", "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["from"]["balance"], -40_000);
+    assert_eq!(json["to"]["balance"], 140_000);
+
+    assert!(bank(&["accrue-interest", file]).status.success());
+
+    let output = bank(&["report", file]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.contains("Bank: CLI Bank"));
+    assert!(text.contains("Liabilities: €1,442.00"));
+    assert!(text.contains("Assets: €420.00"));
+
+    let output = bank(&["statement", file, "Bob", "--format", "csv"]);
+    assert!(output.status.success());
+    let csv = stdout(&output);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[2].ends_with(",transfer,Alice,-400.00,-400.00"));
+    assert!(lines[3].ends_with(",interest,,-20.00,-420.00"));
+
+    let output = bank(&["transfer", file, "Bob", "Alice", "1000", "--json"]);
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert!(
+        json["error"]
+            .as_str()
+            .unwrap()
+            .contains("Insufficient credit")
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_merge() {
+    let first_path = temp_file("p32_cli_merge_1.json");
+    let second_path = temp_file("p32_cli_merge_2.json");
+    let first = first_path.to_str().unwrap();
+    let second = second_path.to_str().unwrap();
+
+    assert!(
+        bank(&[
+            "create",
+            first,
+            "--name",
+            "First",
+            "--credit-interest",
+            "400"
+        ])
+        .status
+        .success()
+    );
+    assert!(
+        bank(&[
+            "create",
+            second,
+            "--name",
+            "Second",
+            "--credit-interest",
+            "600"
+        ])
+        .status
+        .success()
+    );
+    assert!(
+        bank(&["add-user", first, "Alice", "--balance", "100"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", second, "Alice", "--balance", "€50"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", second, "Carol", "--balance", "10"])
+            .status
+            .success()
+    );
+
+    let output = bank(&["merge", first, second, "--conflicts", "fail", "--json"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("Alice"));
+
+    let output = bank(&["merge", first, second, "--dry-run", "--credit-lines", "sum"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("~ Alice: 100.00 -> 150.00 EUR"));
+    assert!(stdout(&output).contains("+ Carol: 10.00 EUR"));
+
+    let output = bank(&["merge", first, second, "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["credit_interest"], 500);
+    assert_eq!(json["users"], 2);
+
+    let output = bank(&["report", first, "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["users"][0]["name"], "Alice");
+    assert_eq!(json["users"][0]["balance"], 15_000);
+    assert_eq!(json["liabilities"], 16_000);
+
+    std::fs::remove_file(&first_path).unwrap();
+    std::fs::remove_file(&second_path).unwrap();
+}
+
+#[test]
+fn test_cli_standing_orders() {
+    let path = temp_file("p32_cli_orders.json");
+    let file = path.to_str().unwrap();
+
+    assert!(bank(&["create", file, "--name", "Orders"]).status.success());
+    assert!(
+        bank(&["add-user", file, "Alice", "--balance", "1,500"])
+            .status
+            .success()
+    );
+    assert!(bank(&["add-user", file, "Bob"]).status.success());
+
+    let output = bank(&[
+        "add-order",
+        file,
+        "Alice",
+        "Bob",
+        "1000",
+        "--schedule",
+        "monthly:31",
+        "--start",
+        "2026-01-10",
+        "--retries",
+        "1",
+        "--retry-days",
+        "5",
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("next due 2026-01-31"));
+
+    let output = bank(&["run-orders", file, "--until", "2026-03-01", "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let payments = json["payments"].as_array().unwrap();
+    assert_eq!(payments.len(), 2);
+    assert_eq!(payments[0]["status"], "paid");
+    assert_eq!(payments[1]["status"], "failed");
+    assert_eq!(payments[1]["due"], "2026-02-28");
+    assert_eq!(payments[1]["retry_on"], "2026-03-05");
+
+    let output = bank(&["cancel-order", file, "1"]);
+    assert!(output.status.success());
+    let output = bank(&["run-orders", file, "--until", "2026-12-31"]);
+    assert!(stdout(&output).contains("0 paid, 0 failed"));
+
+    let output = bank(&[
+        "add-order",
+        file,
+        "Alice",
+        "Bob",
+        "10",
+        "--start",
+        "2026-01-01",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--schedule"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_account_lifecycle() {
+    let path = temp_file("p32_cli_lifecycle.json");
+    let file = path.to_str().unwrap();
+
+    assert!(
+        bank(&["create", file, "--name", "Lifecycle"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", file, "Alice", "--balance", "25"])
+            .status
+            .success()
+    );
+    assert!(bank(&["add-user", file, "Bob"]).status.success());
+
+    assert!(bank(&["freeze", file, "Bob"]).status.success());
+    let output = bank(&["transfer", file, "Alice", "Bob", "5"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'Bob' is frozen"));
+
+    let output = bank(&["kyc", file, "Bob", "verified", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["status"], "frozen");
+    assert_eq!(json["kyc"], "verified");
+    assert!(bank(&["unfreeze", file, "Bob"]).status.success());
+
+    let output = bank(&["close", file, "Alice"]);
+    assert!(!output.status.success());
+    let output = bank(&["close", file, "Alice", "--settle-to", "Bob", "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["closed"]["status"], "closed");
+    assert_eq!(json["settlement"], 3);
+
+    let output = bank(&["report", file]);
+    let text = stdout(&output);
+    assert!(text.contains("User: Alice, Credit Line: €0.00, Balance: €0.00 (closed)"));
+    assert!(text.contains("Liabilities: €25.00"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_multiple_accounts() {
+    let path = temp_file("p32_cli_accounts.json");
+    let file = path.to_str().unwrap();
+
+    assert!(
+        bank(&["create", file, "--name", "Accounts"])
+            .status
+            .success()
+    );
+    let output = bank(&["add-user", file, "Alice", "--balance", "100", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["id"], "PB0700000000000001");
+    assert_eq!(json["customer"], 1);
+
+    let output = bank(&["open-account", file, "Alice", "--customer", "1", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let savings = json["id"].as_str().unwrap().to_string();
+    assert_eq!(json["customer"], 1);
+
+    // The name alone no longer says which account is meant.
+    let output = bank(&["transfer", file, "Alice", &savings, "40"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Several accounts are named 'Alice'"));
+
+    assert!(
+        bank(&["rename", file, &savings, "Alice Savings"])
+            .status
+            .success()
+    );
+    let output = bank(&["transfer", file, "Alice", "Alice Savings", "40", "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["to"]["id"], savings.as_str());
+    assert_eq!(json["to"]["balance"], 4_000);
+
+    let text = stdout(&bank(&["report", file]));
+    assert!(text.contains(&format!("{}  User: Alice Savings", savings)));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_loans() {
+    let path = temp_file("p32_cli_loans.json");
+    let file = path.to_str().unwrap();
+
+    assert!(bank(&["create", file, "--name", "Lender"]).status.success());
+    assert!(bank(&["add-user", file, "Alice"]).status.success());
+
+    let output = bank(&[
+        "grant-loan",
+        file,
+        "Alice",
+        "1,200",
+        "--rate",
+        "1200",
+        "--term",
+        "12",
+        "--start",
+        "2026-01-31",
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("first installment €106.62 due 2026-02-28"));
+
+    let output = bank(&["collect-loans", file, "--until", "2026-03-31", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["installments"].as_array().unwrap().len(), 2);
+    assert_eq!(json["installments"][0]["status"], "paid");
+    assert_eq!(json["installments"][0]["installment"]["interest"], 1_200);
+
+    let output = bank(&["repay-loan", file, "1", "500", "--date", "2026-04-01"]);
+    assert!(output.status.success());
+
+    let output = bank(&["loan", file, "1", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let loan = &json["loans"][0];
+    assert_eq!(loan["installments_paid"], 2);
+    assert_eq!(loan["interest_paid"], 1_200 + 1_105);
+    assert_eq!(loan["schedule"].as_array().unwrap().len(), 10);
+    assert_eq!(loan["next_due"], "2026-04-30");
+
+    // The loan is carried as a receivable in the bank's own books.
+    let outstanding = loan["outstanding"].clone();
+    let output = bank(&["trial-balance", file, "--json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let receivable = json["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|row| row["account"] == "loans-receivable")
+        .unwrap();
+    assert_eq!(receivable["debit"], outstanding);
+    let output = bank(&["trial-balance", file]);
+    assert!(stdout(&output).contains("interest-income"));
+
+    // An account can't be closed while it still owes money on a loan.
+    let output = bank(&["close", file, "Alice"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("of loans outstanding"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_balance_at() {
+    let path = temp_file("p32_cli_balance_at.json");
+    let file = path.to_str().unwrap();
+
+    assert!(
+        bank(&["create", file, "--name", "History"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", file, "Alice", "--balance", "100"])
+            .status
+            .success()
+    );
+    assert!(bank(&["add-user", file, "Bob"]).status.success());
+    assert!(
+        bank(&["transfer", file, "Alice", "Bob", "40"])
+            .status
+            .success()
+    );
+
+    let output = bank(&["balance-at", file, "Alice", "--entry", "2"]);
+    assert_eq!(
+        stdout(&output).trim(),
+        "Balance of Alice at entry #2: €100.00"
+    );
+    let output = bank(&["balance-at", file, "Bob", "--entry", "1", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["balance"], serde_json::Value::Null);
+
+    let output = bank(&["balance-at", file, "Bob", "--at", "2999-12-31", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["balance"], 4000);
+    let output = bank(&["balance-at", file, "Bob", "--at", "2000-01-01T00:00:00Z"]);
+    assert!(stdout(&output).contains("wasn't open yet"));
+
+    let output = bank(&["balance-at", file, "Bob"]);
+    assert!(!output.status.success());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_import() {
+    let path = temp_file("p32_cli_import.json");
+    let file = path.to_str().unwrap();
+    let users = temp_file("p32_cli_import_users.csv");
+    let transfers = temp_file("p32_cli_import_transfers.csv");
+    std::fs::write(&users, "name,balance\nAlice,100\nBob,0\nAlice,5\n").unwrap();
+    std::fs::write(&transfers, "from,to,amount\nAlice,Bob,30\nBob,Carol,1\n").unwrap();
+
+    assert!(
+        bank(&["create", file, "--name", "Imported"])
+            .status
+            .success()
+    );
+    let output = bank(&[
+        "import",
+        file,
+        users.to_str().unwrap(),
+        "--format",
+        "users-csv",
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("Imported 2 users and 0 transfers, 1 failed"));
+    assert!(stdout(&output).contains("line 4: User 'Alice' already exists"));
+
+    let args = [
+        "import",
+        file,
+        transfers.to_str().unwrap(),
+        "--format",
+        "transfers-csv",
+    ];
+    let output = bank(&[&args[..], &["--dry-run", "--json"]].concat());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["transfers"], serde_json::json!([3]));
+    assert_eq!(json["failures"][0]["line"], 3);
+    let output = bank(&["report", file, "--json"]);
+    assert!(stdout(&output).contains("\"liabilities\":10000"));
+
+    assert!(bank(&args).status.success());
+    let output = bank(&["balance-at", file, "Bob", "--entry", "3"]);
+    assert!(stdout(&output).contains("€30.00"));
+
+    let output = bank(&["import", file, users.to_str().unwrap(), "--format", "xml"]);
+    assert!(!output.status.success());
+
+    for path in [&path, &users, &transfers] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_cli_overdraft() {
+    let path = temp_file("p32_cli_overdraft.json");
+    let file = path.to_str().unwrap();
+
+    assert!(
+        bank(&["create", file, "--name", "Overdraft"])
+            .status
+            .success()
+    );
+    assert!(
+        bank(&["add-user", file, "Alice", "--credit-line", "100"])
+            .status
+            .success()
+    );
+    assert!(bank(&["add-user", file, "Bob"]).status.success());
+
+    let output = bank(&[
+        "set-overdraft",
+        file,
+        "Alice",
+        "soft",
+        "--limit",
+        "50",
+        "--fee",
+        "2.50",
+        "--grace",
+        "10",
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Alice: overdraft soft up to 50.00"));
+
+    let output = bank(&["transfer", file, "Alice", "Bob", "130", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["from"]["balance"], -13_250);
+    let output = bank(&["transfer", file, "Alice", "Bob", "20"]);
+    assert!(!output.status.success());
+
+    let output = bank(&[
+        "set-overdraft",
+        file,
+        "Alice",
+        "soft",
+        "--fee",
+        "1",
+        "--fee-percent",
+        "100",
+    ]);
+    assert!(!output.status.success());
+    let output = bank(&["set-overdraft", file, "Alice", "hard", "--json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["overdraft"]["type"], "hard");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cli_usage_errors() {
+    let output = bank(&[]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = bank(&["transfer", "bank.json", "Alice", "Bob", "1.00", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'transfer' doesn't support --dry-run")
+    );
+
+    let output = bank(&["report", "/nonexistent/p32-bank-file"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Cannot load"));
+}
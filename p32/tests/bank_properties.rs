@@ -0,0 +1,338 @@
+use p32::bank::{Bank, BankError, MergePolicy, Money, User};
+use proptest::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+const NAMES: [&str; 5] = ["Alice", "Bob", "Carol", "Dave", "Eve"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    AddUser {
+        name: usize,
+        credit_line: i64,
+        balance: i64,
+    },
+    Transfer {
+        from: usize,
+        to: usize,
+        amount: i64,
+    },
+    Accrue,
+    // Merges in a bank holding these users, by name.
+    Merge {
+        users: BTreeMap<usize, (i64, i64)>,
+        credit_interest: u64,
+        debit_interest: u64,
+    },
+}
+
+// What the bank should do, worked out with plain integers. Names are unique
+// in these tests, so accounts are keyed by them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Model {
+    credit_interest: u64,
+    debit_interest: u64,
+    // Credit line and balance, in minor units.
+    users: BTreeMap<String, (i64, i64)>,
+}
+
+impl Model {
+    fn add_user(&mut self, name: &str, credit_line: i64, balance: i64) -> Result<(), BankError> {
+        if self.users.contains_key(name) {
+            return Err(BankError::DuplicateUser(name.to_string()));
+        }
+        if credit_line < 0 {
+            return Err(BankError::NegativeCreditLine(name.to_string()));
+        }
+        self.users.insert(name.to_string(), (credit_line, balance));
+        Ok(())
+    }
+
+    fn transfer(&mut self, from: &str, to: &str, amount: i64) -> Result<(), BankError> {
+        let Some(&(credit_line, from_balance)) = self.users.get(from) else {
+            return Err(BankError::UserNotFound(from.to_string()));
+        };
+        if !self.users.contains_key(to) {
+            return Err(BankError::UserNotFound(to.to_string()));
+        }
+        if amount < 0 {
+            return Err(BankError::NegativeAmount(Money::from_minor(amount)));
+        }
+
+        let debited = from_balance
+            .checked_sub(amount)
+            .ok_or_else(|| BankError::TransferOverflow(from.to_string()))?;
+        if debited < -credit_line {
+            return Err(BankError::InsufficientCredit {
+                user: from.to_string(),
+                amount: Money::from_minor(amount),
+            });
+        }
+        let to_balance = if from == to {
+            debited
+        } else {
+            self.users[to].1
+        };
+        let credited = to_balance
+            .checked_add(amount)
+            .ok_or_else(|| BankError::TransferOverflow(to.to_string()))?;
+
+        self.users.get_mut(from).unwrap().1 = debited;
+        self.users.get_mut(to).unwrap().1 = credited;
+        Ok(())
+    }
+
+    // On overflow, returns every account whose interest overflows; the bank
+    // may report any one of them.
+    fn accrue(&mut self) -> Result<(), BTreeSet<String>> {
+        let mut accrued = self.users.clone();
+        let mut overflowed = BTreeSet::new();
+        for (name, (_, balance)) in accrued.iter_mut() {
+            let rate = if *balance < 0 {
+                self.credit_interest
+            } else {
+                self.debit_interest
+            };
+            let interest = i128::from(*balance) * i128::from(rate) / 10000;
+            match i64::try_from(i128::from(*balance) + interest) {
+                Ok(result) if balance.unsigned_abs().checked_mul(rate).is_some() => {
+                    *balance = result
+                }
+                _ => {
+                    overflowed.insert(name.clone());
+                }
+            }
+        }
+
+        if !overflowed.is_empty() {
+            return Err(overflowed);
+        }
+        self.users = accrued;
+        Ok(())
+    }
+
+    fn merge(
+        &mut self,
+        users: &BTreeMap<String, (i64, i64)>,
+        credit_interest: u64,
+        debit_interest: u64,
+    ) -> Result<(), BankError> {
+        let mut merged = self.users.clone();
+        for (name, &(credit_line, balance)) in users {
+            match merged.get_mut(name) {
+                // Combined accounts keep their own credit line.
+                Some((_, existing)) => {
+                    *existing = existing
+                        .checked_add(balance)
+                        .ok_or_else(|| BankError::MergeOverflow(name.clone()))?;
+                }
+                None => {
+                    merged.insert(name.clone(), (credit_line, balance));
+                }
+            }
+        }
+
+        self.users = merged;
+        self.credit_interest = (self.credit_interest + credit_interest) / 2;
+        self.debit_interest = (self.debit_interest + debit_interest) / 2;
+        Ok(())
+    }
+}
+
+fn snapshot(bank: &Bank) -> BTreeMap<String, (i64, i64)> {
+    bank.users
+        .values()
+        .map(|user| {
+            (
+                user.name.clone(),
+                (user.credit_line.minor(), user.balance.minor()),
+            )
+        })
+        .collect()
+}
+
+fn total(users: &BTreeMap<String, (i64, i64)>) -> i128 {
+    users
+        .values()
+        .map(|&(_, balance)| i128::from(balance))
+        .sum()
+}
+
+fn apply(bank: &mut Bank, op: &Op) -> Result<(), BankError> {
+    match op {
+        Op::AddUser {
+            name,
+            credit_line,
+            balance,
+        } => bank
+            .add_user(User::new(
+                NAMES[*name].to_string(),
+                Money::from_minor(*credit_line),
+                Money::from_minor(*balance),
+            ))
+            .map(|_| ()),
+        Op::Transfer { from, to, amount } => {
+            bank.transfer_funds(NAMES[*from], NAMES[*to], Money::from_minor(*amount))
+        }
+        Op::Accrue => bank.accrue_interest(),
+        Op::Merge {
+            users,
+            credit_interest,
+            debit_interest,
+        } => {
+            let mut other = Bank::new("Other".to_string(), *credit_interest, *debit_interest);
+            for (name, &(credit_line, balance)) in users {
+                other
+                    .add_user(User::new(
+                        NAMES[*name].to_string(),
+                        Money::from_minor(credit_line),
+                        Money::from_minor(balance),
+                    ))
+                    .unwrap();
+            }
+            bank.merge_bank_with(other, &MergePolicy::default())
+                .map(|_| ())
+        }
+    }
+}
+
+// Mostly everyday amounts, with some right at the edges of the range so
+// overflows come up.
+fn amount() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        6 => 0..=100_000i64,
+        1 => -1000..0i64,
+        1 => (i64::MAX - 100_000)..=i64::MAX,
+        1 => i64::MIN..=(i64::MIN + 100_000),
+    ]
+}
+
+fn credit_line() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        6 => 0..=100_000i64,
+        1 => Just(-1i64),
+        1 => (i64::MAX - 100_000)..=i64::MAX,
+    ]
+}
+
+fn rate() -> impl Strategy<Value = u64> {
+    prop_oneof![0..=3u64, 0..=2000u64]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let name = 0..NAMES.len();
+    prop_oneof![
+        3 => (name.clone(), credit_line(), amount()).prop_map(|(name, credit_line, balance)| {
+            Op::AddUser {
+                name,
+                credit_line,
+                balance,
+            }
+        }),
+        6 => (name.clone(), name.clone(), amount())
+            .prop_map(|(from, to, amount)| Op::Transfer { from, to, amount }),
+        1 => Just(Op::Accrue),
+        1 => (
+            prop::collection::btree_map(name, (0..=100_000i64, amount()), 0..3),
+            rate(),
+            rate(),
+        )
+            .prop_map(|(users, credit_interest, debit_interest)| Op::Merge {
+                users,
+                credit_interest,
+                debit_interest,
+            }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn bank_matches_model(
+        credit_interest in rate(),
+        debit_interest in rate(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        let mut bank = Bank::new("Test Bank".to_string(), credit_interest, debit_interest);
+        let mut model = Model {
+            credit_interest,
+            debit_interest,
+            users: BTreeMap::new(),
+        };
+
+        for op in &ops {
+            let before = snapshot(&bank);
+            let result = apply(&mut bank, op);
+
+            match op {
+                Op::AddUser { name, credit_line, balance } => {
+                    prop_assert_eq!(&result, &model.add_user(NAMES[*name], *credit_line, *balance));
+                }
+                Op::Transfer { from, to, amount } => {
+                    prop_assert_eq!(&result, &model.transfer(NAMES[*from], NAMES[*to], *amount));
+                    if result.is_ok() {
+                        // Money only moves between accounts, and never
+                        // takes the sender past its credit line.
+                        prop_assert_eq!(total(&before), total(&snapshot(&bank)));
+                        let sender = bank.user(NAMES[*from]).unwrap();
+                        prop_assert!(sender.balance.minor() >= -sender.credit_line.minor());
+                    }
+                }
+                Op::Accrue => match model.accrue() {
+                    Ok(()) => prop_assert_eq!(&result, &Ok(())),
+                    Err(overflowed) => prop_assert!(
+                        matches!(&result, Err(BankError::InterestOverflow(name)) if overflowed.contains(name)),
+                        "expected an interest overflow for one of {:?}, got {:?}",
+                        overflowed,
+                        result
+                    ),
+                },
+                Op::Merge { users, credit_interest, debit_interest } => {
+                    let users = users
+                        .iter()
+                        .map(|(name, user)| (NAMES[*name].to_string(), *user))
+                        .collect();
+                    prop_assert_eq!(&result, &model.merge(&users, *credit_interest, *debit_interest));
+                }
+            }
+
+            // A failed operation leaves everything as it was.
+            if result.is_err() {
+                prop_assert_eq!(&before, &snapshot(&bank));
+            }
+            prop_assert_eq!(&model.users, &snapshot(&bank));
+            prop_assert_eq!(model.credit_interest, bank.credit_interest);
+            prop_assert_eq!(model.debit_interest, bank.debit_interest);
+            prop_assert!(bank.reconcile().unwrap().is_empty());
+        }
+    }
+
+    // Two banks given the same operations end up the same, errors included,
+    // whatever order their accounts happen to be stored in.
+    #[test]
+    fn bank_is_deterministic(
+        credit_interest in rate(),
+        debit_interest in rate(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        let mut first = Bank::new("Test Bank".to_string(), credit_interest, debit_interest);
+        let mut second = Bank::new("Test Bank".to_string(), credit_interest, debit_interest);
+
+        for op in &ops {
+            prop_assert_eq!(apply(&mut first, op), apply(&mut second, op));
+        }
+
+        let accounts = |bank: &Bank| {
+            let mut users: Vec<User> = bank.users.values().cloned().collect();
+            users.sort_by(|a, b| a.id.cmp(&b.id));
+            users
+        };
+        prop_assert_eq!(accounts(&first), accounts(&second));
+        let entries = |bank: &Bank| {
+            bank.ledger()
+                .entries()
+                .iter()
+                .map(|entry| (entry.kind, entry.from.clone(), entry.to.clone(), entry.amount))
+                .collect::<Vec<_>>()
+        };
+        prop_assert_eq!(entries(&first), entries(&second));
+    }
+}
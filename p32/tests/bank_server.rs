@@ -0,0 +1,275 @@
+use p32::bank::{Bank, Money, StorageFormat, User};
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+// A server on a free localhost port, killed when dropped.
+struct TestServer {
+    child: Child,
+    addr: String,
+}
+
+impl TestServer {
+    fn start(file: &str) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bank-server"))
+            .args([file, "--addr", "127.0.0.1:0"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to run bank-server binary");
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("Listening on http://")
+            .unwrap_or_else(|| panic!("Unexpected greeting '{}'", line))
+            .to_string();
+
+        TestServer { child, addr }
+    }
+
+    // Sends one request and returns the status, headers and JSON body.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (u16, String, Value) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            self.addr,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (
+            status,
+            head.to_string(),
+            serde_json::from_str(body).unwrap(),
+        )
+    }
+
+    fn get(&self, path: &str) -> (u16, Value) {
+        let (status, _, body) = self.request("GET", path, &[], None);
+        (status, body)
+    }
+
+    fn post(&self, path: &str, body: Value) -> (u16, Value) {
+        let (status, _, body) = self.request("POST", path, &[], Some(body));
+        (status, body)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+}
+
+// Where the server keeps idempotency keys for the bank at `path`.
+fn keys_file(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.idempotency.json", path.display()))
+}
+
+fn bank_file(name: &str) -> PathBuf {
+    let path = temp_file(name);
+    let mut bank = Bank::new("Server Bank".to_string(), 0, 1000);
+    bank.add_user(User::new(
+        "Alice".to_string(),
+        Money::from_minor(500),
+        Money::from_minor(10_000),
+    ))
+    .unwrap();
+    bank.save(&path, StorageFormat::Json).unwrap();
+    path
+}
+
+#[test]
+fn test_server_users_and_report() {
+    let path = bank_file("p32_server_users.json");
+    let server = TestServer::start(path.to_str().unwrap());
+
+    let (status, json) = server.post(
+        "/users",
+        json!({ "name": "Bob Smith", "credit_line": 2000, "currency": "EUR" }),
+    );
+    assert_eq!(status, 201);
+    assert_eq!(json["id"], "PB7700000000000002");
+    assert_eq!(json["currency"], "EUR");
+
+    let (status, json) = server.post("/users", json!({ "name": "Alice" }));
+    assert_eq!(status, 409);
+    assert!(json["error"].as_str().unwrap().contains("already exists"));
+    let (status, _) = server.post("/users", json!({ "balance": 10 }));
+    assert_eq!(status, 400);
+
+    // A second account for an existing customer may share the name.
+    let (status, json) = server.post("/users", json!({ "name": "Alice", "customer": 1 }));
+    assert_eq!(status, 201);
+    assert_eq!(json["customer"], 1);
+
+    let (status, json) = server.get("/users");
+    assert_eq!(status, 200);
+    assert_eq!(json["users"].as_array().unwrap().len(), 3);
+
+    let (status, json) = server.get("/users/Bob%20Smith");
+    assert_eq!(status, 200);
+    assert_eq!(json["credit_line"], 2000);
+    let (status, _) = server.get("/users/Alice");
+    assert_eq!(status, 409);
+    let (status, json) = server.get("/users/PB0700000000000001");
+    assert_eq!(status, 200);
+    assert_eq!(json["balance"], 10_000);
+    let (status, _) = server.get("/users/Nobody");
+    assert_eq!(status, 404);
+
+    let (status, json) = server.post("/interest", json!({}));
+    assert_eq!(status, 200);
+    assert_eq!(json["accruals"][0]["amount"], 1000);
+
+    let (status, json) = server.get("/report");
+    assert_eq!(status, 200);
+    assert_eq!(json["users"], 3);
+    assert_eq!(json["total_liabilities"], 11_000);
+
+    let (status, _) = server.get("/nowhere");
+    assert_eq!(status, 404);
+
+    // Every change is saved as it happens.
+    let bank = Bank::load(&path).unwrap();
+    assert_eq!(bank.users.len(), 3);
+    assert_eq!(
+        bank.user("PB0700000000000001").unwrap().balance,
+        Money::from_minor(11_000)
+    );
+
+    drop(server);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_server_idempotent_transfers() {
+    let path = bank_file("p32_server_transfers.json");
+    let server = TestServer::start(path.to_str().unwrap());
+    server.post("/users", json!({ "name": "Bob" }));
+
+    let transfer = json!({ "from": "Alice", "to": "Bob", "amount": 2500 });
+    let key = [("Idempotency-Key", "transfer-1")];
+    let (status, head, first) = server.request("POST", "/transfers", &key, Some(transfer.clone()));
+    assert_eq!(status, 200);
+    assert!(!head.contains("Idempotent-Replayed"));
+    assert_eq!(first["to"]["balance"], 2500);
+
+    // The retry is answered from the first attempt and moves no money.
+    let (status, head, retry) = server.request("POST", "/transfers", &key, Some(transfer.clone()));
+    assert_eq!(status, 200);
+    assert!(head.contains("Idempotent-Replayed: true"));
+    assert_eq!(retry, first);
+    let (_, bob) = server.get("/users/Bob");
+    assert_eq!(bob["balance"], 2500);
+
+    let (status, _, json) = server.request(
+        "POST",
+        "/transfers",
+        &key,
+        Some(json!({ "from": "Alice", "to": "Bob", "amount": 1 })),
+    );
+    assert_eq!(status, 422);
+    assert!(json["error"].as_str().unwrap().contains("Idempotency key"));
+
+    // Without a key every request is a new transfer.
+    server.post("/transfers", transfer.clone());
+    server.post("/transfers", transfer);
+    let (_, bob) = server.get("/users/Bob");
+    assert_eq!(bob["balance"], 7500);
+
+    // Failed transfers aren't remembered, so the same key can be retried.
+    let large = json!({ "from": "Alice", "to": "Bob", "amount": 5000 });
+    let key = [("Idempotency-Key", "transfer-2")];
+    let (status, _, json) = server.request("POST", "/transfers", &key, Some(large.clone()));
+    assert_eq!(status, 422);
+    assert!(
+        json["error"]
+            .as_str()
+            .unwrap()
+            .contains("Insufficient credit")
+    );
+    server.post(
+        "/transfers",
+        json!({ "from": "Bob", "to": "Alice", "amount": 3000 }),
+    );
+    let (status, _, _) = server.request("POST", "/transfers", &key, Some(large));
+    assert_eq!(status, 200);
+
+    let (status, _) = server.post(
+        "/transfers",
+        json!({ "from": "Alice", "to": "Nobody", "amount": 1 }),
+    );
+    assert_eq!(status, 404);
+
+    drop(server);
+    let bank = Bank::load(&path).unwrap();
+    assert_eq!(bank.ledger().len(), 7);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(keys_file(&path)).unwrap();
+}
+
+#[test]
+fn test_server_idempotency_survives_restart() {
+    let path = bank_file("p32_server_restart.json");
+    let server = TestServer::start(path.to_str().unwrap());
+    server.post("/users", json!({ "name": "Bob" }));
+    let before_transfer = std::fs::read(&path).unwrap();
+
+    let transfer = json!({ "from": "Alice", "to": "Bob", "amount": 2500 });
+    let key = [("Idempotency-Key", "transfer-1")];
+    let (status, _, first) = server.request("POST", "/transfers", &key, Some(transfer.clone()));
+    assert_eq!(status, 200);
+    drop(server);
+
+    // A retry after a restart is still answered from the first attempt.
+    let server = TestServer::start(path.to_str().unwrap());
+    let (status, head, retry) = server.request("POST", "/transfers", &key, Some(transfer.clone()));
+    assert_eq!(status, 200);
+    assert!(head.contains("Idempotent-Replayed: true"));
+    assert_eq!(retry, first);
+    let (_, bob) = server.get("/users/Bob");
+    assert_eq!(bob["balance"], 2500);
+    drop(server);
+
+    // A server that stopped after saving the key but before saving the bank
+    // forgets the key, so the retry moves the money this time.
+    std::fs::write(&path, before_transfer).unwrap();
+    let server = TestServer::start(path.to_str().unwrap());
+    let (status, head, retry) = server.request("POST", "/transfers", &key, Some(transfer));
+    assert_eq!(status, 200);
+    assert!(!head.contains("Idempotent-Replayed"));
+    assert_eq!(retry["to"]["balance"], 2500);
+    drop(server);
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(keys_file(&path)).unwrap();
+}